@@ -1,38 +1,154 @@
 use crate::{
     line_end::LineEnd,
-    patch::{Diff, Hunk, Line},
-    utils::{LineIter, Text},
+    patch::{Diff, Hunk, HunkRange, Line, Patch, PatchFormatter},
+    utils::{self, LineIter, Text},
+};
+use std::{
+    borrow::{Borrow, Cow},
+    collections::BTreeMap,
+    fmt, fs, io, iter, ops,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
-use std::{fmt, iter};
 
 /// An error returned when [`apply`]ing a `Patch` fails
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct ApplyError(usize, String);
-
-impl fmt::Debug for ApplyError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_tuple("ApplyError")
-            .field(&self.0)
-            .field(&self.1)
-            .finish()
-    }
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ApplyError {
+    /// The context of a hunk could not be found in the target
+    ContextMismatch {
+        /// The index of the hunk in [`Diff::hunks`]
+        hunk_index: usize,
+        /// The formatted hunk
+        hunk: String,
+        /// The 1-based number of the first line of the target which doesn't match the hunk at the
+        /// position given by its header
+        ///
+        /// If all lines match there (e.g. because an earlier hunk already changed them), this is
+        /// the first line of the hunk.
+        at_line: usize,
+        /// The line the hunk expected at `at_line`, without its line ending
+        expected: String,
+        /// The line found at `at_line`, without its line ending, or an empty string if the target
+        /// ends before it
+        found: String,
+    },
+    /// Searching for the position of a hunk exceeded
+    /// [`ApplyConfig::max_match_comparisons`]
+    MatchBudgetExceeded {
+        /// The index of the hunk in [`Diff::hunks`]
+        hunk_index: usize,
+        /// The formatted hunk
+        hunk: String,
+    },
+    /// A hunk was requested by an index past the end of [`Diff::hunks`], see [`Diff::apply_hunk`]
+    HunkIndexOutOfBounds {
+        /// The requested index
+        hunk_index: usize,
+        /// The number of hunks of the diff
+        hunk_count: usize,
+    },
+    /// The old ranges of two consecutive hunks overlap, so they would modify the same lines
+    OverlappingHunks {
+        /// The index of the first hunk in [`Diff::hunks`]
+        first: usize,
+        /// The index of the second hunk in [`Diff::hunks`]
+        second: usize,
+    },
+    /// The diff is for a binary file, whose changes can't be applied, see [`Diff::is_binary`]
+    BinaryNotSupported,
 }
 
 impl fmt::Display for ApplyError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(
-            f,
-            "error applying hunk #{}: could not find context in target file",
-            self.0
-        )?;
+        let (hunk_index, hunk, reason) = match self {
+            ApplyError::HunkIndexOutOfBounds {
+                hunk_index,
+                hunk_count,
+            } => {
+                return write!(
+                    f,
+                    "error applying hunk #{}: the diff only has {hunk_count} hunks",
+                    hunk_index + 1
+                );
+            }
+            ApplyError::OverlappingHunks { first, second } => {
+                return write!(
+                    f,
+                    "error applying hunk #{}: it overlaps with hunk #{}",
+                    second + 1,
+                    first + 1
+                );
+            }
+            ApplyError::BinaryNotSupported => {
+                return write!(f, "error applying diff: binary files are not supported");
+            }
+            ApplyError::ContextMismatch {
+                hunk_index, hunk, ..
+            } => (hunk_index, hunk, "could not find context in target file"),
+            ApplyError::MatchBudgetExceeded { hunk_index, hunk } => (
+                hunk_index,
+                hunk,
+                "exceeded the maximum number of line comparisons",
+            ),
+        };
+        writeln!(f, "error applying hunk #{}: {}", hunk_index + 1, reason)?;
         writeln!(f)?;
         writeln!(f, "Hunk content:")?;
-        write!(f, "{}", self.1)
+        write!(f, "{}", hunk)
     }
 }
 
 impl std::error::Error for ApplyError {}
 
+impl ApplyError {
+    /// Create a [`ApplyError::ContextMismatch`] for the hunk at `hunk_index`, comparing it against
+    /// `target`, the lines of the target starting at the position given by the hunk's header
+    pub(crate) fn context_mismatch<'t, T>(
+        hunk_index: usize,
+        hunk: &Hunk<'_, T>,
+        mut target: impl Iterator<Item = &'t T>,
+        options: &MatchOptions,
+    ) -> Self
+    where
+        T: Text + AsRef<[u8]> + ToOwned + ?Sized + 't,
+    {
+        let lossy = |line: &T| String::from_utf8_lossy(line.as_ref()).into_owned();
+        let pre_image = hunk.lines().iter().filter_map(|line| match line {
+            Line::Context((line, _)) | Line::Delete((line, _)) => Some(*line),
+            Line::Insert(_) => None,
+        });
+
+        let start = hunk.old_range().index() + 1;
+        let mut first = None;
+        let mut mismatch = None;
+        for (i, expected) in pre_image.enumerate() {
+            let found = target.next();
+            let pair = (
+                start + i,
+                lossy(expected),
+                found.map(lossy).unwrap_or_default(),
+            );
+            if !found.is_some_and(|found| expected.matches(found, options)) {
+                mismatch = Some(pair);
+                break;
+            }
+            first.get_or_insert(pair);
+        }
+        let (at_line, expected, found) =
+            mismatch
+                .or(first)
+                .unwrap_or((start, String::new(), String::new()));
+
+        ApplyError::ContextMismatch {
+            hunk_index,
+            hunk: hunk_to_string(hunk),
+            at_line,
+            expected,
+            found,
+        }
+    }
+}
+
 /// Statistics for a single hunk application
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct HunkStats {
@@ -42,6 +158,31 @@ struct HunkStats {
     deleted: usize,
     /// Number of context lines in this hunk
     context: usize,
+    /// Number of context lines which matched the target
+    context_matched: usize,
+}
+
+impl HunkStats {
+    /// Count the changes in a hunk
+    fn new<T: ?Sized + Text + ToOwned>(hunk: &Hunk<'_, T>) -> Self {
+        let mut stats = Self {
+            added: 0,
+            deleted: 0,
+            context: 0,
+            context_matched: 0,
+        };
+
+        for line in hunk.lines() {
+            match line {
+                Line::Insert(_) => stats.added += 1,
+                Line::Delete(_) => stats.deleted += 1,
+                Line::Context(_) => stats.context += 1,
+            }
+        }
+        stats.context_matched = stats.context;
+
+        stats
+    }
 }
 
 /// Statistics about the changes made when applying a patch
@@ -55,6 +196,14 @@ pub struct ApplyStats {
     pub lines_context: usize,
     /// Number of hunks successfully applied
     pub hunks_applied: usize,
+    /// Number of context lines which matched the target exactly where the hunks were applied.
+    ///
+    /// Lines are compared using the [`MatchOptions`] of the [`FuzzyConfig`]. Context lines which
+    /// were ignored due to fuzz (see [`FuzzyConfig::max_fuzz`]) or only accepted because they are
+    /// similar to the line in the target are not counted, so this is lower than
+    /// [`lines_context`](Self::lines_context) the more the patch had to be fuzzed to apply. Hunks
+    /// applied at an offset still count their context lines as matched.
+    pub context_lines_matched: usize,
 }
 
 impl ApplyStats {
@@ -65,6 +214,7 @@ impl ApplyStats {
             lines_deleted: 0,
             lines_context: 0,
             hunks_applied: 0,
+            context_lines_matched: 0,
         }
     }
 
@@ -74,12 +224,264 @@ impl ApplyStats {
         self.lines_deleted += hunk_stats.deleted;
         self.lines_context += hunk_stats.context;
         self.hunks_applied += 1;
+        self.context_lines_matched += hunk_stats.context_matched;
     }
 
     /// Returns whether any changes were made
     pub fn has_changes(&self) -> bool {
         self.lines_added > 0 || self.lines_deleted > 0
     }
+
+    /// Returns the share of added and deleted lines among all lines of the applied hunks, between
+    /// `0.0` and `1.0`
+    ///
+    /// Only the context lines of the hunks are taken into account, so this is relative to the whole
+    /// file only if the hunks cover all of its lines. Returns `0.0` if there are no lines at all.
+    ///
+    /// ```
+    /// use flickzeug::{Diff, apply};
+    ///
+    /// let diff = Diff::from_str("@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n").unwrap();
+    /// let (_, stats) = apply("a\nb\nc\n", &diff).unwrap();
+    /// assert_eq!(stats.fraction_changed(), 0.5);
+    /// ```
+    pub fn fraction_changed(&self) -> f64 {
+        let changed = self.lines_added + self.lines_deleted;
+        let total = changed + self.lines_context;
+        if total == 0 {
+            return 0.0;
+        }
+        changed as f64 / total as f64
+    }
+}
+
+impl<T: ?Sized + Text + ToOwned> Diff<'_, T> {
+    /// Returns the statistics that successfully applying this diff would produce.
+    ///
+    /// These are derived purely from the line counts of the hunks, without applying the diff, so
+    /// all context lines are counted as matched.
+    pub fn stats(&self) -> ApplyStats {
+        let mut stats = ApplyStats::new();
+        for hunk in self.hunks() {
+            stats.add_hunk(HunkStats::new(hunk));
+        }
+        stats
+    }
+
+    /// Returns the statistics that successfully applying this diff in reverse would produce.
+    ///
+    /// Like [`Diff::stats`] these are derived purely from the line counts of the hunks, with added
+    /// and deleted lines swapped, so no reversed `Diff` needs to be constructed.
+    pub fn reverse_stats(&self) -> ApplyStats {
+        let stats = self.stats();
+        ApplyStats {
+            lines_added: stats.lines_deleted,
+            lines_deleted: stats.lines_added,
+            ..stats
+        }
+    }
+}
+
+/// Returns the statistics of every file in a patch, in the order the files appear.
+///
+/// Each file is named by its [new path](Diff::new_path), or by its [old path](Diff::old_path) if
+/// the file is deleted, and is `None` if the diff doesn't name a file. See [`Diff::stats`].
+///
+/// ```
+/// use flickzeug::{patch_from_str, stats_by_file};
+///
+/// let patch = patch_from_str(
+///     "\
+/// --- a/src/lib.rs
+/// +++ b/src/lib.rs
+/// @@ -1 +1,2 @@
+///  mod foo;
+/// +mod bar;
+/// ",
+/// )
+/// .unwrap();
+///
+/// for (path, stats) in stats_by_file(&patch) {
+///     let path = path.unwrap_or("<unnamed>");
+///     println!("{path} +{} -{}", stats.lines_added, stats.lines_deleted);
+/// }
+/// ```
+pub fn stats_by_file<'p, T: ?Sized + Text + ToOwned>(
+    patch: &'p Patch<'_, T>,
+) -> Vec<(Option<&'p T>, ApplyStats)> {
+    patch
+        .iter()
+        .map(|diff| (diff.new_path().or(diff.old_path()), diff.stats()))
+        .collect()
+}
+
+/// A detailed report about applying a patch, returned by [`apply_with_report`] and
+/// [`apply_bytes_with_report`]
+#[derive(PartialEq, Eq)]
+pub struct ApplyReport<'a, T: ?Sized + ToOwned> {
+    /// Statistics about the applied changes
+    pub stats: ApplyStats,
+    /// Details about every hunk of the patch, in order
+    pub hunks: Vec<HunkReport<'a, T>>,
+    /// The diff as it was actually applied.
+    ///
+    /// Its hunks are placed where they were applied, and contain the lines of the base image
+    /// instead of the context and deleted lines of the patch, which can differ when matching
+    /// fuzzily or with relaxed [`MatchOptions`]. Hunks are sorted by their position. Applying the
+    /// resolved diff to the same base image without fuzz (a [`FuzzyConfig::max_fuzz`] of `0`) and
+    /// otherwise the same configuration reproduces the same result, so it can be stored as an
+    /// exact record of the change.
+    pub resolved: Diff<'a, T>,
+    /// Problems found by the lints enabled in the [`ApplyConfig`], in hunk order
+    pub warnings: Vec<ApplyWarning>,
+}
+
+// Implemented manually since the `Debug` impl of `Diff` has additional bounds
+impl<T> fmt::Debug for ApplyReport<'_, T>
+where
+    T: ?Sized + ToOwned<Owned: fmt::Debug> + fmt::Debug + Text,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApplyReport")
+            .field("stats", &self.stats)
+            .field("hunks", &self.hunks)
+            .field("resolved", &self.resolved)
+            .field("warnings", &self.warnings)
+            .finish()
+    }
+}
+
+/// A problem with a patch which didn't prevent applying it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyWarning {
+    /// A hunk inserted a line which is indented differently from the rest of the target, see
+    /// [`ApplyConfig::lint_indentation`]
+    IndentationMismatch {
+        /// Index of the hunk in the patch
+        hunk: usize,
+        /// The dominant indentation of the target
+        expected: Indentation,
+        /// The indentation of the first inserted line which doesn't match
+        found: Indentation,
+    },
+}
+
+impl fmt::Display for ApplyWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyWarning::IndentationMismatch {
+                hunk,
+                expected,
+                found,
+            } => write!(
+                f,
+                "hunk #{} inserts a line indented with {found}, but the file uses {expected}",
+                hunk + 1
+            ),
+        }
+    }
+}
+
+/// The indentation style of a text or line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indentation {
+    /// Indented with tabs
+    Tabs,
+    /// Indented with spaces, in steps of the given width for a text, or the given number of
+    /// spaces for a single line
+    Spaces(usize),
+}
+
+impl Indentation {
+    /// Infer the dominant indentation of some lines, or `None` if none of them are indented
+    ///
+    /// Lines starting with a tab count as tab indented. The width of space indentation is the
+    /// most common change in indentation between consecutive non-blank lines, ignoring the ones
+    /// indented with tabs.
+    fn detect<'a, T: ?Sized + Text + 'a>(lines: impl Iterator<Item = &'a T>) -> Option<Self> {
+        let mut tabs = 0;
+        let mut spaces = 0;
+        let mut widths: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut previous = 0;
+
+        for line in lines {
+            let (indent, rest) = split_indent(line);
+            if rest.is_empty() {
+                continue;
+            }
+            let indent = indent.as_bytes();
+            if indent.first() == Some(&b'\t') {
+                tabs += 1;
+                continue;
+            }
+            let width = indent.len();
+            if width > 0 {
+                spaces += 1;
+            }
+            if width != previous {
+                *widths.entry(width.abs_diff(previous)).or_default() += 1;
+            }
+            previous = width;
+        }
+
+        if tabs == 0 && spaces == 0 {
+            None
+        } else if tabs >= spaces {
+            Some(Indentation::Tabs)
+        } else {
+            // Prefer the smaller width on ties
+            let (width, _) = widths
+                .into_iter()
+                .rev()
+                .max_by_key(|&(_, count)| count)
+                .expect("an indented line changes the indentation");
+            Some(Indentation::Spaces(width))
+        }
+    }
+
+    /// Returns the indentation of `line` if it doesn't follow this style
+    fn mismatch<T: ?Sized + Text>(self, indent: &T) -> Option<Self> {
+        let indent = indent.as_bytes();
+        let found = match indent.first() {
+            None => return None,
+            Some(b'\t') => Indentation::Tabs,
+            Some(_) => Indentation::Spaces(indent.iter().take_while(|&&b| b == b' ').count()),
+        };
+        match (self, found) {
+            (Indentation::Tabs, Indentation::Tabs) => None,
+            (Indentation::Spaces(width), Indentation::Spaces(found)) if found % width == 0 => None,
+            _ => Some(found),
+        }
+    }
+}
+
+impl fmt::Display for Indentation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Indentation::Tabs => f.write_str("tabs"),
+            Indentation::Spaces(1) => f.write_str("1 space"),
+            Indentation::Spaces(width) => write!(f, "{width} spaces"),
+        }
+    }
+}
+
+/// Details about how a single hunk was applied
+#[derive(Debug, PartialEq, Eq)]
+pub struct HunkReport<'a, T: ?Sized> {
+    /// The range of lines in the base image which the hunk was applied to.
+    ///
+    /// This follows the same conventions as [`Hunk::old_range`] and can differ from the range
+    /// declared by the hunk if it was applied at an offset.
+    pub old_range: HunkRange,
+    /// The lines of the base image which were deleted by the hunk.
+    ///
+    /// These are the actual lines of the base image, which can differ from the deleted lines of
+    /// the hunk when matching fuzzily or with relaxed [`MatchOptions`]. Together with
+    /// [`HunkReport::old_range`] they allow restoring the base image without the inverse diff.
+    pub replaced: Vec<(&'a T, Option<LineEnd>)>,
+    /// The number of lines the hunk was moved from the position given by its header, negative if
+    /// it was applied earlier in the base image, see [`ApplyConfig::max_offset`].
+    pub offset: isize,
 }
 
 /// Result of applying a patch with statistics
@@ -99,13 +501,96 @@ impl ApplyStats {
 /// ```
 pub type ApplyResult<T, E = ApplyError> = Result<(T, ApplyStats), E>;
 
+/// Recognizes lines by their content, see [`ApplyConfig::volatile_lines`]
+type LinePredicate = dyn Fn(&[u8]) -> bool + Send + Sync;
+
 /// Configuration for patch application
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Clone)]
 pub struct ApplyConfig {
     /// Configuration of line end handling
     pub line_end_strategy: LineEndHandling,
     /// Configuration of fuzzy matching
     pub fuzzy_config: FuzzyConfig,
+    /// Maximum number of line comparisons to perform while searching for the positions of all
+    /// hunks of a patch.
+    ///
+    /// Searching for a hunk which was moved far from its original position, or which doesn't apply
+    /// at all, compares the hunk against every position of the target, and with fuzzy matching
+    /// against every combination of ignored context lines. Setting a budget bounds the cost of
+    /// applying untrusted patches to large files: once it is used up, applying fails with
+    /// [`ApplyError::MatchBudgetExceeded`]. Each position tried counts as one comparison per line
+    /// of the hunk's pre-image.
+    ///
+    /// Defaults to `None`, which doesn't limit the number of comparisons.
+    pub max_match_comparisons: Option<usize>,
+    /// Keep the indentation of the target file on changed lines.
+    ///
+    /// Within every block of changes between context lines, the leading whitespace of each
+    /// inserted line is replaced with the leading whitespace of the deleted line at the same
+    /// position in the block, so only the content after the indentation is taken from the patch.
+    /// Inserted lines without a corresponding deleted line, and blank inserted lines, are kept as
+    /// is. This allows applying a change to code which has since been reindented.
+    ///
+    /// Defaults to `false`.
+    pub preserve_indent: bool,
+    /// Check that inserted lines follow the indentation style of the target.
+    ///
+    /// The dominant [`Indentation`] of the base image is inferred from its indented lines. Every
+    /// hunk which inserts a line indented differently gets an
+    /// [`ApplyWarning::IndentationMismatch`] in [`ApplyReport::warnings`]. The patch is applied
+    /// either way.
+    ///
+    /// Defaults to `false`.
+    pub lint_indentation: bool,
+    /// Recognizes context lines which are expected to differ in the target, such as generated
+    /// version or timestamp headers.
+    ///
+    /// Context lines of a patch for which this returns `true` match any line of the target, in
+    /// addition to the lines ignored by [`FuzzyConfig::max_fuzz`]. The lines of the target are kept
+    /// in place of these context lines, while inserted lines are still written as they are in the
+    /// patch. The function is called with the content of the context line, without its line
+    /// ending. Hardened diffs (see [`Diff::harden`]) ignore it.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use flickzeug::ApplyConfig;
+    ///
+    /// let marker = String::from("// Generated at");
+    /// let config = ApplyConfig {
+    ///     volatile_lines: Some(Arc::new(move |line| line.starts_with(marker.as_bytes()))),
+    ///     ..Default::default()
+    /// };
+    /// ```
+    ///
+    /// Defaults to `None`, which requires all context lines to match.
+    pub volatile_lines: Option<Arc<LinePredicate>>,
+    /// Maximum number of lines a hunk may be moved away from the position given by its header.
+    ///
+    /// Hunks which don't match at their declared position, e.g. because lines were added or
+    /// removed earlier in the target, are searched for at increasing distances from it, like GNU
+    /// patch does. When a hunk matches at two positions equally far away, the earlier one is used.
+    /// The distance each hunk was moved is reported in [`HunkReport::offset`].
+    ///
+    /// Defaults to `None`, which searches the whole target.
+    pub max_offset: Option<usize>,
+}
+
+// Implemented manually since the function recognizing volatile lines can't be debug printed
+impl fmt::Debug for ApplyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApplyConfig")
+            .field("line_end_strategy", &self.line_end_strategy)
+            .field("fuzzy_config", &self.fuzzy_config)
+            .field("max_match_comparisons", &self.max_match_comparisons)
+            .field("preserve_indent", &self.preserve_indent)
+            .field("lint_indentation", &self.lint_indentation)
+            .field(
+                "volatile_lines",
+                &self.volatile_lines.as_ref().map(|_| ".."),
+            )
+            .field("max_offset", &self.max_offset)
+            .finish()
+    }
 }
 
 // TODO: Add option to keep previous behaviour.
@@ -163,8 +648,14 @@ pub struct FuzzyConfig {
     pub max_fuzz: usize,
     /// Whether to allow whitespace-only differences in context lines
     pub ignore_whitespace: bool,
+    /// Whether to allow differences in the whitespace at the end of lines, like trailing spaces
+    /// which were removed from a patch sent by email
+    pub ignore_trailing_whitespace: bool,
     /// Whether to perform case-insensitive matching
     pub ignore_case: bool,
+    /// Expand tabs to tab stops every this many columns before comparing lines, see
+    /// [`MatchOptions::expand_tabs`]
+    pub expand_tabs: Option<usize>,
 }
 
 impl Default for FuzzyConfig {
@@ -172,8 +663,122 @@ impl Default for FuzzyConfig {
         Self {
             max_fuzz: 2,
             ignore_whitespace: false,
+            ignore_trailing_whitespace: false,
             ignore_case: false,
+            expand_tabs: None,
+        }
+    }
+}
+
+impl FuzzyConfig {
+    /// Returns the options used for comparing lines
+    pub fn match_options(&self) -> MatchOptions {
+        MatchOptions {
+            ignore_whitespace: self.ignore_whitespace,
+            ignore_trailing_whitespace: self.ignore_trailing_whitespace,
+            ignore_case: self.ignore_case,
+            expand_tabs: self.expand_tabs,
+        }
+    }
+}
+
+/// Options for comparing two lines with each other
+///
+/// Line endings are not part of the comparison.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// Whether to ignore all whitespace in lines
+    pub ignore_whitespace: bool,
+    /// Whether to ignore whitespace at the end of lines
+    ///
+    /// Only ASCII whitespace is ignored, so that lines which aren't valid utf8 are treated the
+    /// same as other lines. A trailing non-breaking space still has to match.
+    pub ignore_trailing_whitespace: bool,
+    /// Whether to compare lines case-insensitively
+    pub ignore_case: bool,
+    /// Expand tabs to tab stops every this many columns before comparing lines
+    ///
+    /// This lets lines indented with tabs match lines indented with spaces, like `\tfoo` and
+    /// `    foo` with a width of 4. Defaults to `None`, which compares tabs as they are.
+    pub expand_tabs: Option<usize>,
+}
+
+impl MatchOptions {
+    /// Returns `true` if the lines `a` and `b` are equal under these options
+    ///
+    /// ```
+    /// use flickzeug::MatchOptions;
+    ///
+    /// let options = MatchOptions {
+    ///     ignore_whitespace: true,
+    ///     ..Default::default()
+    /// };
+    /// assert!(options.matches("let a = 1;", "let a=1;"));
+    /// assert!(!options.matches("let a = 1;", "let A = 1;"));
+    /// ```
+    pub fn matches<T: Text + ?Sized>(&self, a: &T, b: &T) -> bool {
+        a.matches(b, self)
+    }
+
+    /// Normalizes a line so that lines which match under these options are equal
+    pub(crate) fn normalize<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let mut line = Cow::Borrowed(line);
+        if self.ignore_case {
+            line = Cow::Owned(line.to_lowercase());
+        }
+        if let Some(width) = self.expand_tabs
+            && line.contains('\t')
+        {
+            let expanded = utils::expand_tabs(line.as_bytes(), width).into_owned();
+            line =
+                Cow::Owned(String::from_utf8(expanded).expect("expanding tabs keeps utf8 valid"));
+        }
+        if self.ignore_whitespace {
+            line = Cow::Owned(line.chars().filter(|c| !c.is_whitespace()).collect());
+        }
+        if self.ignore_trailing_whitespace {
+            line = match line {
+                Cow::Borrowed(line) => Cow::Borrowed(line.trim_ascii_end()),
+                Cow::Owned(line) => Cow::Owned(line.trim_ascii_end().to_owned()),
+            };
+        }
+        line
+    }
+
+    /// Normalizes a potentially non-utf8 line, falling back to only considering ASCII for
+    /// non-utf8 content
+    pub(crate) fn normalize_bytes<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        if let Ok(line) = std::str::from_utf8(line) {
+            return match self.normalize(line) {
+                Cow::Borrowed(line) => Cow::Borrowed(line.as_bytes()),
+                Cow::Owned(line) => Cow::Owned(line.into_bytes()),
+            };
+        }
+
+        let mut line = Cow::Borrowed(line);
+        if self.ignore_case {
+            line = Cow::Owned(line.to_ascii_lowercase());
+        }
+        if let Some(width) = self.expand_tabs
+            && line.contains(&b'\t')
+        {
+            line = Cow::Owned(utils::expand_tabs(&line, width).into_owned());
         }
+        if self.ignore_whitespace {
+            line = Cow::Owned(
+                line.iter()
+                    .copied()
+                    .filter(|b| !b.is_ascii_whitespace())
+                    .collect(),
+            );
+        }
+        if self.ignore_trailing_whitespace {
+            line = match line {
+                Cow::Borrowed(line) => Cow::Borrowed(line.trim_ascii_end()),
+                Cow::Owned(line) => Cow::Owned(line.trim_ascii_end().to_owned()),
+            };
+        }
+        line
     }
 }
 
@@ -190,24 +795,8 @@ impl FuzzyComparable for str {
     }
 
     fn similarity(&self, other: &Self, config: &ApplyConfig) -> f32 {
-        let (s1, s2) = if config.fuzzy_config.ignore_case {
-            (self.to_lowercase(), other.to_lowercase())
-        } else {
-            (self.to_string(), other.to_string())
-        };
-
-        let (s1, s2) = if config.fuzzy_config.ignore_whitespace {
-            (
-                s1.chars()
-                    .filter(|c| !c.is_whitespace())
-                    .collect::<String>(),
-                s2.chars()
-                    .filter(|c| !c.is_whitespace())
-                    .collect::<String>(),
-            )
-        } else {
-            (s1, s2)
-        };
+        let options = config.fuzzy_config.match_options();
+        let (s1, s2) = (options.normalize(self), options.normalize(other));
 
         if s1 == s2 {
             return 1.0;
@@ -250,25 +839,59 @@ impl FuzzyComparable for [u8] {
 enum ImageLine<'a, T: ?Sized> {
     Unpatched((&'a T, Option<LineEnd>)),
     Patched((&'a T, Option<LineEnd>)),
+    /// A patched line with its indentation replaced, see [`ApplyConfig::preserve_indent`]
+    Reindented {
+        indent: &'a T,
+        line: (&'a T, Option<LineEnd>),
+    },
 }
 
 impl<'a, T: ?Sized + Text> ImageLine<'a, T> {
+    /// Returns the line, excluding the indentation of reindented lines
     fn inner(&self) -> (&T, Option<LineEnd>) {
         match self {
-            ImageLine::Unpatched(inner) | ImageLine::Patched(inner) => *inner,
+            ImageLine::Unpatched(inner)
+            | ImageLine::Patched(inner)
+            | ImageLine::Reindented { line: inner, .. } => *inner,
+        }
+    }
+
+    /// Returns the leading whitespace of the line, or nothing for blank lines
+    fn indent(&self) -> &T {
+        match self {
+            ImageLine::Reindented { indent, .. } => indent,
+            ImageLine::Unpatched((line, _)) | ImageLine::Patched((line, _)) => {
+                match split_indent(*line) {
+                    (_, rest) if rest.is_empty() => rest,
+                    (indent, _) => indent,
+                }
+            }
         }
     }
 
+    /// Returns the line, excluding the indentation of reindented lines
     fn into_inner(self) -> (&'a T, Option<LineEnd>) {
         match self {
-            ImageLine::Unpatched(inner) | ImageLine::Patched(inner) => inner,
+            ImageLine::Unpatched(inner)
+            | ImageLine::Patched(inner)
+            | ImageLine::Reindented { line: inner, .. } => inner,
         }
     }
 
+    /// Returns the pieces making up the line, which are joined without a line ending if there are
+    /// multiple
+    fn into_pieces(self) -> impl Iterator<Item = (&'a T, Option<LineEnd>)> {
+        let indent = match self {
+            ImageLine::Reindented { indent, .. } => Some((indent, None)),
+            _ => None,
+        };
+        indent.into_iter().chain(iter::once(self.into_inner()))
+    }
+
     fn is_patched(&self) -> bool {
         match self {
             ImageLine::Unpatched(_) => false,
-            ImageLine::Patched(_) => true,
+            ImageLine::Patched(_) | ImageLine::Reindented { .. } => true,
         }
     }
 }
@@ -296,6 +919,78 @@ where
     }
 }
 
+impl<T: ApplyText + ?Sized> Diff<'_, T> {
+    /// Check whether the diff applies to a base image with default fuzzy matching, returning the
+    /// stats [`apply`] would return without producing the patched content
+    ///
+    /// This runs the same matching as [`apply_check`] and returns the same errors.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let diff = Diff::from_str("@@ -2 +2,2 @@\n-b\n+B\n+C\n").unwrap();
+    /// let stats = diff.dry_run("a\nb\n").unwrap();
+    /// assert_eq!((stats.lines_added, stats.lines_deleted), (2, 1));
+    /// assert!(diff.dry_run("a\nx\n").is_err());
+    /// ```
+    pub fn dry_run(&self, base_image: &T) -> Result<ApplyStats, ApplyError> {
+        apply_to_image(base_image, self, &ApplyConfig::default(), &mut |_, _| {})
+            .map(|(_, report)| report.stats)
+    }
+
+    /// Apply only the hunk at `hunk_index` to a base image with default fuzzy matching
+    ///
+    /// The hunk is looked for at the position given by its old range, so it applies to the
+    /// original text regardless of which of the other hunks have been applied. This allows
+    /// reviewing and applying the hunks of a diff one at a time. Returns
+    /// [`ApplyError::HunkIndexOutOfBounds`] if the diff has no such hunk.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let diff = Diff::from_str("@@ -1 +1,2 @@\n a\n+b\n@@ -3 +4 @@\n-c\n+C\n").unwrap();
+    /// let (content, stats) = diff.apply_hunk("a\nx\nc\n", 1).unwrap();
+    /// assert_eq!(content, "a\nx\nC\n");
+    /// assert_eq!(stats.hunks_applied, 1);
+    /// ```
+    pub fn apply_hunk(
+        &self,
+        base_image: &T,
+        hunk_index: usize,
+    ) -> ApplyResult<T::Owned, ApplyError> {
+        let hunk = self
+            .hunks()
+            .get(hunk_index)
+            .ok_or(ApplyError::HunkIndexOutOfBounds {
+                hunk_index,
+                hunk_count: self.hunks().len(),
+            })?;
+
+        // Without the hunks before it, the hunk starts at the same line in the new file as in
+        // the old one
+        let mut hunk = hunk.clone();
+        let new_range = HunkRange::from_index(hunk.old_range().index(), hunk.new_range().len());
+        hunk.set_new_range(new_range);
+
+        let diff = self.with_hunks(vec![hunk]);
+        apply_text(base_image, &diff, &ApplyConfig::default())
+            .map(|(content, report)| (content, report.stats))
+            .map_err(|mut err| {
+                // Refer to the hunk by its index in this diff
+                if let ApplyError::ContextMismatch {
+                    hunk_index: index, ..
+                }
+                | ApplyError::MatchBudgetExceeded {
+                    hunk_index: index, ..
+                } = &mut err
+                {
+                    *index = hunk_index;
+                }
+                err
+            })
+    }
+}
+
 /// Apply a `Diff` to a base image with default fuzzy matching
 pub fn apply(base_image: &str, diff: &Diff<'_, str>) -> ApplyResult<String, ApplyError> {
     apply_with_config(base_image, diff, &ApplyConfig::default())
@@ -307,86 +1002,400 @@ pub fn apply_with_config(
     diff: &Diff<'_, str>,
     config: &ApplyConfig,
 ) -> ApplyResult<String, ApplyError> {
-    let mut image: Vec<_> = LineIter::new(base_image)
-        .map(ImageLine::Unpatched)
-        .collect();
+    apply_text(base_image, diff, config).map(|(content, report)| (content, report.stats))
+}
 
-    let mut stats = ApplyStats::new();
+/// Undo a `Diff` by applying it in reverse to the modified text, with default fuzzy matching
+///
+/// This is the same as applying [`Diff::reverse`].
+///
+/// ```
+/// use flickzeug::{apply, apply_reverse, create_patch};
+///
+/// let diff = create_patch("a\nb\nc\n", "a\nB\nc\nd\n");
+/// let (modified, _) = apply("a\nb\nc\n", &diff).unwrap();
+/// assert_eq!(apply_reverse(&modified, &diff).unwrap().0, "a\nb\nc\n");
+/// ```
+pub fn apply_reverse(base_image: &str, diff: &Diff<'_, str>) -> ApplyResult<String, ApplyError> {
+    apply(base_image, &diff.reverse())
+}
 
-    for (i, hunk) in diff.hunks().iter().enumerate() {
-        let hunk_stats = match apply_hunk_with_config(&mut image, hunk, config) {
-            Ok(stats) => stats,
-            Err(_) => return Err(ApplyError(i + 1, format!("{}", hunk))),
-        };
-        stats.add_hunk(hunk_stats);
+/// Apply a `Diff` to a text in place, with default fuzzy matching
+///
+/// The result and the returned stats are the same as with [`apply`], but instead of building a
+/// patched copy of the whole text, only the lines covered by the hunks are replaced in `buf`,
+/// starting from the last hunk. If the diff doesn't apply, `buf` is left untouched.
+///
+/// Like [`apply`], this changes the line endings of all lines to the most common one of the text,
+/// so a text with mixed line endings is rebuilt as a whole.
+///
+/// ```
+/// use flickzeug::{Diff, apply_in_place};
+///
+/// let mut buf = String::from("a\nb\nc\n");
+/// let diff = Diff::from_str("@@ -1 +1 @@\n-a\n+A\n@@ -3 +3,2 @@\n-c\n+C\n+D\n").unwrap();
+///
+/// let stats = apply_in_place(&mut buf, &diff).unwrap();
+/// assert_eq!(buf, "A\nb\nC\nD\n");
+/// assert_eq!(stats.lines_added, 3);
+/// ```
+pub fn apply_in_place(buf: &mut String, diff: &Diff<'_, str>) -> Result<ApplyStats, ApplyError> {
+    let config = ApplyConfig::default();
+    let (image, report) = apply_to_image(buf.as_str(), diff, &config, &mut |_, _| {})?;
+    let preferred_line_ending = preferred_line_ending(buf.as_str(), diff, &config);
+
+    let keeps_line_endings = LineIter::new(buf.as_str())
+        .all(|(_, end)| end.is_none_or(|end| end == preferred_line_ending));
+    if !keeps_line_endings {
+        let content = str::join_lines(
+            image.into_iter().flat_map(ImageLine::into_pieces),
+            Some(preferred_line_ending),
+        );
+        let stats = report.stats;
+        *buf = content;
+        return Ok(stats);
     }
 
-    // TODO: Keep line ending as is like it was before.
-    let preferred_line_ending = Some(match config.line_end_strategy {
-        LineEndHandling::EnsurePatchLineEnding => {
-            let mut lf_score = 0usize;
-            let mut crlf_score = 0usize;
-
-            for hunk in diff.hunks().iter() {
-                for line in hunk.lines() {
-                    match line.line_end() {
-                        Some(LineEnd::Lf) => lf_score += 1,
-                        Some(LineEnd::CrLf) => crlf_score += 1,
-                        _ => (),
-                    }
-                }
-            }
-
-            LineEnd::choose_from_scores(lf_score, crlf_score)
-        }
-        LineEndHandling::EnsureFileLineEnding => LineEnd::most_common(base_image),
-        LineEndHandling::EnsureLineEnding(line_end) => line_end,
-    });
+    // The byte offset of the start of every line, followed by the end of the text
+    let mut starts: Vec<_> = LineIter::new(buf.as_str())
+        .with_offsets()
+        .map(|(_, start, _)| start)
+        .collect();
+    starts.push(buf.len());
 
-    let content = image
-        .into_iter()
-        .map(ImageLine::into_inner)
-        .map(|(line, ending)| {
-            format!(
-                "{}{}",
-                line,
-                map_line_ending::<&str>(ending, preferred_line_ending)
-            )
+    // The resolved hunks are sorted and their new ranges point into the patched image
+    let splices: Vec<_> = report
+        .resolved
+        .hunks()
+        .iter()
+        .map(|hunk| {
+            let (old_range, new_range) = (hunk.old_range(), hunk.new_range());
+            let lines = image[new_range.index()..new_range.index() + new_range.len()].iter();
+            let content = str::join_lines(
+                lines.flat_map(|line| line.into_pieces()),
+                Some(preferred_line_ending),
+            );
+            let start = old_range.index();
+            (starts[start]..starts[start + old_range.len()], content)
         })
         .collect();
+    let stats = report.stats;
 
-    Ok((content, stats))
-}
-
-/// Apply a non-utf8 `Diff` to a base image with default fuzzy matching
-pub fn apply_bytes(base_image: &[u8], patch: &Diff<'_, [u8]>) -> ApplyResult<Vec<u8>, ApplyError> {
-    apply_bytes_with_config(base_image, patch, &ApplyConfig::default())
+    for (range, content) in splices.into_iter().rev() {
+        buf.replace_range(range, &content);
+    }
+    Ok(stats)
 }
 
-/// Returns `true` if `diff` already appears to be applied to `base_image`,
-/// i.e. `base_image` already reflects the *modified* side of the diff
-/// ("reversed or previously applied", in GNU patch terms).
+/// Apply a `Diff` to a text with default fuzzy matching, writing the result to `out`
 ///
-/// This is robust under fuzzy matching, where a *forward* apply is not a
-/// reliable signal: on already-applied content a forward apply may fail (a
-/// deleted line no longer matches) *or* succeed while wrongly re-applying the
-/// change (e.g. inserting an already-present line a second time). Instead we
-/// perform a reverse round-trip — reversing an already-applied diff must produce
-/// a *different* pre-image that, patched forward again, reproduces the input.
+/// This writes the same content [`apply`] and [`apply_bytes`] return, line by line, without
+/// building the patched text in memory, so `out` should be buffered. The hunks are matched
+/// before anything is written, so if the diff doesn't apply, nothing is written and the
+/// [`ApplyError`] is returned as an [`io::Error`] of kind [`io::ErrorKind::InvalidData`].
 ///
-/// This is only meaningful for content-modifying diffs; callers should handle
-/// pure file creation/deletion/rename at the path level.
+/// ```
+/// use flickzeug::{Diff, apply_to_writer};
 ///
-/// # Examples
+/// let diff = Diff::from_str("@@ -2 +2 @@\n-b\n+B\n").unwrap();
+/// let mut out = Vec::new();
+/// let stats = apply_to_writer("a\nb\nc\n", &diff, &mut out).unwrap();
+/// assert_eq!(out, b"a\nB\nc\n");
+/// assert_eq!(stats.lines_added, 1);
+/// ```
+pub fn apply_to_writer<'a, T: ApplyText + ?Sized, W: io::Write>(
+    base_image: &'a T,
+    diff: &Diff<'a, T>,
+    out: &mut W,
+) -> io::Result<ApplyStats> {
+    let config = ApplyConfig::default();
+    let (image, report) = apply_to_image(base_image, diff, &config, &mut |_, _| {})
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let preferred_line_ending = preferred_line_ending(base_image, diff, &config);
+
+    for (line, ending) in image.into_iter().flat_map(ImageLine::into_pieces) {
+        out.write_all(line.as_ref())?;
+        out.write_all(map_line_ending::<&[u8]>(
+            ending,
+            Some(preferred_line_ending),
+        ))?;
+    }
+    Ok(report.stats)
+}
+
+/// Apply only the hunks of a `Diff` within `range` of the base image, with default fuzzy matching
+///
+/// The range is given as 1-based line numbers of the base image like [`HunkRange::range`]. A hunk
+/// is applied if all lines of its old range are within `range`, while hunks outside of it or
+/// crossing its boundary are skipped entirely. Hunks only inserting lines belong to the line they
+/// follow, or to the first line if they insert at the start of the file. Returns the patched text,
+/// the stats of the applied hunks and the indices of the skipped hunks in [`Diff::hunks`].
 ///
 /// ```
-/// use flickzeug::{is_diff_applied_with_config, ApplyConfig, Diff};
+/// use flickzeug::{Diff, apply_within};
 ///
-/// let patch = "\
-/// --- a/version
-/// +++ b/version
-/// @@ -1 +1 @@
-/// -3.1
+/// let diff = Diff::from_str("@@ -1 +1 @@\n-a\n+A\n@@ -3 +3 @@\n-c\n+C\n").unwrap();
+/// let (content, stats, skipped) = apply_within("a\nb\nc\n", &diff, 2..4).unwrap();
+/// assert_eq!(content, "a\nb\nC\n");
+/// assert_eq!(stats.hunks_applied, 1);
+/// assert_eq!(skipped, [0]);
+/// ```
+pub fn apply_within(
+    base_image: &str,
+    diff: &Diff<'_, str>,
+    range: ops::Range<usize>,
+) -> Result<(String, ApplyStats, Vec<usize>), ApplyError> {
+    let (applied, skipped): (Vec<_>, Vec<_>) = (0..diff.hunks().len()).partition(|&index| {
+        let old_range = diff.hunks()[index].old_range();
+        if old_range.is_empty() {
+            range.contains(&old_range.start().max(1))
+        } else {
+            range.start <= old_range.start() && old_range.end() <= range.end
+        }
+    });
+
+    let hunks = applied.iter().map(|&index| diff.hunks()[index].clone());
+    let (content, stats) =
+        apply(base_image, &diff.with_hunks(hunks.collect())).map_err(|mut err| {
+            // Refer to the hunks by their index in `diff`
+            match &mut err {
+                ApplyError::ContextMismatch { hunk_index, .. }
+                | ApplyError::MatchBudgetExceeded { hunk_index, .. } => {
+                    *hunk_index = applied[*hunk_index];
+                }
+                ApplyError::OverlappingHunks { first, second } => {
+                    (*first, *second) = (applied[*first], applied[*second]);
+                }
+                ApplyError::HunkIndexOutOfBounds { .. } | ApplyError::BinaryNotSupported => {}
+            }
+            err
+        })?;
+    Ok((content, stats, skipped))
+}
+
+/// The files written by [`apply_to_path`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathReport {
+    /// Statistics about the applied hunks
+    pub stats: ApplyStats,
+    /// The patched file, which is removed instead if the diff deletes it and every hunk applied
+    pub path: PathBuf,
+    /// The reject file holding the hunks which didn't apply, see [`Diff::reject_path`]
+    ///
+    /// This is `None` if every hunk applied, in which case no reject file is written.
+    pub reject_path: Option<PathBuf>,
+    /// The indices of the rejected hunks in [`Diff::hunks`]
+    pub rejected: Vec<usize>,
+}
+
+/// Apply a `Diff` to the file it names below `dir` with default fuzzy matching, writing the hunks
+/// which don't apply to a reject file like `patch` does
+///
+/// The file is read from the [old path](Diff::old_path) of the diff and written to its
+/// [new path](Diff::new_path), so created files start out empty. Hunks which don't apply are
+/// skipped, the others are applied and written to the file, while the skipped hunks are written
+/// as a patch to the [reject file](Diff::reject_path) of the diff. The old file of a deletion or
+/// rename is only removed once every hunk applied.
+///
+/// Returns an error of kind [`io::ErrorKind::InvalidInput`] if the diff doesn't name a file, and
+/// of kind [`io::ErrorKind::InvalidData`] if it can't be applied at all, e.g. because it's binary
+/// or its hunks overlap.
+///
+/// ```no_run
+/// use flickzeug::{Diff, apply_to_path};
+/// use std::path::Path;
+///
+/// let diff = Diff::from_str("--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\n").unwrap();
+/// let report = apply_to_path(Path::new("."), &diff).unwrap();
+/// if let Some(reject_path) = report.reject_path {
+///     eprintln!("{} hunks rejected, see {}", report.rejected.len(), reject_path.display());
+/// }
+/// ```
+pub fn apply_to_path(dir: &Path, diff: &Diff<'_, str>) -> io::Result<PathReport> {
+    let (Some(old), Some(new), Some(reject_path)) = (
+        diff.old_path().or(diff.new_path()),
+        diff.new_path().or(diff.old_path()),
+        diff.reject_path(),
+    ) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the diff doesn't name a file",
+        ));
+    };
+    let (old, path) = (dir.join(old), dir.join(new));
+    let base_image = match diff.old_path() {
+        Some(_) => fs::read_to_string(&old)?,
+        None => String::new(),
+    };
+
+    // Drop the hunks which don't apply one at a time, as every hunk is placed relative to the
+    // ones before it
+    let mut rejected = Vec::new();
+    let (content, stats) = loop {
+        let applied: Vec<_> = (0..diff.hunks().len())
+            .filter(|index| !rejected.contains(index))
+            .collect();
+        let hunks = applied.iter().map(|&index| diff.hunks()[index].clone());
+        match apply(&base_image, &diff.with_hunks(hunks.collect())) {
+            Ok(result) => break result,
+            Err(
+                ApplyError::ContextMismatch { hunk_index, .. }
+                | ApplyError::MatchBudgetExceeded { hunk_index, .. },
+            ) => rejected.push(applied[hunk_index]),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        }
+    };
+    rejected.sort_unstable();
+
+    if rejected.is_empty() && diff.new_path().is_none() {
+        fs::remove_file(&path)?;
+    } else {
+        fs::write(&path, content)?;
+    }
+    if rejected.is_empty() && old != path {
+        fs::remove_file(&old)?;
+    }
+
+    let reject_path = if rejected.is_empty() {
+        None
+    } else {
+        let hunks = rejected.iter().map(|&index| diff.hunks()[index].clone());
+        let reject_path = dir.join(reject_path);
+        fs::write(&reject_path, diff.with_hunks(hunks.collect()).to_string())?;
+        Some(reject_path)
+    };
+    Ok(PathReport {
+        stats,
+        path,
+        reject_path,
+        rejected,
+    })
+}
+
+/// Apply a `Diff` to a base image, returning a detailed [`ApplyReport`]
+///
+/// ```
+/// use flickzeug::{ApplyConfig, Diff, apply_with_report};
+///
+/// let base = "first\nsecond\nthird\n";
+/// let diff = Diff::from_str("@@ -2 +2 @@\n-second\n+2nd\n").unwrap();
+///
+/// let (content, report) = apply_with_report(base, &diff, &ApplyConfig::default()).unwrap();
+/// assert_eq!(content, "first\n2nd\nthird\n");
+/// // The lines of the base image which were replaced by the hunk
+/// assert_eq!(report.hunks[0].replaced, [("second", Some(flickzeug::LineEnd::Lf))]);
+/// ```
+pub fn apply_with_report<'a>(
+    base_image: &'a str,
+    diff: &Diff<'a, str>,
+    config: &ApplyConfig,
+) -> Result<(String, ApplyReport<'a, str>), ApplyError> {
+    apply_text(base_image, diff, config)
+}
+
+/// Check whether a `Diff` applies to a base image without producing the patched content
+///
+/// This performs the same matching as [`apply_with_report`], including offsets and fuzz, and
+/// returns the same report or error, but skips joining the lines of the patched content. It's
+/// meant for checking many patches up front before applying them.
+///
+/// ```
+/// use flickzeug::{ApplyConfig, Diff, apply_check};
+///
+/// let diff = Diff::from_str("@@ -2 +2 @@\n-second\n+2nd\n").unwrap();
+///
+/// let report = apply_check("first\nsecond\n", &diff, &ApplyConfig::default()).unwrap();
+/// assert_eq!(report.stats.lines_added, 1);
+/// assert!(apply_check("first\n", &diff, &ApplyConfig::default()).is_err());
+/// ```
+pub fn apply_check<'a>(
+    base_image: &'a str,
+    diff: &Diff<'a, str>,
+    config: &ApplyConfig,
+) -> Result<ApplyReport<'a, str>, ApplyError> {
+    apply_to_image(base_image, diff, config, &mut |_, _| {}).map(|(_, report)| report)
+}
+
+/// Apply a `Diff` to a base image, calling `on_hunk_done` after each hunk has been applied
+///
+/// The callback receives the [`HunkReport`] of the hunk and the [`ApplyStats`] of all hunks
+/// applied so far, e.g. to show the progress of applying a large patch. It isn't called for the
+/// hunk which fails to apply, if any.
+///
+/// ```
+/// use flickzeug::{ApplyConfig, Diff, apply_with_progress};
+///
+/// let base = "a\nb\nc\n";
+/// let diff = Diff::from_str("@@ -1 +1 @@\n-a\n+A\n@@ -3 +3,2 @@\n-c\n+C\n+D\n").unwrap();
+///
+/// let mut added = Vec::new();
+/// let (content, _) = apply_with_progress(base, &diff, &ApplyConfig::default(), |_, stats| {
+///     added.push(stats.lines_added)
+/// })
+/// .unwrap();
+/// assert_eq!(content, "A\nb\nC\nD\n");
+/// assert_eq!(added, [1, 3]);
+/// ```
+pub fn apply_with_progress<'a>(
+    base_image: &'a str,
+    diff: &Diff<'a, str>,
+    config: &ApplyConfig,
+    mut on_hunk_done: impl FnMut(&HunkReport<'a, str>, &ApplyStats),
+) -> ApplyResult<String, ApplyError> {
+    apply_text_with_progress(base_image, diff, config, &mut on_hunk_done)
+        .map(|(content, report)| (content, report.stats))
+}
+
+/// Apply a non-utf8 `Diff` to a base image with default fuzzy matching
+///
+/// Lines are split on `\n` bytes and compared byte by byte, so content in other encodings which
+/// are compatible with ASCII line endings (e.g. Latin-1 or Shift-JIS) can be patched as long as the
+/// patch uses the same encoding as the base image. The output keeps that encoding. Fuzzy matching
+/// and [`MatchOptions`] only look past exact byte equality for lines which are valid UTF-8 (or,
+/// for non-UTF-8 lines, at ASCII characters).
+///
+/// The base image is only ever borrowed, so it can e.g. be a read-only memory map of a large file.
+/// Unchanged lines are not copied until the output is assembled, at which point every line of the
+/// result is copied exactly once.
+pub fn apply_bytes(base_image: &[u8], patch: &Diff<'_, [u8]>) -> ApplyResult<Vec<u8>, ApplyError> {
+    apply_bytes_with_config(base_image, patch, &ApplyConfig::default())
+}
+
+/// Undo a non-utf8 `Diff` by applying it in reverse to the modified text, see [`apply_reverse`]
+pub fn apply_bytes_reverse(
+    base_image: &[u8],
+    diff: &Diff<'_, [u8]>,
+) -> ApplyResult<Vec<u8>, ApplyError> {
+    apply_bytes(base_image, &diff.reverse())
+}
+
+/// Returns `true` if `diff` already appears to be applied to `base_image`,
+/// i.e. `base_image` already reflects the *modified* side of the diff
+/// ("reversed or previously applied", in GNU patch terms).
+///
+/// This is robust under fuzzy matching, where a *forward* apply is not a
+/// reliable signal: on already-applied content a forward apply may fail (a
+/// deleted line no longer matches) *or* succeed while wrongly re-applying the
+/// change (e.g. inserting an already-present line a second time). Instead we
+/// perform a reverse round-trip — reversing an already-applied diff must produce
+/// a *different* pre-image that, patched forward again, reproduces the input.
+/// The input only needs to be reproduced up to the [`MatchOptions`] of
+/// `config`, e.g. a change which was applied with different whitespace is
+/// detected if [`FuzzyConfig::ignore_whitespace`] is set.
+///
+/// This is only meaningful for content-modifying diffs; callers should handle
+/// pure file creation/deletion/rename at the path level.
+///
+/// # Examples
+///
+/// ```
+/// use flickzeug::{is_diff_applied_with_config, ApplyConfig, Diff};
+///
+/// let patch = "\
+/// --- a/version
+/// +++ b/version
+/// @@ -1 +1 @@
+/// -3.1
 /// +3.12
 /// ";
 /// let diff = Diff::from_bytes(patch.as_bytes()).unwrap();
@@ -401,17 +1410,167 @@ pub fn is_diff_applied_with_config(
     base_image: &[u8],
     diff: &Diff<'_, [u8]>,
     config: &ApplyConfig,
+) -> bool {
+    is_applied(base_image, diff, config)
+}
+
+fn is_applied<T: ApplyText + ?Sized>(
+    base_image: &T,
+    diff: &Diff<'_, T>,
+    config: &ApplyConfig,
 ) -> bool {
     // Reverse round-trip: reversing an already-applied diff must produce a
     // *different* pre-image that, patched forward again, reproduces the input.
-    match apply_bytes_with_config(base_image, &diff.reverse(), config) {
-        Ok((pre, _)) if pre != base_image => apply_bytes_with_config(&pre, diff, config)
-            .map(|(re, _)| re == base_image)
+    // The input only needs to be reproduced up to the match options, as
+    // applying takes the inserted lines from the diff.
+    let options = config.fuzzy_config.match_options();
+    match apply_text(base_image, &diff.reverse(), config) {
+        Ok((pre, _)) if pre.borrow() != base_image => apply_text(pre.borrow(), diff, config)
+            .map(|(re, _)| {
+                let re: Vec<_> = LineIter::new(re.borrow()).collect();
+                let base: Vec<_> = LineIter::new(base_image).collect();
+                re.len() == base.len()
+                    && re
+                        .iter()
+                        .zip(&base)
+                        .all(|((a, a_end), (b, b_end))| a_end == b_end && a.matches(b, &options))
+            })
             .unwrap_or(false),
         _ => false,
     }
 }
 
+impl<T: ApplyText + ?Sized> Diff<'_, T> {
+    /// Returns `true` if applying the diff to `base_image` would leave it unchanged, using the
+    /// default configuration.
+    ///
+    /// See [`Diff::is_noop_with_config`] for details.
+    pub fn is_noop(&self, base_image: &T) -> bool {
+        self.is_noop_with_config(base_image, &ApplyConfig::default())
+    }
+
+    /// Returns `true` if applying the diff to `base_image` would leave it unchanged.
+    ///
+    /// This is the case when the diff is already applied to `base_image` (see
+    /// [`is_diff_applied_with_config`]), or when it applies to `base_image` and:
+    /// * the diff doesn't contain any changes,
+    /// * all changes are insignificant under the fuzzy matching options of `config`, e.g. they
+    ///   only touch whitespace and [`FuzzyConfig::ignore_whitespace`] is set, or
+    /// * applying the diff reproduces `base_image`.
+    ///
+    /// A diff which doesn't apply isn't a no-op, even if its changes are insignificant.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let diff = Diff::from_str("@@ -1 +1 @@\n-3.1\n+3.12\n").unwrap();
+    /// assert!(!diff.is_noop("3.1\n"));
+    /// assert!(diff.is_noop("3.12\n"));
+    /// ```
+    pub fn is_noop_with_config(&self, base_image: &T, config: &ApplyConfig) -> bool {
+        let insignificant = self.hunks().iter().all(|hunk| {
+            let pre_image: Vec<_> = pre_image(hunk.lines()).collect();
+            let post_image: Vec<_> = post_image(hunk.lines()).collect();
+            pre_image.len() == post_image.len()
+                && pre_image
+                    .iter()
+                    .zip(&post_image)
+                    .all(|(pre, post)| pre.0.similarity(post.0, config) >= 1.0)
+        });
+
+        is_applied(base_image, self, config)
+            || apply_text(base_image, self, config)
+                .is_ok_and(|(content, _)| insignificant || content.borrow() == base_image)
+    }
+
+    /// Returns `true` if the diff and `other` both apply to `base_image` with the default
+    /// configuration and produce the same text
+    ///
+    /// Unlike comparing the diffs with `==`, this doesn't depend on how the changes are grouped
+    /// into hunks or on how much context they have.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let one = Diff::from_str("@@ -1,3 +1,3 @@\n-a\n+A\n b\n-c\n+C\n").unwrap();
+    /// let two = Diff::from_str("@@ -1 +1 @@\n-a\n+A\n@@ -3 +3 @@\n-c\n+C\n").unwrap();
+    /// assert_ne!(one, two);
+    /// assert!(one.semantically_eq(&two, "a\nb\nc\n"));
+    /// ```
+    pub fn semantically_eq(&self, other: &Diff<'_, T>, base_image: &T) -> bool {
+        let config = ApplyConfig::default();
+        match (
+            apply_text(base_image, self, &config),
+            apply_text(base_image, other, &config),
+        ) {
+            (Ok((content, _)), Ok((other_content, _))) => {
+                content.borrow() == other_content.borrow()
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the [`HunkState`] of every hunk of the diff relative to `base_image`, using the
+    /// default configuration.
+    ///
+    /// See [`Diff::hunk_states_with_config`] for details.
+    pub fn hunk_states(&self, base_image: &T) -> Vec<HunkState> {
+        self.hunk_states_with_config(base_image, &ApplyConfig::default())
+    }
+
+    /// Returns the [`HunkState`] of every hunk of the diff relative to `base_image`, in order.
+    ///
+    /// Every hunk is checked on its own against the unmodified `base_image`, using the same
+    /// matching as applying the diff with `config`, so e.g. hunks which only differ from the base
+    /// image in whitespace are considered already applied if
+    /// [`FuzzyConfig::ignore_whitespace`] is set. A hunk is already applied if it would be
+    /// detected as such by [`is_diff_applied_with_config`] as the only hunk of the diff.
+    ///
+    /// This allows checking which hunks of a diff still need to be applied, and which ones can't
+    /// be applied, without modifying anything.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use flickzeug::{Diff, HunkState};
+    ///
+    /// let diff = Diff::from_str("@@ -1 +1 @@\n-a\n+A\n@@ -3 +3 @@\n-c\n+C\n@@ -5 +5 @@\n-e\n+E\n").unwrap();
+    /// assert_eq!(
+    ///     diff.hunk_states("A\nb\nc\nd\nx\n"),
+    ///     [HunkState::AlreadyApplied, HunkState::Pending, HunkState::Conflicts],
+    /// );
+    /// ```
+    pub fn hunk_states_with_config(&self, base_image: &T, config: &ApplyConfig) -> Vec<HunkState> {
+        self.hunks()
+            .iter()
+            .map(|hunk| {
+                let diff = self.with_hunks(vec![hunk.clone()]);
+                if is_applied(base_image, &diff, config) {
+                    HunkState::AlreadyApplied
+                } else if apply_text(base_image, &diff, config).is_ok() {
+                    HunkState::Pending
+                } else {
+                    HunkState::Conflicts
+                }
+            })
+            .collect()
+    }
+}
+
+/// The state of a single hunk of a diff relative to a base image, see
+/// [`Diff::hunk_states_with_config`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HunkState {
+    /// The hunk applies to the base image and hasn't been applied yet
+    Pending,
+    /// The base image already contains the changes of the hunk
+    AlreadyApplied,
+    /// The hunk neither applies to the base image nor is it already applied
+    Conflicts,
+}
+
 /// The outcome of attempting to apply a diff with [`apply_bytes_reporting`].
 ///
 /// This distinguishes the three cases a caller usually cares about: the diff
@@ -497,22 +1656,317 @@ pub fn apply_bytes_with_config(
     diff: &Diff<'_, [u8]>,
     config: &ApplyConfig,
 ) -> ApplyResult<Vec<u8>, ApplyError> {
+    apply_text(base_image, diff, config).map(|(content, report)| (content, report.stats))
+}
+
+/// Apply a non-utf8 `Diff` to a base image, returning a detailed [`ApplyReport`]
+pub fn apply_bytes_with_report<'a>(
+    base_image: &'a [u8],
+    diff: &Diff<'a, [u8]>,
+    config: &ApplyConfig,
+) -> Result<(Vec<u8>, ApplyReport<'a, [u8]>), ApplyError> {
+    apply_text(base_image, diff, config)
+}
+
+/// Check whether a non-utf8 `Diff` applies to a base image without producing the patched
+/// content
+///
+/// See [`apply_check`].
+pub fn apply_bytes_check<'a>(
+    base_image: &'a [u8],
+    diff: &Diff<'a, [u8]>,
+    config: &ApplyConfig,
+) -> Result<ApplyReport<'a, [u8]>, ApplyError> {
+    apply_to_image(base_image, diff, config, &mut |_, _| {}).map(|(_, report)| report)
+}
+
+/// Apply a non-utf8 `Diff` to a base image, calling `on_hunk_done` after each hunk has been
+/// applied
+///
+/// See [`apply_with_progress`].
+pub fn apply_bytes_with_progress<'a>(
+    base_image: &'a [u8],
+    diff: &Diff<'a, [u8]>,
+    config: &ApplyConfig,
+    mut on_hunk_done: impl FnMut(&HunkReport<'a, [u8]>, &ApplyStats),
+) -> ApplyResult<Vec<u8>, ApplyError> {
+    apply_text_with_progress(base_image, diff, config, &mut on_hunk_done)
+        .map(|(content, report)| (content, report.stats))
+}
+
+/// Text which a `Diff` can be applied to
+pub trait ApplyText: Text + FuzzyComparable + ToOwned + AsRef<[u8]> {
+    /// Join lines into an owned text, mapping their line endings to `preferred_line_ending`
+    fn join_lines<'a>(
+        lines: impl Iterator<Item = (&'a Self, Option<LineEnd>)>,
+        preferred_line_ending: Option<LineEnd>,
+    ) -> Self::Owned
+    where
+        Self: 'a;
+}
+
+impl ApplyText for str {
+    fn join_lines<'a>(
+        lines: impl Iterator<Item = (&'a Self, Option<LineEnd>)>,
+        preferred_line_ending: Option<LineEnd>,
+    ) -> String {
+        let mut content = String::new();
+        for (line, ending) in lines {
+            content.push_str(line);
+            content.push_str(map_line_ending::<&str>(ending, preferred_line_ending));
+        }
+        content
+    }
+}
+
+impl ApplyText for [u8] {
+    fn join_lines<'a>(
+        lines: impl Iterator<Item = (&'a Self, Option<LineEnd>)>,
+        preferred_line_ending: Option<LineEnd>,
+    ) -> Vec<u8> {
+        let mut content = Vec::new();
+        for (line, ending) in lines {
+            content.extend_from_slice(line);
+            content.extend_from_slice(map_line_ending::<&[u8]>(ending, preferred_line_ending));
+        }
+        content
+    }
+}
+
+fn apply_text<'a, T: ApplyText + ?Sized>(
+    base_image: &'a T,
+    diff: &Diff<'a, T>,
+    config: &ApplyConfig,
+) -> Result<(T::Owned, ApplyReport<'a, T>), ApplyError> {
+    apply_text_with_progress(base_image, diff, config, &mut |_, _| {})
+}
+
+fn apply_text_with_progress<'a, T: ApplyText + ?Sized>(
+    base_image: &'a T,
+    diff: &Diff<'a, T>,
+    config: &ApplyConfig,
+    on_hunk_done: &mut dyn FnMut(&HunkReport<'a, T>, &ApplyStats),
+) -> Result<(T::Owned, ApplyReport<'a, T>), ApplyError> {
+    let (image, report) = apply_to_image(base_image, diff, config, on_hunk_done)?;
+    let preferred_line_ending = preferred_line_ending(base_image, diff, config);
+
+    let content = T::join_lines(
+        image.into_iter().flat_map(ImageLine::into_pieces),
+        Some(preferred_line_ending),
+    );
+
+    Ok((content, report))
+}
+
+/// Make sure that no hunk starts before the end of the old range of the hunk before it
+///
+/// Hunks which end exactly where the next one begins are fine.
+fn check_overlapping_hunks<T: Text + ?Sized + ToOwned>(
+    hunks: &[Hunk<'_, T>],
+) -> Result<(), ApplyError> {
+    for (first, pair) in hunks.windows(2).enumerate() {
+        if pair[0].old_range().end() > pair[1].old_range().start() {
+            return Err(ApplyError::OverlappingHunks {
+                first,
+                second: first + 1,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn apply_to_image<'a, T: ApplyText + ?Sized>(
+    base_image: &'a T,
+    diff: &Diff<'a, T>,
+    config: &ApplyConfig,
+    on_hunk_done: &mut dyn FnMut(&HunkReport<'a, T>, &ApplyStats),
+) -> Result<(Vec<ImageLine<'a, T>>, ApplyReport<'a, T>), ApplyError> {
+    if diff.is_binary() {
+        return Err(ApplyError::BinaryNotSupported);
+    }
+    // Misnumbered hunk headers are only trusted to be in order if they were checked when parsing
+    if !diff.skip_order_check() {
+        check_overlapping_hunks(diff.hunks())?;
+    }
+
     let mut image: Vec<_> = LineIter::new(base_image)
         .map(ImageLine::Unpatched)
         .collect();
 
-    let mut stats = ApplyStats::new();
+    // Hardened diffs only apply where their context matches exactly
+    let verifying_config;
+    let config = if diff.requires_verification() {
+        verifying_config = ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                max_fuzz: 0,
+                ignore_whitespace: false,
+                ignore_trailing_whitespace: false,
+                ignore_case: false,
+                expand_tabs: None,
+            },
+            volatile_lines: None,
+            ..config.clone()
+        };
+        &verifying_config
+    } else {
+        config
+    };
 
-    for (i, hunk) in diff.hunks().iter().enumerate() {
-        let hunk_stats = match apply_hunk_with_config(&mut image, hunk, config) {
-            Ok(stats) => stats,
-            Err(_) => return Err(ApplyError(i + 1, format!("{}", hunk))),
+    let mut report = ApplyReport {
+        stats: ApplyStats::new(),
+        hunks: Vec::with_capacity(diff.hunks().len()),
+        resolved: diff.with_hunks(Vec::with_capacity(diff.hunks().len())),
+        warnings: Vec::new(),
+    };
+    let indentation = config
+        .lint_indentation
+        .then(|| Indentation::detect(image.iter().map(|line| line.inner().0)))
+        .flatten();
+    let mut resolved: Vec<Hunk<'a, T>> = Vec::with_capacity(diff.hunks().len());
+    // Position and length in the image and the change in the number of lines of every applied
+    // hunk, used to map positions in the image back to the base image
+    let mut applied: Vec<(usize, usize, isize)> = Vec::with_capacity(diff.hunks().len());
+    let mut budget = MatchBudget(config.max_match_comparisons);
+
+    for (hunk_index, hunk) in diff.hunks().iter().enumerate() {
+        // The position of the hunk's header in the image, shifted by the hunks applied before it.
+        // Earlier pure insertions at the same line were placed in front of it.
+        let start = hunk.old_range().index();
+        let offset: isize = resolved
+            .iter()
+            .zip(&applied)
+            .filter(|(resolved, _)| resolved.old_range().index() <= start)
+            .map(|(_, (_, _, delta))| delta)
+            .sum();
+        let expected = start.saturating_add_signed(offset);
+        let (pos, pre_image) =
+            match apply_hunk_with_config(&mut image, hunk, expected, config, &mut budget) {
+                Ok(applied) => applied,
+                Err(HunkError::NoMatch) => {
+                    // Compare against the position the hunk was expected at
+                    let target = image
+                        .get(expected..)
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|line| line.inner().0);
+                    return Err(ApplyError::context_mismatch(
+                        hunk_index,
+                        hunk,
+                        target,
+                        &config.fuzzy_config.match_options(),
+                    ));
+                }
+                Err(HunkError::BudgetExceeded) => {
+                    return Err(ApplyError::MatchBudgetExceeded {
+                        hunk_index,
+                        hunk: hunk_to_string(hunk),
+                    });
+                }
+            };
+        let match_options = config.fuzzy_config.match_options();
+        let context_matched = hunk
+            .lines()
+            .iter()
+            .filter(|line| !matches!(line, Line::Insert(_)))
+            .zip(&pre_image)
+            .filter(|(line, image_line)| match line {
+                Line::Context((line, _)) => line.matches(image_line.inner().0, &match_options),
+                _ => false,
+            })
+            .count();
+        if let Some(expected) = indentation {
+            let found = hunk
+                .lines()
+                .iter()
+                .filter(|line| !matches!(line, Line::Delete(_)))
+                .zip(&image[pos..])
+                .filter(|(line, _)| matches!(line, Line::Insert(_)))
+                .find_map(|(_, image_line)| expected.mismatch(image_line.indent()));
+            if let Some(found) = found {
+                report.warnings.push(ApplyWarning::IndentationMismatch {
+                    hunk: hunk_index,
+                    expected,
+                    found,
+                });
+            }
+        }
+        let mut image_lines = pre_image.iter().map(|image_line| image_line.into_inner());
+        let resolved_lines: Vec<_> = hunk
+            .lines()
+            .iter()
+            .map(|line| match line {
+                Line::Context(_) => Line::Context(image_lines.next().unwrap()),
+                Line::Delete(_) => Line::Delete(image_lines.next().unwrap()),
+                Line::Insert(line) => Line::Insert(*line),
+            })
+            .collect();
+        let replaced = hunk
+            .lines()
+            .iter()
+            .filter(|line| !matches!(line, Line::Insert(_)))
+            .zip(pre_image)
+            .filter(|(line, _)| matches!(line, Line::Delete(_)))
+            .map(|(_, image_line)| image_line.into_inner())
+            .collect();
+
+        let hunk_stats = HunkStats {
+            context_matched,
+            ..HunkStats::new(hunk)
         };
-        stats.add_hunk(hunk_stats);
+        let delta = hunk_stats.added as isize - hunk_stats.deleted as isize;
+        // Hunks which left nothing behind at `pos` (pure deletions) come before this one
+        let is_before = |start: usize, len: usize| start < pos || (start == pos && len == 0);
+        let old_start = applied
+            .iter()
+            .filter(|(start, len, _)| is_before(*start, *len))
+            .fold(pos as isize, |old_start, (_, _, delta)| old_start - delta)
+            as usize;
+        for (start, len, _) in &mut applied {
+            if !is_before(*start, *len) {
+                *start = start.checked_add_signed(delta).unwrap();
+            }
+        }
+        applied.push((pos, hunk_stats.context + hunk_stats.added, delta));
+
+        let old_len = hunk_stats.context + hunk_stats.deleted;
+        let old_range = HunkRange::from_index(old_start, old_len);
+        resolved.push(Hunk::new(
+            old_range,
+            old_range,
+            hunk.function_context(),
+            resolved_lines,
+        ));
+        report.hunks.push(HunkReport {
+            old_range,
+            replaced,
+            offset: old_start as isize - hunk.old_range().index() as isize,
+        });
+        report.stats.add_hunk(hunk_stats);
+        on_hunk_done(&report.hunks[hunk_index], &report.stats);
+    }
+
+    // Pure insertions at the same position keep their order, as they are applied in hunk order
+    resolved.sort_by_key(|hunk| hunk.old_range().index());
+    let mut delta = 0isize;
+    for hunk in &mut resolved {
+        let old_range = hunk.old_range();
+        let new_start = old_range.index().checked_add_signed(delta).unwrap();
+        hunk.set_new_range(HunkRange::from_index(new_start, 0));
+        hunk.recount();
+        delta += hunk.new_range().len() as isize - old_range.len() as isize;
     }
+    report.resolved = diff.with_hunks(resolved);
+
+    Ok((image, report))
+}
 
-    // TODO: Keep line ending as is like it was before.
-    let preferred_line_ending = Some(match config.line_end_strategy {
+// TODO: Keep line ending as is like it was before.
+fn preferred_line_ending<T: ApplyText + ?Sized>(
+    base_image: &T,
+    diff: &Diff<'_, T>,
+    config: &ApplyConfig,
+) -> LineEnd {
+    match config.line_end_strategy {
         LineEndHandling::EnsurePatchLineEnding => {
             let mut lf_score = 0usize;
             let mut crlf_score = 0usize;
@@ -531,140 +1985,180 @@ pub fn apply_bytes_with_config(
         }
         LineEndHandling::EnsureFileLineEnding => LineEnd::most_common(base_image),
         LineEndHandling::EnsureLineEnding(line_end) => line_end,
-    });
+    }
+}
 
-    let content = image
-        .into_iter()
-        .map(ImageLine::into_inner)
-        .flat_map(|(line, ending)| {
-            [
-                line,
-                map_line_ending::<&[u8]>(ending, preferred_line_ending),
-            ]
-            .concat()
-        })
-        .collect();
+/// Formats a hunk for error messages, replacing invalid UTF-8 with the replacement character
+pub(crate) fn hunk_to_string<T: AsRef<[u8]> + ToOwned + ?Sized>(hunk: &Hunk<'_, T>) -> String {
+    let mut buf = Vec::new();
+    PatchFormatter::new()
+        .write_hunk_into(hunk, &mut buf)
+        .expect("writing into a Vec can't fail");
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+/// Reasons for failing to apply a single hunk
+#[derive(Debug)]
+enum HunkError {
+    /// No position matching the hunk was found
+    NoMatch,
+    /// The comparison budget ran out while searching for a position
+    BudgetExceeded,
+}
 
-    Ok((content, stats))
+/// The remaining number of line comparisons allowed while searching for hunk positions, see
+/// [`ApplyConfig::max_match_comparisons`]
+#[derive(Debug)]
+struct MatchBudget(Option<usize>);
+
+impl MatchBudget {
+    /// Use up `comparisons` from the budget, failing if not enough are left
+    fn spend(&mut self, comparisons: usize) -> Result<(), HunkError> {
+        if let Some(remaining) = &mut self.0 {
+            *remaining = remaining
+                .checked_sub(comparisons)
+                .ok_or(HunkError::BudgetExceeded)?;
+        }
+        Ok(())
+    }
 }
 
+/// Apply `hunk` at the matching position of `image` closest to `expected`
 fn apply_hunk_with_config<'a, T>(
     image: &mut Vec<ImageLine<'a, T>>,
     hunk: &Hunk<'a, T>,
+    expected: usize,
     config: &ApplyConfig,
-) -> Result<HunkStats, ()>
+    budget: &mut MatchBudget,
+) -> Result<(usize, Vec<ImageLine<'a, T>>), HunkError>
 where
     T: PartialEq + FuzzyComparable + ?Sized + Text + ToOwned,
 {
     // Find position with fuzzy matching
-    let (pos, fuzz_level) = find_position_fuzzy(image, hunk, config).ok_or(())?;
-
-    // Count changes in this hunk
-    let mut added = 0;
-    let mut deleted = 0;
-    let mut context = 0;
-
-    for line in hunk.lines() {
-        match line {
-            Line::Insert(_) => added += 1,
-            Line::Delete(_) => deleted += 1,
-            Line::Context(_) => context += 1,
+    let (mut pos, _fuzz_level) =
+        find_position_fuzzy(image, hunk, expected, config, budget)?.ok_or(HunkError::NoMatch)?;
+
+    // Pure insertions anchored at the same position as an earlier hunk are applied in hunk
+    // order: they're placed after any lines which have already been inserted there.
+    if pre_image_line_count(hunk.lines()) == 0 {
+        while image.get(pos).is_some_and(ImageLine::is_patched) {
+            pos += 1;
         }
     }
 
-    // update image
-    if fuzz_level == 0 {
-        // Exact match - replace all lines as before
-        image.splice(
-            pos..pos + pre_image_line_count(hunk.lines()),
-            post_image(hunk.lines()).map(ImageLine::Patched),
-        );
-    } else {
-        // Fuzzy match - preserve original context lines, only apply insertions/deletions
-        apply_hunk_preserving_context(image, hunk, pos);
-    }
+    // Update the image, keeping the context lines of the target which may differ from the ones in
+    // the hunk when matching fuzzily or with relaxed match options.
+    let len = pre_image_line_count(hunk.lines());
+    let patched = patched_lines(&image[pos..pos + len], hunk.lines(), config.preserve_indent);
+    let pre_image = image.splice(pos..pos + len, patched).collect();
 
-    Ok(HunkStats {
-        added,
-        deleted,
-        context,
-    })
+    Ok((pos, pre_image))
 }
 
-/// Apply hunk while preserving original context lines (for fuzzy matching)
-fn apply_hunk_preserving_context<'a, T>(
-    image: &mut Vec<ImageLine<'a, T>>,
-    hunk: &Hunk<'a, T>,
-    pos: usize,
-) where
-    T: ?Sized + Text + ToOwned,
-{
-    let mut image_offset = 0;
-
-    for line in hunk.lines() {
-        match *line {
-            Line::Context(_) => {
-                // Keep the original context line, just mark it as patched
-                if let Some(img_line) = image.get_mut(pos + image_offset) {
-                    *img_line = ImageLine::Patched(img_line.into_inner());
-                }
-                image_offset += 1;
-            }
-            Line::Delete(_) => {
-                // Remove the line
-                image.remove(pos + image_offset);
-            }
-            Line::Insert(line) => {
-                // Insert the new line
-                image.insert(pos + image_offset, ImageLine::Patched(line));
-                image_offset += 1;
-            }
-        }
-    }
+/// Computes the lines replacing `image` when applying the hunk `lines` at its position, preserving
+/// the original context lines, and with `preserve_indent` the indentation of changed lines
+fn patched_lines<'a, T: ?Sized + Text>(
+    image: &[ImageLine<'a, T>],
+    lines: &[Line<'a, T>],
+    preserve_indent: bool,
+) -> Vec<ImageLine<'a, T>> {
+    // Pair every line of the hunk with the line of the image it corresponds to
+    let mut image = image.iter();
+    let lines: Vec<_> = lines
+        .iter()
+        .map(|line| match line {
+            Line::Context(_) | Line::Delete(_) => (*line, image.next()),
+            Line::Insert(_) => (*line, None),
+        })
+        .collect();
+
+    lines
+        .chunk_by(|(a, _), (b, _)| !matches!(a, Line::Context(_)) && !matches!(b, Line::Context(_)))
+        .flat_map(|block| {
+            // The indentation of the deleted lines of this block of changes, in order
+            let mut indents = block
+                .iter()
+                .filter(|(line, _)| preserve_indent && matches!(line, Line::Delete(_)))
+                .filter_map(|(_, image_line)| *image_line)
+                .map(|image_line| split_indent(image_line.into_inner().0).0);
+
+            block
+                .iter()
+                .filter_map(move |&(line, image_line)| match line {
+                    Line::Context(_) => image_line.map(|l| ImageLine::Patched(l.into_inner())),
+                    Line::Delete(_) => None,
+                    Line::Insert((content, ending)) => {
+                        let line = match indents.next() {
+                            Some(indent) if !split_indent(content).1.is_empty() => {
+                                ImageLine::Reindented {
+                                    indent,
+                                    line: (split_indent(content).1, ending),
+                                }
+                            }
+                            _ => ImageLine::Patched((content, ending)),
+                        };
+                        Some(line)
+                    }
+                })
+        })
+        .collect()
+}
+
+/// Split a line into its leading whitespace and the rest
+fn split_indent<T: ?Sized + Text>(line: &T) -> (&T, &T) {
+    let len = line
+        .as_bytes()
+        .iter()
+        .take_while(|&&b| b == b' ' || b == b'\t')
+        .count();
+    line.split_at(len)
 }
 
 /// Search in `image` for a place to apply hunk with fuzzy matching support
 fn find_position_fuzzy<T>(
     image: &[ImageLine<T>],
     hunk: &Hunk<'_, T>,
+    start: usize,
     config: &ApplyConfig,
-) -> Option<(usize, usize)>
+    budget: &mut MatchBudget,
+) -> Result<Option<(usize, usize)>, HunkError>
 where
     T: PartialEq + FuzzyComparable + ?Sized + Text + ToOwned,
 {
     // Try exact match first (fuzz level 0)
-    if let Some(pos) = find_position(image, hunk) {
-        return Some((pos, 0));
+    if let Some(pos) = find_position(image, hunk, start, config, budget)? {
+        return Ok(Some((pos, 0)));
     }
 
     // Try fuzzy matching with increasing fuzz levels
     for fuzz_level in 1..=config.fuzzy_config.max_fuzz {
-        if let Some(pos) = find_position_with_fuzz(image, hunk, fuzz_level, config) {
-            return Some((pos, fuzz_level));
+        if let Some(pos) = find_position_with_fuzz(image, hunk, start, fuzz_level, config, budget)?
+        {
+            return Ok(Some((pos, fuzz_level)));
         }
     }
 
-    None
+    Ok(None)
 }
 
 /// Find position with specified fuzz level
 fn find_position_with_fuzz<T>(
     image: &[ImageLine<T>],
     hunk: &Hunk<'_, T>,
+    start: usize,
     fuzz_level: usize,
     config: &ApplyConfig,
-) -> Option<usize>
+    budget: &mut MatchBudget,
+) -> Result<Option<usize>, HunkError>
 where
     T: PartialEq + FuzzyComparable + ?Sized + Text + ToOwned,
 {
-    let pos = std::cmp::min(hunk.new_range().start().saturating_sub(1), image.len());
-
-    let backward = (0..pos).rev();
-    let forward = pos + 1..image.len();
-
-    iter::once(pos)
-        .chain(interleave(backward, forward))
-        .find(|&pos| match_fragment_fuzzy(image, hunk.lines(), pos, fuzz_level, config))
+    for pos in search_positions(start, image.len(), config.max_offset) {
+        if match_fragment_fuzzy(image, hunk.lines(), pos, fuzz_level, config, budget)? {
+            return Ok(Some(pos));
+        }
+    }
+    Ok(None)
 }
 
 /// Match fragment with fuzzy context matching
@@ -674,7 +2168,8 @@ fn match_fragment_fuzzy<T>(
     pos: usize,
     fuzz_level: usize,
     config: &ApplyConfig,
-) -> bool
+    budget: &mut MatchBudget,
+) -> Result<bool, HunkError>
 where
     T: PartialEq + FuzzyComparable + ?Sized + Text,
 {
@@ -683,19 +2178,19 @@ where
     let image_slice = if let Some(image) = image.get(pos..pos + len) {
         image
     } else {
-        return false;
+        return Ok(false);
     };
 
     // If any of these lines have already been patched then we can't match at this position
     if image_slice.iter().any(ImageLine::is_patched) {
-        return false;
+        return Ok(false);
     }
 
     let pre_image_lines: Vec<_> = pre_image(lines).collect();
     let image_lines: Vec<_> = image_slice.iter().map(ImageLine::inner).collect();
 
     if pre_image_lines.len() != image_lines.len() {
-        return false;
+        return Ok(false);
     }
 
     // Get context line indices from the original lines
@@ -710,12 +2205,18 @@ where
 
     // Map context indices to pre_image indices
     let mut pre_image_context_indices = Vec::new();
+    // Context lines which match any line, see `ApplyConfig::volatile_lines`
+    let mut volatile_indices = Vec::new();
     let mut pre_image_idx = 0;
     for (original_idx, line) in lines.iter().enumerate() {
         match line {
             Line::Context(_) | Line::Delete(_) => {
                 if context_indices.contains(&original_idx) {
                     pre_image_context_indices.push(pre_image_idx);
+                    let line = pre_image_lines[pre_image_idx].0;
+                    if is_volatile(line, config.volatile_lines.as_deref()) {
+                        volatile_indices.push(pre_image_idx);
+                    }
                 }
                 pre_image_idx += 1;
             }
@@ -731,38 +2232,40 @@ where
         let image = if let Some(image) = image.get(pos..pos + len) {
             image
         } else {
-            return false;
+            return Ok(false);
         };
 
         // If any of these lines have already been patched then we can't match at this position
         if image.iter().any(ImageLine::is_patched) {
-            return false;
-        }
-
-        for (pre_line, image_line) in pre_image_lines.iter().zip(image_lines.iter()) {
-            if !pre_line.0.fuzzy_eq(image_line.0, config) {
-                return false;
-            }
+            return Ok(false);
         }
 
-        return true;
+        budget.spend(len)?;
+        return Ok(match_with_ignored_context(
+            pre_image_lines.as_slice(),
+            &image_lines,
+            &volatile_indices,
+            config,
+        ));
     }
 
     // Try different combinations of ignoring context lines
     let combinations = generate_fuzz_combinations(&pre_image_context_indices, fuzz_level);
 
-    for ignored_indices in combinations {
+    for mut ignored_indices in combinations {
+        ignored_indices.extend_from_slice(&volatile_indices);
+        budget.spend(len)?;
         if match_with_ignored_context(
             pre_image_lines.as_slice(),
             &image_lines,
             &ignored_indices,
             config,
         ) {
-            return true;
+            return Ok(true);
         }
     }
 
-    false
+    Ok(false)
 }
 
 /// Generate combinations of context line indices to ignore using GNU patch-style edge fuzz.
@@ -840,19 +2343,44 @@ where
 fn find_position<T: PartialEq + ?Sized + Text + ToOwned>(
     image: &[ImageLine<T>],
     hunk: &Hunk<'_, T>,
-) -> Option<usize> {
+    start: usize,
+    config: &ApplyConfig,
+    budget: &mut MatchBudget,
+) -> Result<Option<usize>, HunkError> {
+    let options = config.fuzzy_config.match_options();
+    let len = pre_image_line_count(hunk.lines());
+    for pos in search_positions(start, image.len(), config.max_offset) {
+        budget.spend(len)?;
+        let volatile_lines = config.volatile_lines.as_deref();
+        if match_fragment(image, hunk.lines(), pos, &options, volatile_lines) {
+            return Ok(Some(pos));
+        }
+    }
+    Ok(None)
+}
+
+/// The positions of the image to look for a hunk expected at `start`, ordered by their distance
+/// to it and limited to `max_offset`, see [`ApplyConfig::max_offset`]
+fn search_positions(
+    start: usize,
+    image_len: usize,
+    max_offset: Option<usize>,
+) -> impl Iterator<Item = usize> {
+    let max_offset = max_offset.unwrap_or(usize::MAX);
     // In order to avoid searching through positions which are out of bounds of the image,
     // clamp the starting position based on the length of the image
-    let pos = std::cmp::min(hunk.new_range().start().saturating_sub(1), image.len());
-
-    // Create an iterator that starts with 'pos' and then interleaves
-    // moving pos backward/foward by one.
-    let backward = (0..pos).rev();
-    let forward = pos + 1..image.len();
-
+    let pos = std::cmp::min(start, image_len);
+
+    // Start with 'pos' and then interleave moving pos backward/forward by one.
+    let backward = (start.saturating_sub(max_offset)..pos).rev();
+    let forward = pos + 1
+        ..std::cmp::min(
+            image_len,
+            start.saturating_add(max_offset).saturating_add(1),
+        );
     iter::once(pos)
+        .filter(move |pos| start - pos <= max_offset)
         .chain(interleave(backward, forward))
-        .find(|&pos| match_fragment(image, hunk.lines(), pos))
 }
 
 fn pre_image_line_count<T: ?Sized>(lines: &[Line<'_, T>]) -> usize {
@@ -881,6 +2409,8 @@ fn match_fragment<T: PartialEq + ?Sized + Text>(
     image: &[ImageLine<T>],
     lines: &[Line<'_, T>],
     pos: usize,
+    options: &MatchOptions,
+    volatile_lines: Option<&LinePredicate>,
 ) -> bool {
     let len = pre_image_line_count(lines);
 
@@ -895,7 +2425,21 @@ fn match_fragment<T: PartialEq + ?Sized + Text>(
         return false;
     }
 
-    pre_image(lines).eq(image.iter().map(ImageLine::inner))
+    lines
+        .iter()
+        .filter(|line| !matches!(line, Line::Insert(_)))
+        .zip(image.iter().map(ImageLine::inner))
+        .all(|(line, image_line)| match *line {
+            Line::Context(line) if is_volatile(line.0, volatile_lines) => true,
+            Line::Context(line) | Line::Delete(line) | Line::Insert(line) => {
+                line.1 == image_line.1 && line.0.matches(image_line.0, options)
+            }
+        })
+}
+
+/// Returns `true` if `line` is recognized by [`ApplyConfig::volatile_lines`]
+fn is_volatile<T: ?Sized + Text>(line: &T, volatile_lines: Option<&LinePredicate>) -> bool {
+    volatile_lines.is_some_and(|is_volatile| is_volatile(line.as_bytes()))
 }
 
 #[derive(Debug)]
@@ -948,10 +2492,59 @@ mod test {
     use std::path::PathBuf;
 
     use crate::{
-        ApplyConfig, ApplyOutcome, Diff, FuzzyConfig, apply, apply_bytes_reporting,
-        is_diff_applied_with_config,
+        ApplyConfig, ApplyError, ApplyOutcome, ApplyWarning, Diff, FuzzyConfig, HunkState,
+        Indentation, Line, LineEnd, MatchOptions, apply, apply_bytes_reporting,
+        apply_bytes_with_config, apply_to_path, apply_to_writer, apply_with_config,
+        apply_with_report, apply_within, is_diff_applied_with_config,
     };
 
+    #[test]
+    fn apply_to_path_writes_rejects() {
+        let dir =
+            std::env::temp_dir().join(format!("flickzeug-apply-to-path-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "a\nb\nc\nd\ne\nf\n").unwrap();
+
+        // The second hunk doesn't apply, so it ends up in the reject file
+        let patch = "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-a\n+A\n\
+                     @@ -3 +3 @@\n-x\n+X\n@@ -6 +6 @@\n-f\n+F\n";
+        let diff = Diff::from_str(patch).unwrap();
+        let report = apply_to_path(&dir, &diff).unwrap();
+        assert_eq!(report.path, dir.join("src/lib.rs"));
+        assert_eq!(report.reject_path, Some(dir.join("src/lib.rs.rej")));
+        assert_eq!(report.rejected, [1]);
+        assert_eq!(report.stats.hunks_applied, 2);
+        assert_eq!(
+            std::fs::read_to_string(&report.path).unwrap(),
+            "A\nb\nc\nd\ne\nF\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("src/lib.rs.rej")).unwrap(),
+            "--- src/lib.rs\n+++ src/lib.rs\n@@ -3 +3 @@\n-x\n+X\n"
+        );
+
+        // Renames move the file once every hunk applied, and created files start out empty
+        let renamed = "diff --git a/src/lib.rs b/src/main.rs\nrename from src/lib.rs\n\
+                       rename to src/main.rs\n--- a/src/lib.rs\n+++ b/src/main.rs\n\
+                       @@ -1 +1 @@\n-A\n+a\n";
+        let report = apply_to_path(&dir, &Diff::from_str(renamed).unwrap()).unwrap();
+        assert_eq!(report.reject_path, None);
+        assert!(!dir.join("src/lib.rs").exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("src/main.rs")).unwrap(),
+            "a\nb\nc\nd\ne\nF\n"
+        );
+        let created = Diff::from_str("--- /dev/null\n+++ b/new\n@@ -0,0 +1 @@\n+new\n").unwrap();
+        apply_to_path(&dir, &created).unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("new")).unwrap(), "new\n");
+
+        let unnamed = Diff::from_str("@@ -1 +1 @@\n-a\n+b\n").unwrap();
+        let err = apply_to_path(&dir, &unnamed).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
     fn load_files(name: &str) -> (String, String) {
         let base_folder = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
             .join("test-data")
@@ -980,6 +2573,169 @@ mod test {
         println!("Result:\n{}", result);
     }
 
+    #[test]
+    fn bytes_and_str_agree() {
+        for name in ["fuzzy", "no-newline-both"] {
+            let (base_image, patch) = load_files(name);
+            let (content, stats) = apply(&base_image, &Diff::from_str(&patch).unwrap()).unwrap();
+
+            let diff = Diff::from_bytes(patch.as_bytes()).unwrap();
+            let (bytes, bytes_stats) = crate::apply_bytes(base_image.as_bytes(), &diff).unwrap();
+            assert_eq!(bytes, content.as_bytes(), "{name}");
+            assert_eq!(bytes_stats, stats, "{name}");
+        }
+    }
+
+    #[test]
+    fn interleaved_deletes_and_inserts() {
+        let base_image = "a\nb\nc\nd\ne\n";
+        let patch = "@@ -1,5 +1,5 @@\n a\n-b\n+B\n-c\n+C\n d\n e\n";
+        let diff = Diff::from_str(patch).unwrap();
+        let deletes: Vec<_> = diff.hunks()[0]
+            .lines()
+            .iter()
+            .map(Line::is_delete)
+            .collect();
+        assert_eq!(deletes, [false, true, false, true, false, false, false]);
+
+        let (content, stats) = apply(base_image, &diff).unwrap();
+        assert_eq!(content, "a\nB\nC\nd\ne\n");
+        assert_eq!((stats.lines_added, stats.lines_deleted), (2, 2));
+
+        // The interleaved hunk applies like the one with the deletes grouped before the inserts
+        let grouped = Diff::from_str("@@ -1,5 +1,5 @@\n a\n-b\n-c\n+B\n+C\n d\n e\n").unwrap();
+        assert_eq!(apply(base_image, &grouped).unwrap().0, content);
+        let (reversed, _) = crate::apply_reverse(&content, &diff).unwrap();
+        assert_eq!(reversed, base_image);
+    }
+
+    #[test]
+    fn apply_to_writer_matches_apply() {
+        for name in ["fuzzy", "no-newline-both"] {
+            let (base_image, patch) = load_files(name);
+            let diff = Diff::from_str(&patch).unwrap();
+            let (content, stats) = apply(&base_image, &diff).unwrap();
+
+            let mut out = Vec::new();
+            assert_eq!(
+                apply_to_writer(&*base_image, &diff, &mut out).unwrap(),
+                stats
+            );
+            assert_eq!(out, content.as_bytes(), "{name}");
+
+            let diff = Diff::from_bytes(patch.as_bytes()).unwrap();
+            let mut out = Vec::new();
+            apply_to_writer(base_image.as_bytes(), &diff, &mut out).unwrap();
+            assert_eq!(out, content.as_bytes(), "{name}");
+        }
+
+        // Nothing is written if the diff doesn't apply
+        let diff = Diff::from_str("@@ -1 +1 @@\n-x\n+y\n").unwrap();
+        let mut out = Vec::new();
+        let err = apply_to_writer("a\n", &diff, &mut out).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(
+            err.get_ref().unwrap().downcast_ref(),
+            Some(ApplyError::ContextMismatch { .. })
+        ));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn apply_within_range() {
+        let base_image = "a\nb\nc\nd\ne\nf\n";
+        let diff = Diff::from_str("@@ -1,2 +1,2 @@\n-a\n+A\n b\n@@ -4,2 +4,3 @@\n d\n-e\n+E\n+x\n")
+            .unwrap();
+
+        // Only the second hunk is within lines 3 to 6
+        let (content, stats, skipped) = apply_within(base_image, &diff, 3..7).unwrap();
+        assert_eq!(content, "a\nb\nc\nd\nE\nx\nf\n");
+        assert_eq!((stats.hunks_applied, stats.lines_added), (1, 2));
+        assert_eq!(skipped, [0]);
+
+        // The first hunk crosses the boundary of lines 2 to 6, so it's skipped as well
+        let (content, _, skipped) = apply_within(base_image, &diff, 2..7).unwrap();
+        assert_eq!(content, "a\nb\nc\nd\nE\nx\nf\n");
+        assert_eq!(skipped, [0]);
+
+        let (content, _, skipped) = apply_within(base_image, &diff, 1..7).unwrap();
+        assert_eq!(content, apply(base_image, &diff).unwrap().0);
+        assert!(skipped.is_empty());
+
+        // Errors refer to the hunks by their index in the diff
+        let err = apply_within("a\nb\nc\nd\nx\nf\n", &diff, 3..7).unwrap_err();
+        assert!(matches!(
+            err,
+            ApplyError::ContextMismatch { hunk_index: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn ignore_trailing_whitespace() {
+        let base_image = "fn main() {  \n    foo(); \n}\t\n";
+        let diff = Diff::from_str("@@ -1,3 +1,3 @@\n fn main() {\n-    foo();\n+    bar();\n }\n")
+            .unwrap();
+        // Fuzzy matching would accept lines which only differ slightly
+        let mut config = ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                max_fuzz: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(apply_with_config(base_image, &diff, &config).is_err());
+
+        config.fuzzy_config.ignore_trailing_whitespace = true;
+        let (content, stats) = apply_with_config(base_image, &diff, &config).unwrap();
+        // The context lines keep their trailing whitespace
+        assert_eq!(content, "fn main() {  \n    bar();\n}\t\n");
+        assert_eq!((stats.lines_added, stats.lines_deleted), (1, 1));
+
+        let diff =
+            Diff::from_bytes(b"@@ -1,3 +1,3 @@\n fn main() {\n-    foo();\n+    bar();\n }\n")
+                .unwrap();
+        let (content, _) = apply_bytes_with_config(base_image.as_bytes(), &diff, &config).unwrap();
+        assert_eq!(content, b"fn main() {  \n    bar();\n}\t\n");
+
+        // Leading whitespace still has to match
+        let options = config.fuzzy_config.match_options();
+        assert!(options.matches("foo();  ", "foo();"));
+        assert!(!options.matches("  foo();", "foo();"));
+
+        // Lines which aren't valid utf8 have the same trailing whitespace removed
+        assert!(!options.matches("foo();\u{a0}", "foo();"));
+        assert!(!options.matches(b"\xfffoo();\xc2\xa0".as_slice(), b"\xfffoo();".as_slice()));
+        assert!(options.matches(b"\xfffoo();\t ".as_slice(), b"\xfffoo();".as_slice()));
+    }
+
+    #[test]
+    fn no_newline_at_eof_on_both_sides() {
+        let (base_image, patch) = load_files("no-newline-both");
+        let expected = "first line\nsecond line\nlast line, changed";
+
+        let diff = Diff::from_str(&patch).unwrap();
+        let lines = diff.hunks()[0].lines();
+        assert_eq!(lines[2], Line::Delete(("last line", None)));
+        assert_eq!(lines[3], Line::Insert(("last line, changed", None)));
+
+        let (content, _) = apply(&base_image, &diff).unwrap();
+        assert_eq!(content, expected);
+        let (content, _) = crate::apply_bytes(
+            base_image.as_bytes(),
+            &Diff::from_bytes(patch.as_bytes()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(content, expected.as_bytes());
+
+        // Round trip through the formatter and a freshly created diff
+        assert!(patch.ends_with(&diff.hunks()[0].to_string()));
+        assert_eq!(Diff::from_str(&diff.to_string()).unwrap(), diff);
+        let created = crate::create_patch(&base_image, expected);
+        assert_eq!(created.hunks(), diff.hunks());
+        let (reverted, _) = apply(expected, &diff.reverse()).unwrap();
+        assert_eq!(reverted, base_image);
+    }
+
     fn assert_patch(old: &str, new: &str, patch: &str) {
         let diff = Diff::from_str(patch).unwrap();
         let (content, _stats) = apply(old, &diff).unwrap();
@@ -1011,6 +2767,347 @@ mod test {
         assert!(stats.has_changes());
     }
 
+    #[test]
+    fn diff_stats() {
+        let old = "line 1\nline 2\nline 3\nline 4\nline 5\n";
+        let patch = "\
+@@ -1,3 +1,2 @@
+ line 1
+-line 2
+-line 3
++line 2 modified
+@@ -5,0 +5,2 @@
++line 6
++line 7
+";
+        let diff = Diff::from_str(patch).unwrap();
+        let (content, stats) = apply(old, &diff).unwrap();
+        assert_eq!(diff.stats(), stats);
+
+        let reverse_stats = diff.reverse_stats();
+        assert_eq!(reverse_stats.lines_added, 2);
+        assert_eq!(reverse_stats.lines_deleted, 3);
+        assert_eq!(reverse_stats.lines_context, 1);
+        assert_eq!(reverse_stats.hunks_applied, 2);
+
+        let (_, stats) = apply(&content, &diff.reverse()).unwrap();
+        assert_eq!(reverse_stats, stats);
+    }
+
+    #[test]
+    fn context_lines_matched() {
+        let patch = Diff::from_str(
+            "\
+@@ -1,7 +1,7 @@
+ one
+ two
+ three
+-four
++4
+ five
+ six
+ seven
+",
+        )
+        .unwrap();
+
+        let (_, stats) = apply("one\ntwo\nthree\nfour\nfive\nsix\nseven\n", &patch).unwrap();
+        assert_eq!((stats.context_lines_matched, stats.lines_context), (6, 6));
+
+        // The first context line has changed and is ignored by fuzz, the second one is only
+        // similar to the target
+        let target = "uno\ntwo!\nthree\nfour\nfive\nsix\nseven\n";
+        let (content, stats) = apply(target, &patch).unwrap();
+        assert_eq!(content, "uno\ntwo!\nthree\n4\nfive\nsix\nseven\n");
+        assert_eq!((stats.context_lines_matched, stats.lines_context), (4, 6));
+    }
+
+    #[test]
+    fn hunk_states() {
+        let diff = Diff::from_str(
+            "\
+@@ -1,3 +1,3 @@
+ fn main() {
+-    let x = 1;
++    let x = 2;
+ }
+@@ -10,3 +10,3 @@
+ fn other() {
+-    call(1, 2);
++    call(1, 3);
+ }
+@@ -20,3 +20,3 @@
+ fn last() {
+-    old();
++    new();
+ }
+",
+        )
+        .unwrap();
+
+        let mut base = vec!["filler"; 25];
+        base[0..3].copy_from_slice(&["fn main() {", "    let x = 1;", "}"]);
+        base[9..12].copy_from_slice(&["fn other() {", "    call( 1 ,  3 );", "}"]);
+        base[19..22].copy_from_slice(&["fn last() {", "    unrelated();", "}"]);
+        let base = base.join("\n") + "\n";
+
+        // The second hunk is applied with different whitespace
+        assert_eq!(
+            diff.hunk_states(&base),
+            [
+                HunkState::Pending,
+                HunkState::Conflicts,
+                HunkState::Conflicts
+            ]
+        );
+
+        let config = ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                max_fuzz: 0,
+                ignore_whitespace: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            diff.hunk_states_with_config(&base, &config),
+            [
+                HunkState::Pending,
+                HunkState::AlreadyApplied,
+                HunkState::Conflicts
+            ]
+        );
+
+        // Applying a pending hunk changes its state
+        let first =
+            Diff::from_str("@@ -1,3 +1,3 @@\n fn main() {\n-    let x = 1;\n+    let x = 2;\n }\n")
+                .unwrap();
+        let (patched, _) = crate::apply_with_config(&base, &first, &config).unwrap();
+        assert_eq!(
+            diff.hunk_states_with_config(&patched, &config),
+            [
+                HunkState::AlreadyApplied,
+                HunkState::AlreadyApplied,
+                HunkState::Conflicts
+            ]
+        );
+    }
+
+    #[test]
+    fn progress_callback() {
+        let diff = Diff::from_bytes(b"@@ -1 +1 @@\n-a\n+A\n@@ -3 +3 @@\n-c\n+C\n").unwrap();
+
+        let mut reports = Vec::new();
+        let (_, stats) = crate::apply_bytes_with_progress(
+            b"a\nb\nc\n",
+            &diff,
+            &ApplyConfig::default(),
+            |hunk, stats| reports.push((hunk.old_range.start(), stats.clone())),
+        )
+        .unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].0, 1);
+        assert_eq!(reports[0].1.hunks_applied, 1);
+        assert_eq!(reports[1], (3, stats));
+
+        // Hunks which fail to apply aren't reported
+        let mut calls = 0;
+        crate::apply_bytes_with_progress(b"a\nb\nx\n", &diff, &ApplyConfig::default(), |_, _| {
+            calls += 1
+        })
+        .unwrap_err();
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn indentation_mismatch() {
+        let base = "fn main() {\n    let a = 1;\n    if a > 0 {\n        run();\n    }\n}\n";
+        let diff = Diff::from_str(
+            "\
+@@ -3,3 +3,4 @@
+     if a > 0 {
+         run();
++\t\tstop();
+     }
+@@ -6 +7,2 @@
+ }
++fn other() {}
+",
+        )
+        .unwrap();
+        let config = ApplyConfig {
+            lint_indentation: true,
+            ..Default::default()
+        };
+
+        let (content, report) = apply_with_report(base, &diff, &config).unwrap();
+        assert!(content.contains("\t\tstop();"));
+        assert_eq!(
+            report.warnings,
+            [ApplyWarning::IndentationMismatch {
+                hunk: 0,
+                expected: Indentation::Spaces(4),
+                found: Indentation::Tabs,
+            }]
+        );
+        assert_eq!(
+            report.warnings[0].to_string(),
+            "hunk #1 inserts a line indented with tabs, but the file uses 4 spaces"
+        );
+
+        // Lints are opt-in
+        let (_, report) = apply_with_report(base, &diff, &ApplyConfig::default()).unwrap();
+        assert!(report.warnings.is_empty());
+
+        // Matching indentation is fine
+        let tabbed = base.replace("    ", "\t");
+        let (_, report) = apply_with_report(&tabbed, &diff, &config).unwrap();
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn volatile_lines() {
+        use std::sync::Arc;
+
+        let diff = Diff::from_str(
+            "\
+@@ -1,3 +1,4 @@
+ // Generated at 2024-01-01 12:00:00
+ const A: u32 = 1;
++const B: u32 = 2;
+ const C: u32 = 3;
+",
+        )
+        .unwrap();
+        let base = "// Generated at 2025-06-30 08:15:42\nconst A: u32 = 1;\nconst C: u32 = 3;\n";
+        let expected = "// Generated at 2025-06-30 08:15:42\nconst A: u32 = 1;\nconst B: u32 = 2;\nconst C: u32 = 3;\n";
+
+        let exact = FuzzyConfig {
+            max_fuzz: 0,
+            ..Default::default()
+        };
+        let config = ApplyConfig {
+            fuzzy_config: exact.clone(),
+            volatile_lines: Some(Arc::new(|line| line.starts_with(b"// Generated at"))),
+            ..Default::default()
+        };
+        assert_eq!(
+            crate::apply_with_config(base, &diff, &config).unwrap().0,
+            expected
+        );
+
+        // Without the matcher the timestamp has to be fuzzed away
+        let strict = ApplyConfig {
+            fuzzy_config: exact,
+            ..Default::default()
+        };
+        assert!(crate::apply_with_config(base, &diff, &strict).is_err());
+
+        // Volatile lines are ignored on top of the fuzz factor
+        let base = base.replace("const C", "const D");
+        let fuzzy = ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                max_fuzz: 1,
+                ..Default::default()
+            },
+            ..config.clone()
+        };
+        assert_eq!(
+            crate::apply_with_config(&base, &diff, &fuzzy).unwrap().0,
+            expected.replace("const C", "const D")
+        );
+
+        // Hardened diffs only apply where their context matches exactly
+        let old = "// Generated at 2024-01-01 12:00:00\nconst A: u32 = 1;\nconst C: u32 = 3;\n";
+        let hardened = diff.harden(old, 3).unwrap();
+        assert!(crate::apply_with_config(old, &hardened, &config).is_ok());
+        assert!(matches!(
+            crate::apply_with_config(&old.replace("2024-01-01", "2025-06-30"), &hardened, &config),
+            Err(ApplyError::ContextMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn check_without_output() {
+        let (base_image, patch) = load_files("fuzzy");
+        let diff = Diff::from_str(&patch).unwrap();
+        let config = ApplyConfig::default();
+
+        let (_, report) = apply_with_report(&base_image, &diff, &config).unwrap();
+        assert_eq!(crate::apply_check(&base_image, &diff, &config), Ok(report));
+        let diff_bytes = Diff::from_bytes(patch.as_bytes()).unwrap();
+        let (_, report) =
+            crate::apply_bytes_with_report(base_image.as_bytes(), &diff_bytes, &config).unwrap();
+        assert_eq!(
+            crate::apply_bytes_check(base_image.as_bytes(), &diff_bytes, &config),
+            Ok(report)
+        );
+
+        assert_eq!(
+            crate::apply_check("", &diff, &config).unwrap_err(),
+            crate::apply("", &diff).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn resolved_diff() {
+        let diff = Diff::from_str(
+            "\
+@@ -1,4 +1,4 @@
+ a
+ b
+-c
++C
+ d
+@@ -10,3 +10,4 @@
+ x
++inserted
+ y
+ z
+",
+        )
+        .unwrap();
+        // Both hunks moved, the first one's context changed and the second one comes first now
+        let base = "x\ny\nz\n1\n2\na\nB\nc\nd\n";
+
+        let (content, report) =
+            crate::apply_with_report(base, &diff, &ApplyConfig::default()).unwrap();
+        assert_eq!(content, "x\ninserted\ny\nz\n1\n2\na\nB\nC\nd\n");
+
+        let expected = "\
+@@ -1,3 +1,4 @@
+ x
++inserted
+ y
+ z
+@@ -6,4 +7,4 @@
+ a
+ B
+-c
++C
+ d
+";
+        assert_eq!(report.resolved.to_string(), expected);
+
+        let exact = ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                max_fuzz: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            crate::apply_with_config(base, &report.resolved, &exact)
+                .unwrap()
+                .0,
+            content
+        );
+        // The resolved diff is already where it applies
+        let (_, resolved_report) =
+            crate::apply_with_report(base, &report.resolved, &exact).unwrap();
+        assert_eq!(resolved_report.resolved, report.resolved);
+    }
+
     #[test]
     fn test_apply_result_no_changes() {
         let old = "line 1\nline 2\n";
@@ -1086,6 +3183,411 @@ mod test {
         assert!(result.is_err(), "Applying the same patch twice should fail");
     }
 
+    #[test]
+    fn inserts_at_the_same_position_apply_in_hunk_order() {
+        let old = "x\ny\n";
+        let new = "x\na\nb\ny\n";
+
+        // Both hunks anchored after line 1, with the new ranges accounting for the first hunk
+        let patch = "\
+@@ -1,0 +2 @@
++a
+@@ -1,0 +3 @@
++b
+";
+        let diff = Diff::from_str(patch).unwrap();
+        let (content, stats) = apply(old, &diff).unwrap();
+        assert_eq!(content, new);
+        assert_eq!(stats.lines_added, 2);
+        assert_eq!(stats.hunks_applied, 2);
+
+        // Both hunks declaring the exact same anchor
+        let patch = "\
+@@ -1,0 +2 @@
++a
+@@ -1,0 +2 @@
++b
+";
+        let diff = crate::patch_from_str_with_config(
+            patch,
+            crate::ParserConfig {
+                skip_order_check: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+        .remove(0);
+        let (content, stats) = apply(old, &diff).unwrap();
+        assert_eq!(content, new);
+        assert_eq!(stats.lines_added, 2);
+        assert_eq!(stats.hunks_applied, 2);
+    }
+
+    #[test]
+    fn semantically_eq() {
+        let base = "a\nb\nc\nd\ne\nf\n";
+        let new = "a\nB\nc\nd\nE\nf\n";
+        let grouped = crate::DiffOptions::new()
+            .set_context_len(3)
+            .create_patch(base, new);
+        let split = crate::DiffOptions::new()
+            .set_context_len(0)
+            .create_patch(base, new);
+        assert_eq!((grouped.hunks().len(), split.hunks().len()), (1, 2));
+        assert_ne!(grouped, split);
+        assert!(grouped.semantically_eq(&split, base));
+        assert!(split.semantically_eq(&grouped, base));
+
+        // The changes have to be the same
+        let other = crate::create_patch(base, "a\nB\nc\nd\ne\nf\n");
+        assert!(!grouped.semantically_eq(&other, base));
+
+        // Diffs which don't apply are never equal
+        assert!(!grouped.semantically_eq(&grouped, "x\ny\n"));
+
+        let (base, new) = (base.as_bytes(), new.as_bytes());
+        let grouped = crate::create_patch_bytes(base, new);
+        let split = crate::DiffOptions::new()
+            .set_context_len(0)
+            .create_patch_bytes(base, new);
+        assert!(grouped.semantically_eq(&split, base));
+    }
+
+    #[test]
+    fn is_noop() {
+        let base = "fn main() {\n    foo();\n}\n";
+
+        // No changes at all
+        assert!(crate::create_patch(base, base).is_noop(base));
+        assert!(
+            Diff::from_str("@@ -1,2 +1,2 @@\n fn main() {\n     foo();\n")
+                .unwrap()
+                .is_noop(base)
+        );
+
+        // Already applied
+        let diff = Diff::from_str("@@ -2 +2 @@\n-    bar();\n+    foo();\n").unwrap();
+        assert!(diff.is_noop(base));
+        assert!(!diff.is_noop("fn main() {\n    bar();\n}\n"));
+
+        // Whitespace-only changes
+        let diff = Diff::from_str("@@ -2 +2 @@\n-    foo();\n+\tfoo();\n").unwrap();
+        assert!(!diff.is_noop(base));
+        let config = ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                ignore_whitespace: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(diff.is_noop_with_config(base, &config));
+
+        // Insignificant changes still need to apply
+        let diff =
+            Diff::from_str("@@ -1,2 +1,2 @@\n fn other() {\n-    foo();\n+\tfoo();\n").unwrap();
+        let config = ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                max_fuzz: 0,
+                ..config.fuzzy_config
+            },
+            ..config
+        };
+        assert!(!diff.is_noop_with_config(base, &config));
+
+        // The same checks work for non-utf8 diffs
+        let diff = Diff::from_bytes(b"@@ -2 +2 @@\n-    foo();\n+    baz();\n").unwrap();
+        assert!(!diff.is_noop(base.as_bytes()));
+        assert!(diff.is_noop(b"fn main() {\n    baz();\n}\n"));
+    }
+
+    #[test]
+    fn exact_matching_uses_match_options() {
+        let base = "Fn Main() {\n    foo();\n}\n";
+        let diff =
+            Diff::from_str("@@ -1,2 +1,2 @@\n fn main() {\n-    foo();\n+    bar();\n").unwrap();
+
+        let mut config = ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                max_fuzz: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(crate::apply_with_config(base, &diff, &config).is_err());
+
+        config.fuzzy_config.ignore_case = true;
+        let (content, _) = crate::apply_with_config(base, &diff, &config).unwrap();
+        // The context line of the target is preserved
+        assert_eq!(content, "Fn Main() {\n    bar();\n}\n");
+    }
+
+    #[test]
+    fn apply_bytes_to_shift_jis() {
+        // "こんにちは" and "世界" / "日本" encoded as Shift-JIS
+        let konnichiwa: &[u8] = b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd";
+        let sekai: &[u8] = b"\x90\xa2\x8a\x45";
+        let nihon: &[u8] = b"\x93\xfa\x96\x7b";
+
+        let base = [konnichiwa, b"\n", sekai, b"\r\n"].concat();
+        let patch = [
+            b"@@ -1,2 +1,2 @@\n ",
+            konnichiwa,
+            b"\n-",
+            sekai,
+            b"\r\n+",
+            nihon,
+            b"\r\n",
+        ]
+        .concat();
+        assert!(std::str::from_utf8(&patch).is_err());
+
+        let diff = Diff::from_bytes(&patch).unwrap();
+        let (content, stats) = crate::apply_bytes(&base, &diff).unwrap();
+        assert_eq!(content, [konnichiwa, b"\n", nihon, b"\n"].concat());
+        assert_eq!(stats.lines_added, 1);
+        assert_eq!(stats.lines_deleted, 1);
+    }
+
+    #[test]
+    fn preserve_indent() {
+        let base = "fn main() {\n\tif x {\n\t\tfoo();\n\t}\n}\n";
+        let diff = Diff::from_str(
+            "\
+@@ -1,5 +1,5 @@
+ fn main() {
+     if x {
+-        foo();
++        bar();
+     }
+ }
+",
+        )
+        .unwrap();
+
+        let mut config = ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                ignore_whitespace: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (result, _) = crate::apply_with_config(base, &diff, &config).unwrap();
+        assert_eq!(result, "fn main() {\n\tif x {\n        bar();\n\t}\n}\n");
+
+        config.preserve_indent = true;
+        let (result, _) = crate::apply_with_config(base, &diff, &config).unwrap();
+        assert_eq!(result, "fn main() {\n\tif x {\n\t\tbar();\n\t}\n}\n");
+
+        // Indentation is matched positionally within each block of changes. Extra and blank
+        // inserted lines are kept as is.
+        let patch = "\
+@@ -1,4 +1,6 @@
+-  a
+-    b
++      a2
++      b2
++
++      c
+ d
+-  e
++f
+";
+        let base = "  a\n    b\nd\n  e\n";
+        let expected = "  a2\n    b2\n\n      c\nd\n  f\n";
+        let diff = Diff::from_str(patch).unwrap();
+        let (result, _) = crate::apply_with_config(base, &diff, &config).unwrap();
+        assert_eq!(result, expected);
+
+        let diff = Diff::from_bytes(patch.as_bytes()).unwrap();
+        let (result, _) = crate::apply_bytes_with_config(base.as_bytes(), &diff, &config).unwrap();
+        assert_eq!(result, expected.as_bytes());
+    }
+
+    #[test]
+    fn fraction_changed() {
+        let old: String = (1..=12).map(|i| format!("line {i}\n")).collect();
+        let new = old
+            .replace("line 3\n", "line three\n")
+            .replace("line 7\n", "")
+            .replace("line 12\n", "line 12\nline 13\n");
+        // Hunks covering the whole file
+        let diff = crate::DiffOptions::new()
+            .set_context_len(usize::MAX)
+            .create_patch(&old, &new);
+
+        let (content, stats) = apply(&old, &diff).unwrap();
+        assert_eq!(
+            (stats.lines_added, stats.lines_deleted, stats.lines_context),
+            (2, 2, 10)
+        );
+        assert_eq!(
+            stats.lines_added + stats.lines_context,
+            content.lines().count()
+        );
+        assert_eq!(
+            stats.lines_deleted + stats.lines_context,
+            old.lines().count()
+        );
+        assert_eq!(stats.fraction_changed(), 4.0 / 14.0);
+
+        let (_, stats) = apply("", &Diff::from_str("@@ -0,0 +1 @@\n+a\n").unwrap()).unwrap();
+        assert_eq!(stats.fraction_changed(), 1.0);
+        assert_eq!(
+            Diff::<str>::new(None::<&str>, None::<&str>, Vec::new())
+                .stats()
+                .fraction_changed(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn stats_by_file() {
+        let patch = crate::patch_from_str(
+            "\
+--- /dev/null
++++ b/added.txt
+@@ -0,0 +1,2 @@
++one
++two
+--- a/modified.txt
++++ b/modified.txt
+@@ -1,3 +1,3 @@
+ one
+-two
++2
+ three
+--- a/deleted.txt
++++ /dev/null
+@@ -1,3 +0,0 @@
+-one
+-two
+-three
+",
+        )
+        .unwrap();
+
+        let stats: Vec<_> = crate::stats_by_file(&patch)
+            .into_iter()
+            .map(|(path, stats)| {
+                (
+                    path.unwrap(),
+                    stats.lines_added,
+                    stats.lines_deleted,
+                    stats.hunks_applied,
+                )
+            })
+            .collect();
+        assert_eq!(
+            stats,
+            [
+                ("added.txt", 2, 0, 1),
+                ("modified.txt", 1, 1, 1),
+                ("deleted.txt", 0, 3, 1),
+            ]
+        );
+
+        // Files are named without the `a/` and `b/` prefixes, even if they are kept when parsing
+        let config = crate::ParserConfig {
+            strip_ab_prefix: false,
+            ..Default::default()
+        };
+        let patch = crate::patch_from_str_with_config(
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\n\
+             --- a/src/old.rs\n+++ /dev/null\n@@ -1 +0,0 @@\n-a\n",
+            config,
+        )
+        .unwrap();
+        let paths: Vec<_> = crate::stats_by_file(&patch)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(paths, [Some("src/lib.rs"), Some("src/old.rs")]);
+    }
+
+    #[test]
+    fn max_match_comparisons() {
+        let base: String = (0..1000).map(|i| format!("line {i}\n")).collect();
+        let at_start = Diff::from_str("@@ -1,2 +1,2 @@\n line 0\n-line 1\n+line one\n").unwrap();
+        let moved = Diff::from_str("@@ -1,2 +1,2 @@\n line 900\n-line 901\n+line 901!\n").unwrap();
+        let missing = Diff::from_str("@@ -1,2 +1,2 @@\n line x\n-line y\n+line z\n").unwrap();
+
+        let config = |max_match_comparisons| ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                max_fuzz: 0,
+                ..Default::default()
+            },
+            max_match_comparisons,
+            ..Default::default()
+        };
+
+        // A hunk at its declared position only needs to compare its own lines
+        crate::apply_with_config(&base, &at_start, &config(Some(2))).unwrap();
+        assert!(matches!(
+            crate::apply_with_config(&base, &at_start, &config(Some(1))),
+            Err(ApplyError::MatchBudgetExceeded { hunk_index: 0, .. })
+        ));
+
+        // Searching far away exhausts a small budget, but not a large one
+        assert!(matches!(
+            crate::apply_with_config(&base, &moved, &config(Some(100))),
+            Err(ApplyError::MatchBudgetExceeded { hunk_index: 0, .. })
+        ));
+        crate::apply_with_config(&base, &moved, &config(Some(10_000))).unwrap();
+
+        // Without a budget, failing hunks are reported as not matching
+        let err = crate::apply_with_config(&base, &missing, &config(None)).unwrap_err();
+        assert!(matches!(
+            err,
+            ApplyError::ContextMismatch { hunk_index: 0, .. }
+        ));
+        let err = crate::apply_with_config(&base, &missing, &config(Some(1000))).unwrap_err();
+        assert!(matches!(
+            err,
+            ApplyError::MatchBudgetExceeded { hunk_index: 0, .. }
+        ));
+        assert!(err.to_string().starts_with(
+            "error applying hunk #1: exceeded the maximum number of line comparisons\n"
+        ));
+    }
+
+    #[test]
+    fn apply_report() {
+        let base = "zero\none\nTWO\nthree\nfour\nfive\n";
+        let diff = Diff::from_str(
+            "\
+@@ -1,3 +1,3 @@
+ one
+-two
++2
+ three
+@@ -4,2 +4,3 @@
+ four
++4.5
+ five
+",
+        )
+        .unwrap();
+        let config = ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                ignore_case: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let (content, report) = crate::apply_with_report(base, &diff, &config).unwrap();
+        assert_eq!(content, "zero\none\n2\nthree\nfour\n4.5\nfive\n");
+        assert_eq!(report.stats, diff.stats());
+
+        // The hunks were applied one line further down than declared
+        assert_eq!(report.hunks[0].old_range, crate::HunkRange::new(2, 3));
+        assert_eq!(report.hunks[1].old_range, crate::HunkRange::new(5, 2));
+
+        // The actually replaced line is reported, not the one from the patch
+        assert_eq!(report.hunks[0].replaced, [("TWO", Some(LineEnd::Lf))]);
+        assert!(report.hunks[1].replaced.is_empty());
+    }
+
     #[test]
     fn line_end_strategies() {
         let old = "old line\r\n";
@@ -1137,6 +3639,383 @@ mod test {
         insta::assert_snapshot!(err_msg);
     }
 
+    #[test]
+    fn reverse_round_trip() {
+        let old = "a\nb\nc\nd\ne\nf\n";
+        // A pure insertion, a change with different line counts and a pure deletion
+        let diff =
+            crate::parse_normal("0a1,2\n> x\n> y\n2,3c4\n< b\n< c\n---\n> BC\n5d5\n< e\n").unwrap();
+
+        let (new, _) = apply(old, &diff).unwrap();
+        assert_eq!(new, "x\ny\na\nBC\nd\nf\n");
+        let (reverted, stats) = crate::apply_reverse(&new, &diff).unwrap();
+        assert_eq!(reverted, old);
+        assert_eq!(stats, diff.reverse_stats());
+
+        let diff = crate::Diff::from_bytes(b"@@ -1,2 +1 @@\n-a\n-b\n+ab\n").unwrap();
+        let (new, _) = crate::apply_bytes(b"a\nb\nc\n", &diff).unwrap();
+        assert_eq!(new, b"ab\nc\n");
+        let (reverted, _) = crate::apply_bytes_reverse(&new, &diff).unwrap();
+        assert_eq!(reverted, b"a\nb\nc\n");
+    }
+
+    #[test]
+    fn context_mismatch_location() {
+        let lines: Vec<_> = (1..=40).map(|i| i.to_string()).collect();
+        let old = lines.join("\n") + "\n";
+        let mut new_lines = lines.clone();
+        new_lines.remove(29);
+        new_lines[14] = "fifteen".to_owned();
+        new_lines.insert(2, "2.5".to_owned());
+        let new = new_lines.join("\n") + "\n";
+        let diff = crate::create_patch(&old, &new);
+        assert_eq!(diff.hunks().len(), 3);
+
+        // The third hunk deletes line 30, which differs in the target
+        let corrupted = old.replace("\n30\n", "\nthirty\n");
+        let err = apply(&corrupted, &diff).unwrap_err();
+        let ApplyError::ContextMismatch {
+            hunk_index,
+            at_line,
+            expected,
+            found,
+            ..
+        } = err
+        else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(
+            (hunk_index, at_line, expected.as_str(), found.as_str()),
+            (2, 30, "30", "thirty")
+        );
+
+        // The target ends before the hunk
+        let diff = Diff::from_str("@@ -3,2 +3 @@\n c\n-d\n").unwrap();
+        let err = apply("a\nb\nc\n", &diff).unwrap_err();
+        assert!(matches!(
+            err,
+            ApplyError::ContextMismatch { at_line: 4, ref expected, ref found, .. }
+                if expected == "d" && found.is_empty()
+        ));
+    }
+
+    #[test]
+    fn max_offset() {
+        let diff = Diff::from_str(
+            "\
+@@ -1,3 +1,3 @@
+ fn main() {
+-    old();
++    new();
+ }
+@@ -8,2 +8,3 @@
+ // end
++// more
+ 
+",
+        )
+        .unwrap();
+        let base = "fn main() {\n    old();\n}\n\n\n\n\n// end\n\n";
+        let config = |max_offset| ApplyConfig {
+            max_offset,
+            ..Default::default()
+        };
+
+        // A blank line was inserted at the start of the target
+        let shifted = format!("\n{base}");
+        let (content, report) = apply_with_report(&shifted, &diff, &config(Some(1))).unwrap();
+        assert_eq!(
+            content,
+            "\nfn main() {\n    new();\n}\n\n\n\n\n// end\n// more\n\n"
+        );
+        let offsets: Vec<_> = report.hunks.iter().map(|hunk| hunk.offset).collect();
+        assert_eq!(offsets, [1, 1]);
+        assert_eq!(report.hunks[0].old_range, crate::HunkRange::new(2, 3));
+
+        let (_, report) = apply_with_report(base, &diff, &config(Some(0))).unwrap();
+        assert!(report.hunks.iter().all(|hunk| hunk.offset == 0));
+        assert!(matches!(
+            apply_with_report(&shifted, &diff, &config(Some(0))),
+            Err(ApplyError::ContextMismatch { hunk_index: 0, .. })
+        ));
+
+        // Two matches equally far away from the declared position
+        let diff = Diff::from_str("@@ -3 +3 @@\n-x\n+y\n").unwrap();
+        let (content, report) = apply_with_report("a\nx\nb\nx\n", &diff, &config(Some(1))).unwrap();
+        assert_eq!(content, "a\ny\nb\nx\n");
+        assert_eq!(report.hunks[0].offset, -1);
+    }
+
+    #[test]
+    fn range_starting_at_line_zero() {
+        let diff = Diff::from_str("@@ -0,1 +0,1 @@\n-a\n+b\n").unwrap();
+        assert_eq!(apply("a\n", &diff).unwrap().0, "b\n");
+
+        let (_, report) = apply_with_report("a\n", &diff, &ApplyConfig::default()).unwrap();
+        assert_eq!(report.hunks[0].offset, 0);
+
+        let error = apply("x\n", &diff).unwrap_err();
+        assert!(matches!(
+            error,
+            ApplyError::ContextMismatch { hunk_index: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn pure_deletion_searched_from_its_line() {
+        // The new range of a pure deletion ends before the deleted line, so it doesn't give the
+        // position to start searching at. The deleted line is repeated on both sides of it.
+        let diff = Diff::from_str("@@ -4 +3,0 @@\n-l13\n").unwrap();
+        let base = "l2\nl13\nl1\nl13\nl1\nl13\nl1\n";
+
+        let (content, report) = apply_with_report(base, &diff, &ApplyConfig::default()).unwrap();
+        assert_eq!(content, "l2\nl13\nl1\nl1\nl13\nl1\n");
+        assert_eq!(report.hunks[0].offset, 0);
+
+        // The same holds after earlier hunks changed the number of lines
+        let diff = Diff::from_str("@@ -1 +1,2 @@\n l2\n+l3\n@@ -4 +4,0 @@\n-l13\n").unwrap();
+        let (content, report) = apply_with_report(base, &diff, &ApplyConfig::default()).unwrap();
+        assert_eq!(content, "l2\nl3\nl13\nl1\nl1\nl13\nl1\n");
+        assert_eq!(report.hunks[1].offset, 0);
+    }
+
+    #[test]
+    fn clean_apply_reports_declared_positions() {
+        let diff = Diff::from_str(
+            "\
+@@ -1,2 +1,2 @@
+-a
++A
+ b
+@@ -5,0 +6 @@
++f
+@@ -7 +8 @@
+-g
++G
+",
+        )
+        .unwrap();
+        let base = "a\nb\nc\nd\ne\nf\ng\n";
+
+        let (content, report) = apply_with_report(base, &diff, &ApplyConfig::default()).unwrap();
+        assert_eq!(content, "A\nb\nc\nd\ne\nf\nf\nG\n");
+        assert_eq!(report.hunks.len(), diff.hunks().len());
+        for (hunk, applied) in diff.hunks().iter().zip(&report.hunks) {
+            assert_eq!(applied.offset, 0);
+            assert_eq!(applied.old_range, hunk.old_range());
+        }
+
+        // Pure deletions, whose new range ends before the deleted lines, as well
+        let diff = Diff::from_str("@@ -2 +1,0 @@\n-b\n@@ -4,2 +2,0 @@\n-b\n-a\n").unwrap();
+        let (content, report) =
+            apply_with_report("a\nb\na\nb\na\nb\n", &diff, &ApplyConfig::default()).unwrap();
+        assert_eq!(content, "a\na\nb\n");
+        for (hunk, applied) in diff.hunks().iter().zip(&report.hunks) {
+            assert_eq!(applied.offset, 0);
+            assert_eq!(applied.old_range, hunk.old_range());
+        }
+        assert_eq!(report.resolved, diff);
+    }
+
+    #[test]
+    fn apply_single_hunk() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let diff = Diff::from_str(
+            "\
+@@ -1,3 +1,5 @@
+ 1
++1.1
++1.2
+ 2
+ 3
+@@ -9,3 +11,2 @@
+ 9
+-10
+ 11
+",
+        )
+        .unwrap();
+
+        // The second hunk applies at its own position without the first one
+        let (content, stats) = diff.apply_hunk(old, 1).unwrap();
+        assert_eq!(content, "1\n2\n3\n4\n5\n6\n7\n8\n9\n11\n12\n");
+        assert_eq!((stats.hunks_applied, stats.lines_deleted), (1, 1));
+
+        // Applying the hunks one at a time gives the same result as applying the whole diff
+        let (first, _) = diff.apply_hunk(old, 0).unwrap();
+        let (both, _) = diff.apply_hunk(&first, 1).unwrap();
+        assert_eq!(both, apply(old, &diff).unwrap().0);
+
+        let err = diff.apply_hunk(old, 2).unwrap_err();
+        assert_eq!(
+            err,
+            ApplyError::HunkIndexOutOfBounds {
+                hunk_index: 2,
+                hunk_count: 2
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "error applying hunk #3: the diff only has 2 hunks"
+        );
+        assert!(matches!(
+            diff.apply_hunk("x\n", 1),
+            Err(ApplyError::ContextMismatch { hunk_index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn overlapping_hunks() {
+        let base = "a\nb\nc\nd\ne\nf\n";
+        let mut diff = crate::parse_normal("2,3d1\n< b\n< c\n5d3\n< e\n").unwrap();
+        assert_eq!(apply(base, &diff).unwrap().0, "a\nd\nf\n");
+
+        // Both hunks delete line 3
+        diff.hunks_mut()[1].set_old_range(crate::HunkRange::new(3, 1));
+        let err = apply(base, &diff).unwrap_err();
+        assert_eq!(
+            err,
+            ApplyError::OverlappingHunks {
+                first: 0,
+                second: 1
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "error applying hunk #2: it overlaps with hunk #1"
+        );
+
+        // Adjacent hunks are fine
+        let diff = crate::parse_normal("2,3d1\n< b\n< c\n4d1\n< d\n").unwrap();
+        assert_eq!(apply(base, &diff).unwrap().0, "a\ne\nf\n");
+
+        // Misnumbered hunks parsed without checking their order are applied by their content
+        let patch = "@@ -5,2 +5,2 @@\n b\n-c\n+C\n@@ -1,2 +1,2 @@\n f\n-g\n+G\n";
+        let config = crate::ParserConfig {
+            skip_order_check: true,
+            ..Default::default()
+        };
+        let diff = crate::patch_from_str_with_config(patch, config)
+            .unwrap()
+            .remove(0);
+        let base = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        assert_eq!(apply(base, &diff).unwrap().0, "a\nb\nC\nd\ne\nf\nG\nh\n");
+    }
+
+    #[test]
+    fn expand_tabs() {
+        let base = "fn main() {\n    let a = 1;\n    let b = 2;\n}\n";
+        let patch =
+            "@@ -1,4 +1,4 @@\n fn main() {\n-\tlet a = 1;\n+\tlet a = 3;\n \tlet b = 2;\n }\n";
+        let diff = crate::Diff::from_str(patch).unwrap();
+        let config = |expand_tabs| ApplyConfig {
+            fuzzy_config: FuzzyConfig {
+                max_fuzz: 0,
+                expand_tabs,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            apply_with_config(base, &diff, &config(None)),
+            Err(ApplyError::ContextMismatch { .. })
+        ));
+        assert!(apply_with_config(base, &diff, &config(Some(8))).is_err());
+
+        // Unchanged lines keep the spaces of the target, inserted lines the tab of the diff
+        let (content, stats) = apply_with_config(base, &diff, &config(Some(4))).unwrap();
+        assert_eq!(content, "fn main() {\n\tlet a = 3;\n    let b = 2;\n}\n");
+        assert_eq!(stats.lines_context, 3);
+        let diff = crate::Diff::from_bytes(patch.as_bytes()).unwrap();
+        let (content, _) =
+            apply_bytes_with_config(base.as_bytes(), &diff, &config(Some(4))).unwrap();
+        assert_eq!(
+            content,
+            "fn main() {\n\tlet a = 3;\n    let b = 2;\n}\n".as_bytes()
+        );
+
+        // Tabs expand to the next tab stop rather than a fixed number of spaces
+        let options = MatchOptions {
+            expand_tabs: Some(4),
+            ..Default::default()
+        };
+        assert!(options.matches("ab\tc", "ab  c"));
+        assert!(options.matches("\t\tx", "        x"));
+        assert!(!options.matches("ab\tc", "ab    c"));
+        assert!(options.matches(b"\xff\tc".as_slice(), b"\xff   c".as_slice()));
+
+        // Characters count as one column, also in lines which aren't valid utf8
+        assert!(options.matches("é\tc", "é   c"));
+        assert!(options.matches(b"\xc3\xa9\xff\tc".as_slice(), b"\xc3\xa9\xff  c".as_slice()));
+    }
+
+    #[test]
+    fn dry_run() {
+        let (base_image, patch) = load_files("fuzzy");
+        let diff = crate::Diff::from_str(&patch).unwrap();
+        let stats = diff.dry_run(&base_image).unwrap();
+        assert_eq!(stats, apply(&base_image, &diff).unwrap().1);
+        assert_eq!(stats.hunks_applied, 2);
+
+        let diff = crate::Diff::from_bytes(patch.as_bytes()).unwrap();
+        assert_eq!(diff.dry_run(base_image.as_bytes()).unwrap(), stats);
+
+        let err = diff.dry_run(b"unrelated\n".as_slice()).unwrap_err();
+        assert_eq!(
+            err,
+            super::apply_bytes(b"unrelated\n".as_slice(), &diff).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn apply_in_place() {
+        let (base_image, patch) = load_files("fuzzy");
+        let diff = crate::Diff::from_str(&patch).unwrap();
+        let mut buf = base_image.clone();
+        let stats = super::apply_in_place(&mut buf, &diff).unwrap();
+        assert_eq!((buf, stats), apply(&base_image, &diff).unwrap());
+
+        let diff = crate::Diff::from_str(
+            "\
+@@ -0,0 +1 @@
++first
+@@ -2,3 +3,2 @@
+ b
+-c
+ d
+@@ -5,2 +5,3 @@
+-e
++E
++E2
+ f
+@@ -7 +8 @@
+-g
++G
+",
+        )
+        .unwrap();
+        for base_image in [
+            "a\nb\nc\nd\ne\nf\ng",
+            "a\r\nb\r\nc\r\nd\r\ne\r\nf\r\ng\r\n",
+            "a\r\nb\nc\nd\ne\nf\ng\n",
+        ] {
+            let mut buf = base_image.to_owned();
+            let stats = super::apply_in_place(&mut buf, &diff).unwrap();
+            assert_eq!(
+                (buf, stats),
+                apply(base_image, &diff).unwrap(),
+                "{base_image:?}"
+            );
+        }
+
+        // The buffer is left untouched if the diff doesn't apply
+        let mut buf = "x\ny\n".to_owned();
+        let err = super::apply_in_place(&mut buf, &diff).unwrap_err();
+        assert!(matches!(err, ApplyError::ContextMismatch { .. }));
+        assert_eq!(buf, "x\ny\n");
+    }
+
     #[test]
     fn test_tectonic_patch_with_fuzz() {
         // Test case from real-world patch that succeeds with GNU patch-style edge fuzz.
@@ -1180,7 +4059,9 @@ mod test {
             fuzzy_config: FuzzyConfig {
                 max_fuzz: 2,
                 ignore_whitespace: true,
+                ignore_trailing_whitespace: false,
                 ignore_case: false,
+                expand_tabs: None,
             },
             ..Default::default()
         }
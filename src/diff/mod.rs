@@ -43,12 +43,46 @@ where
 }
 
 /// A collection of options for modifying the way a diff is performed
-#[derive(Debug)]
+///
+/// Diffs are computed with the linear space variant of Myers' algorithm without any heuristics or
+/// early termination, so the produced edit scripts are always minimal (similar to
+/// `git diff --minimal`). Compaction only shifts edits around and never changes their size.
 pub struct DiffOptions {
     compact: bool,
     context_len: usize,
+    ignore_cr_at_eol: bool,
     original_filename: Option<Cow<'static, str>>,
     modified_filename: Option<Cow<'static, str>>,
+    block_context: Option<Box<BlockRoleFn>>,
+}
+
+/// Predicate classifying the lines of a text, see [`DiffOptions::set_block_context`]
+type BlockRoleFn = dyn Fn(&[u8]) -> BlockRole;
+
+impl std::fmt::Debug for DiffOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiffOptions")
+            .field("compact", &self.compact)
+            .field("context_len", &self.context_len)
+            .field("ignore_cr_at_eol", &self.ignore_cr_at_eol)
+            .field("original_filename", &self.original_filename)
+            .field("modified_filename", &self.modified_filename)
+            .field("block_context", &self.block_context.is_some())
+            .finish()
+    }
+}
+
+/// How a line affects the block structure of a text, see [`DiffOptions::set_block_context`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockRole {
+    /// The line opens a new block, e.g. `fn main() {`
+    Open,
+    /// The line closes the innermost open block, e.g. `}`
+    Close,
+    /// The line closes the innermost open block and opens a new one, e.g. `} else {`
+    CloseOpen,
+    /// The line doesn't change the nesting
+    Other,
 }
 
 impl DiffOptions {
@@ -56,12 +90,15 @@ impl DiffOptions {
     ///
     /// ## Defaults
     /// * context_len = 3
+    /// * ignore_cr_at_eol = false
     pub fn new() -> Self {
         Self {
             compact: true,
             context_len: 3,
+            ignore_cr_at_eol: false,
             original_filename: Some("original".into()),
             modified_filename: Some("modified".into()),
+            block_context: None,
         }
     }
 
@@ -71,6 +108,61 @@ impl DiffOptions {
         self
     }
 
+    /// Treat lines which only differ by a trailing carriage return as equal, similar to
+    /// `git diff --ignore-cr-at-eol`.
+    ///
+    /// Context lines in the produced patch are taken from the modified text.
+    pub fn set_ignore_cr_at_eol(&mut self, ignore_cr_at_eol: bool) -> &mut Self {
+        self.ignore_cr_at_eol = ignore_cr_at_eol;
+        self
+    }
+
+    /// Expand every change to the complete block that contains it, similar to
+    /// `git diff --function-context` but for blocks at any nesting level.
+    ///
+    /// `role` is called with every line of the original text (without its line ending) and
+    /// reports whether the line opens or closes a block. A change is expanded to the innermost
+    /// block which contains all of its lines, from the opening to the closing line. Changes
+    /// outside of any block are instead expanded to cover all the top-level blocks they touch.
+    /// The usual `context_len` lines of context are still added, and hunks whose expanded ranges
+    /// overlap are merged.
+    ///
+    /// Blocks have to be delimited by explicit lines, so indentation based blocks need a
+    /// predicate which recognizes the line ending a block. Unbalanced closing lines are ignored
+    /// and blocks still open at the end of the text end at its last line.
+    ///
+    /// This calls `role` once per line of the original text and scans all blocks for each
+    /// change, so it is noticeably slower than a plain diff for texts with many blocks and
+    /// changes. The produced hunks can also be much larger than with plain context.
+    ///
+    /// ```
+    /// use flickzeug::{BlockRole, DiffOptions};
+    ///
+    /// let original = "fn main() {\n    a();\n    b();\n    c();\n    d();\n}\n";
+    /// let modified = "fn main() {\n    a();\n    b();\n    C();\n    d();\n}\n";
+    ///
+    /// let mut options = DiffOptions::new();
+    /// options.set_context_len(0).set_block_context(|line: &[u8]| {
+    ///     if line.ends_with(b"{") {
+    ///         BlockRole::Open
+    ///     } else if line.starts_with(b"}") {
+    ///         BlockRole::Close
+    ///     } else {
+    ///         BlockRole::Other
+    ///     }
+    /// });
+    ///
+    /// let patch = options.create_patch(original, modified);
+    /// assert_eq!(patch.hunks()[0].old_range().range(), 1..7);
+    /// ```
+    pub fn set_block_context<F>(&mut self, role: F) -> &mut Self
+    where
+        F: Fn(&[u8]) -> BlockRole + 'static,
+    {
+        self.block_context = Some(Box::new(role));
+        self
+    }
+
     /// Enable/Disable diff compaction. Compaction is a post-processing step which attempts to
     /// produce a prettier diff by reducing the number of edited blocks by shifting and merging
     /// edit blocks.
@@ -122,13 +214,95 @@ impl DiffOptions {
 
     /// Produce a Patch between two texts based on the configured options
     pub fn create_patch<'a>(&self, original: &'a str, modified: &'a str) -> Diff<'a, str> {
-        let mut classifier = Classifier::default();
+        let hunks = self.hunks(original, modified, None);
+        self.str_patch(hunks.expect("the number of edits isn't limited"))
+    }
+
+    /// Create a patch between two potentially non-utf8 texts
+    pub fn create_patch_bytes<'a>(&self, original: &'a [u8], modified: &'a [u8]) -> Diff<'a, [u8]> {
+        let hunks = self.hunks(original, modified, None);
+        self.bytes_patch(hunks.expect("the number of edits isn't limited"))
+    }
+
+    /// Produce a Patch between two texts, or `None` if it would delete and insert more than
+    /// `max_edits` lines in total.
+    ///
+    /// Finding out that two texts differ by more than `max_edits` lines takes time proportional
+    /// to `max_edits` rather than to the actual number of differences, which makes this much
+    /// cheaper than [`DiffOptions::create_patch`] for checking whether texts are similar.
+    ///
+    /// ```
+    /// use flickzeug::DiffOptions;
+    ///
+    /// let original = "a\nb\nc\nd\n";
+    /// let modified = "a\nB\nc\nd\n";
+    ///
+    /// // Replacing a line is one deletion and one insertion
+    /// let options = DiffOptions::new();
+    /// assert!(options.create_patch_bounded(original, modified, 2).is_some());
+    /// assert!(options.create_patch_bounded(original, modified, 1).is_none());
+    /// ```
+    pub fn create_patch_bounded<'a>(
+        &self,
+        original: &'a str,
+        modified: &'a str,
+        max_edits: usize,
+    ) -> Option<Diff<'a, str>> {
+        let hunks = self.hunks(original, modified, Some(max_edits))?;
+        Some(self.str_patch(hunks))
+    }
+
+    /// Create a patch between two potentially non-utf8 texts, or `None` if it would delete and
+    /// insert more than `max_edits` lines in total. See [`DiffOptions::create_patch_bounded`].
+    pub fn create_patch_bytes_bounded<'a>(
+        &self,
+        original: &'a [u8],
+        modified: &'a [u8],
+        max_edits: usize,
+    ) -> Option<Diff<'a, [u8]>> {
+        let hunks = self.hunks(original, modified, Some(max_edits))?;
+        Some(self.bytes_patch(hunks))
+    }
+
+    /// Compute the hunks between two texts, or `None` if there are more than `max_edits` changes
+    fn hunks<'a, T>(
+        &self,
+        original: &'a T,
+        modified: &'a T,
+        max_edits: Option<usize>,
+    ) -> Option<Vec<Hunk<'a, T>>>
+    where
+        T: Text + ToOwned + std::fmt::Debug + ?Sized,
+    {
+        let mut classifier = self.classifier();
         let (old_lines, old_ids) = classifier.classify_lines(original);
         let (new_lines, new_ids) = classifier.classify_lines(modified);
 
-        let solution = self.diff_slice(&old_ids, &new_ids);
+        let solution = match max_edits {
+            Some(max_edits) => self.compact(myers::diff_bounded(&old_ids, &new_ids, max_edits)?),
+            None => self.diff_slice(&old_ids, &new_ids),
+        };
+
+        if let Some(role) = &self.block_context {
+            let blocks = find_blocks(&old_lines, role);
+            return Some(to_block_hunks(
+                &old_lines,
+                &new_lines,
+                &solution,
+                self.context_len,
+                &blocks,
+            ));
+        }
+
+        Some(to_hunks(
+            &old_lines,
+            &new_lines,
+            &solution,
+            self.context_len,
+        ))
+    }
 
-        let hunks = to_hunks(&old_lines, &new_lines, &solution, self.context_len);
+    fn str_patch<'a>(&self, hunks: Vec<Hunk<'a, str>>) -> Diff<'a, str> {
         Diff::new(
             self.original_filename.clone(),
             self.modified_filename.clone(),
@@ -136,16 +310,7 @@ impl DiffOptions {
         )
     }
 
-    /// Create a patch between two potentially non-utf8 texts
-    pub fn create_patch_bytes<'a>(&self, original: &'a [u8], modified: &'a [u8]) -> Diff<'a, [u8]> {
-        let mut classifier = Classifier::default();
-        let (old_lines, old_ids) = classifier.classify_lines(original);
-        let (new_lines, new_ids) = classifier.classify_lines(modified);
-
-        let solution = self.diff_slice(&old_ids, &new_ids);
-
-        let hunks = to_hunks(&old_lines, &new_lines, &solution, self.context_len);
-
+    fn bytes_patch<'a>(&self, hunks: Vec<Hunk<'a, [u8]>>) -> Diff<'a, [u8]> {
         // helper function to convert a utf8 cow to a bytes cow
         fn cow_str_to_bytes(cow: Cow<'static, str>) -> Cow<'static, [u8]> {
             match cow {
@@ -161,17 +326,30 @@ impl DiffOptions {
         )
     }
 
+    fn classifier<'a, T: Eq + std::hash::Hash + ?Sized>(&self) -> Classifier<'a, T> {
+        if self.ignore_cr_at_eol {
+            Classifier::ignoring_cr_at_eol()
+        } else {
+            Classifier::default()
+        }
+    }
+
     pub(crate) fn diff_slice<'a, T: PartialEq>(
         &self,
         old: &'a [T],
         new: &'a [T],
     ) -> Vec<DiffRange<'a, 'a, [T]>> {
-        let mut solution = myers::diff(old, new);
+        self.compact(myers::diff(old, new))
+    }
 
+    /// Compact the changes of `solution` if enabled, see [`DiffOptions::set_compact`]
+    fn compact<'a, T: PartialEq>(
+        &self,
+        mut solution: Vec<DiffRange<'a, 'a, [T]>>,
+    ) -> Vec<DiffRange<'a, 'a, [T]>> {
         if self.compact {
             cleanup::compact(&mut solution);
         }
-
         solution
     }
 }
@@ -343,6 +521,150 @@ fn calc_end(
     (end1, end2)
 }
 
+/// Find the blocks of a text as inclusive ranges of line indices, outer blocks before the blocks
+/// nested in them
+fn find_blocks<T: Text + ?Sized>(
+    lines: &[(&T, Option<LineEnd>)],
+    role: &BlockRoleFn,
+) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut open = Vec::new();
+
+    for (idx, (line, _)) in lines.iter().enumerate() {
+        let role = role(line.as_bytes());
+        if matches!(role, BlockRole::Close | BlockRole::CloseOpen)
+            && let Some(block) = open.pop()
+        {
+            let block: &mut Block = &mut blocks[block];
+            block.end = idx;
+        }
+        if matches!(role, BlockRole::Open | BlockRole::CloseOpen) {
+            open.push(blocks.len());
+            blocks.push(Block {
+                start: idx,
+                end: lines.len() - 1,
+                depth: open.len() - 1,
+            });
+        }
+    }
+
+    blocks
+}
+
+#[derive(Debug)]
+struct Block {
+    /// Index of the opening line
+    start: usize,
+    /// Index of the closing line
+    end: usize,
+    depth: usize,
+}
+
+impl Block {
+    fn contains(&self, edit: &ops::Range<usize>) -> bool {
+        if edit.is_empty() {
+            // Insertions between the opening and the closing line
+            self.start < edit.start && edit.start <= self.end
+        } else {
+            self.start <= edit.start && edit.end - 1 <= self.end
+        }
+    }
+
+    fn intersects(&self, edit: &ops::Range<usize>) -> bool {
+        self.start < edit.end && edit.start <= self.end
+    }
+}
+
+/// Expand a range of changed lines in the original text to the blocks covering it
+fn expand_to_blocks(blocks: &[Block], edit: &ops::Range<usize>) -> ops::Range<usize> {
+    let innermost = blocks
+        .iter()
+        .filter(|block| block.contains(edit))
+        .max_by_key(|block| block.depth);
+    if let Some(block) = innermost {
+        return block.start..block.end + 1;
+    }
+
+    blocks
+        .iter()
+        .filter(|block| block.depth == 0 && block.intersects(edit))
+        .fold(edit.clone(), |range, block| {
+            cmp::min(range.start, block.start)..cmp::max(range.end, block.end + 1)
+        })
+}
+
+/// Like [`to_hunks`], but expands the context of every edit to the blocks containing it
+fn to_block_hunks<'a, T: Text + ?Sized + ToOwned>(
+    lines1: &[(&'a T, Option<LineEnd>)],
+    lines2: &[(&'a T, Option<LineEnd>)],
+    solution: &[DiffRange<[u64]>],
+    context_len: usize,
+    blocks: &[Block],
+) -> Vec<Hunk<'a, T>> {
+    let edit_script = build_edit_script(solution);
+
+    // The range of the original text each hunk covers, along with the edits in it
+    let mut groups: Vec<(ops::Range<usize>, ops::Range<usize>)> = Vec::new();
+    for (idx, script) in edit_script.iter().enumerate() {
+        let blocks = expand_to_blocks(blocks, &script.old);
+        let (end1, _) = calc_end(
+            context_len,
+            lines1.len(),
+            lines2.len(),
+            script.old.end,
+            script.new.end,
+        );
+        let mut start = cmp::min(script.old.start.saturating_sub(context_len), blocks.start);
+        let mut end = cmp::max(end1, blocks.end);
+        let mut edits = idx..idx + 1;
+
+        // Blocks can reach back over previous hunks, so merge until there's no overlap
+        while let Some((range, prev_edits)) = groups.pop_if(|(range, _)| start < range.end) {
+            start = cmp::min(start, range.start);
+            end = cmp::max(end, range.end);
+            edits.start = prev_edits.start;
+        }
+        groups.push((start..end, edits));
+    }
+
+    groups
+        .into_iter()
+        .map(|(range, edits)| {
+            let scripts = &edit_script[edits];
+            let (first, last) = (&scripts[0], &scripts[scripts.len() - 1]);
+
+            // Lines outside of the edits are the same in both texts
+            let start2 = first.new.start - (first.old.start - range.start);
+            let end2 = last.new.end + (range.end - last.old.end);
+
+            let mut lines = Vec::new();
+            let mut next2 = start2;
+            for script in scripts {
+                for line in &lines2[next2..script.new.start] {
+                    lines.push(Line::Context(*line));
+                }
+                for line in &lines1[script.old.clone()] {
+                    lines.push(Line::Delete(*line));
+                }
+                for line in &lines2[script.new.clone()] {
+                    lines.push(Line::Insert(*line));
+                }
+                next2 = script.new.end;
+            }
+            for line in &lines2[next2..end2] {
+                lines.push(Line::Context(*line));
+            }
+
+            Hunk::new(
+                HunkRange::from_index(range.start, range.len()),
+                HunkRange::from_index(start2, end2 - start2),
+                None,
+                lines,
+            )
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 struct EditRange {
     old: ops::Range<usize>,
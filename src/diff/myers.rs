@@ -76,12 +76,16 @@ fn max_d(len1: usize, len2: usize) -> usize {
 // D-path. The idea for doing so is to simultaneously run the basic algorithm in both the
 // forward and reverse directions until furthest reaching forward and reverse paths starting at
 // opposing corners 'overlap'.
+//
+// If `max_edits` is given, the search stops early and returns `None` once it is known that the
+// shortest edit script is longer than `max_edits`.
 fn find_middle_snake<T: PartialEq>(
     old: Range<'_, [T]>,
     new: Range<'_, [T]>,
     vf: &mut V,
     vb: &mut V,
-) -> (isize, Snake) {
+    max_edits: Option<usize>,
+) -> Option<(isize, Snake)> {
     let n = old.len();
     let m = new.len();
 
@@ -101,6 +105,11 @@ fn find_middle_snake<T: PartialEq>(
     assert!(vb.len() >= d_max);
 
     for d in 0..d_max as isize {
+        // Any snake found from here on has an edit distance of at least `2 * d - 1`
+        if max_edits.is_some_and(|max_edits| 2 * d - 1 > max_edits as isize) {
+            return None;
+        }
+
         // Forward path
         for k in (-d..=d).rev().step_by(2) {
             let mut x = if k == -d || (k != d && vf[k - 1] < vf[k + 1]) {
@@ -134,7 +143,7 @@ fn find_middle_snake<T: PartialEq>(
                         y_end: y,
                     };
                     // Edit distance to this snake is `2 * d - 1`
-                    return (2 * d - 1, snake);
+                    return Some((2 * d - 1, snake));
                 }
             }
         }
@@ -170,7 +179,7 @@ fn find_middle_snake<T: PartialEq>(
                         y_end: m - y0,
                     };
                     // Edit distance to this snake is `2 * d`
-                    return (2 * d, snake);
+                    return Some((2 * d, snake));
                 }
             }
         }
@@ -181,13 +190,16 @@ fn find_middle_snake<T: PartialEq>(
     unreachable!("unable to find a middle snake");
 }
 
+// If `max_edits` is given, `None` is returned as soon as it is known that the shortest edit script
+// is longer than `max_edits`, leaving `solution` incomplete.
 fn conquer<'a, 'b, T: PartialEq>(
     mut old: Range<'a, [T]>,
     mut new: Range<'b, [T]>,
     vf: &mut V,
     vb: &mut V,
+    max_edits: Option<usize>,
     solution: &mut Vec<DiffRange<'a, 'b, [T]>>,
-) {
+) -> Option<()> {
     // Check for common prefix
     let common_prefix_len = old.common_prefix_len(new);
     if common_prefix_len > 0 {
@@ -210,6 +222,12 @@ fn conquer<'a, 'b, T: PartialEq>(
     old = old.slice(..old.len() - common_suffix_len);
     new = new.slice(..new.len() - common_suffix_len);
 
+    if (old.is_empty() || new.is_empty())
+        && max_edits.is_some_and(|max_edits| old.len() + new.len() > max_edits)
+    {
+        return None;
+    }
+
     if old.is_empty() && new.is_empty() {
         // Do nothing
     } else if old.is_empty() {
@@ -220,21 +238,49 @@ fn conquer<'a, 'b, T: PartialEq>(
         solution.push(DiffRange::Delete(old));
     } else {
         // Divide & Conquer
-        let (_shortest_edit_script_len, snake) = find_middle_snake(old, new, vf, vb);
+        let (shortest_edit_script_len, snake) = find_middle_snake(old, new, vf, vb, max_edits)?;
+        if max_edits.is_some_and(|max_edits| shortest_edit_script_len as usize > max_edits) {
+            return None;
+        }
 
         let (old_a, old_b) = old.split_at(snake.x_start);
         let (new_a, new_b) = new.split_at(snake.y_start);
 
-        conquer(old_a, new_a, vf, vb, solution);
-        conquer(old_b, new_b, vf, vb, solution);
+        // The edit scripts of both halves add up to the one of the whole, so they're within
+        // `max_edits` as well
+        conquer(old_a, new_a, vf, vb, None, solution);
+        conquer(old_b, new_b, vf, vb, None, solution);
     }
 
     if common_suffix_len > 0 {
         solution.push(common_suffix);
     }
+    Some(())
 }
 
 pub fn diff<'a, 'b, T: PartialEq>(old: &'a [T], new: &'b [T]) -> Vec<DiffRange<'a, 'b, [T]>> {
+    solve(old, new, None).expect("the number of edits isn't limited")
+}
+
+/// Returns the shortest edit script between `old` and `new`, or `None` if it deletes and inserts
+/// more than `max_edits` elements in total.
+///
+/// Only the search for the middle snake of the whole sequences is bounded, which is enough to
+/// bail out in `O((N + M) * max_edits)` time. Once it is found, the edit script is known to be
+/// short enough and the rest of it is computed as usual.
+pub fn diff_bounded<'a, 'b, T: PartialEq>(
+    old: &'a [T],
+    new: &'b [T],
+    max_edits: usize,
+) -> Option<Vec<DiffRange<'a, 'b, [T]>>> {
+    solve(old, new, Some(max_edits))
+}
+
+fn solve<'a, 'b, T: PartialEq>(
+    old: &'a [T],
+    new: &'b [T],
+    max_edits: Option<usize>,
+) -> Option<Vec<DiffRange<'a, 'b, [T]>>> {
     let old_recs = Range::new(old, ..);
     let new_recs = Range::new(new, ..);
 
@@ -247,9 +293,16 @@ pub fn diff<'a, 'b, T: PartialEq>(old: &'a [T], new: &'b [T]) -> Vec<DiffRange<'
     let mut vf = V::new(max_d);
     let mut vb = V::new(max_d);
 
-    conquer(old_recs, new_recs, &mut vf, &mut vb, &mut solution);
+    conquer(
+        old_recs,
+        new_recs,
+        &mut vf,
+        &mut vb,
+        max_edits,
+        &mut solution,
+    )?;
 
-    solution
+    Some(solution)
 }
 
 #[cfg(test)]
@@ -263,6 +316,49 @@ mod tests {
         let max_d = max_d(a.len(), b.len());
         let mut vf = V::new(max_d);
         let mut vb = V::new(max_d);
-        find_middle_snake(a, b, &mut vf, &mut vb);
+        find_middle_snake(a, b, &mut vf, &mut vb, None);
+    }
+
+    #[test]
+    fn test_diff_bounded() {
+        // The number of elements deleted and inserted by the bounded edit script
+        let edit_distance = |old: &[u8], new: &[u8], max_edits| {
+            let solution = diff_bounded(old, new, max_edits)?;
+            let edits = solution
+                .iter()
+                .map(|range| match range {
+                    DiffRange::Equal(..) => 0,
+                    DiffRange::Delete(range) | DiffRange::Insert(range) => range.len(),
+                })
+                .sum::<usize>();
+            Some(edits)
+        };
+
+        let a = b"ABCABBA";
+        let b = b"CBABAC";
+        // The example from Myers' paper has an edit distance of 5
+        assert_eq!(edit_distance(a, b, 5), Some(5));
+        assert_eq!(edit_distance(a, b, 100), Some(5));
+        assert_eq!(edit_distance(a, b, 4), None);
+        assert_eq!(edit_distance(a, b, 0), None);
+
+        assert_eq!(edit_distance(a, a, 0), Some(0));
+        assert_eq!(edit_distance(b"", b"abc", 3), Some(3));
+        assert_eq!(edit_distance(b"abc", b"", 2), None);
+        assert_eq!(edit_distance(b"xabcx", b"xbx", 2), Some(2));
+
+        for max_edits in 0..10 {
+            let expected = diff(a, b)
+                .iter()
+                .map(|range| match range {
+                    DiffRange::Equal(..) => 0,
+                    DiffRange::Delete(range) | DiffRange::Insert(range) => range.len(),
+                })
+                .sum::<usize>();
+            assert_eq!(
+                edit_distance(a, b, max_edits),
+                (expected <= max_edits).then_some(expected)
+            );
+        }
     }
 }
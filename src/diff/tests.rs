@@ -469,6 +469,37 @@ The door of all subtleties!
     assert_patch!(opts, lao, tzu, expected);
 }
 
+#[test]
+fn context_len_merges_nearby_changes() {
+    // Changes on lines 3 and 7 with three unchanged lines between them, and a line added after
+    // the five unchanged lines at the end
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+    let new = "1\n2\nthree\n4\n5\n6\nseven\n8\n9\n10\n11\n12\nthirteen\n";
+
+    // The hunk headers match the output of `diff -U<context_len>`
+    for (context_len, expected) in [
+        (0, &["-3 +3", "-7 +7", "-12,0 +13"][..]),
+        (1, &["-2,3 +2,3", "-6,3 +6,3", "-12 +12,2"]),
+        (2, &["-1,9 +1,9", "-11,2 +11,3"]),
+        (3, &["-1,12 +1,13"]),
+    ] {
+        let patch = DiffOptions::new()
+            .set_context_len(context_len)
+            .create_patch(old, new);
+        let ranges: Vec<_> = patch
+            .hunks()
+            .iter()
+            .map(|hunk| format!("-{} +{}", hunk.old_range(), hunk.new_range()))
+            .collect();
+        assert_eq!(ranges, expected, "context_len {context_len}");
+        assert_eq!(apply(old, &patch).unwrap().0, new);
+    }
+
+    // Like `diff -u`, three lines of context are used by default
+    let patch = DiffOptions::default().create_patch(old, new);
+    assert_eq!(patch.hunks().len(), 1);
+}
+
 #[test]
 fn no_newline_at_eof() {
     let old = "old line";
@@ -814,3 +845,181 @@ Kupluh, Indeed
     let (re_reverse_content, _stats) = apply(&first_content, &reverse).unwrap();
     assert_eq!(re_reverse_content, original);
 }
+
+#[test]
+fn ignore_cr_at_eol() {
+    let lf = "first\nsecond\nthird\n";
+    let crlf = "first\r\nsecond\r\nthird\r\n";
+
+    let mut opts = DiffOptions::default();
+    assert_eq!(opts.create_patch(lf, crlf).hunks().len(), 1);
+
+    opts.set_ignore_cr_at_eol(true);
+    assert!(opts.create_patch(lf, crlf).hunks().is_empty());
+    assert!(
+        opts.create_patch_bytes(lf.as_bytes(), crlf.as_bytes())
+            .hunks()
+            .is_empty()
+    );
+
+    // A final line without a newline only differing by a trailing `\r`
+    assert!(
+        opts.create_patch("first\nlast", "first\r\nlast\r")
+            .hunks()
+            .is_empty()
+    );
+
+    // Adding or removing the final newline is a change
+    assert_eq!(opts.create_patch("a\n", "a").hunks().len(), 1);
+    assert_eq!(opts.create_patch("a\r\n", "a").hunks().len(), 1);
+    assert_eq!(opts.create_patch("a", "a\r\n").hunks().len(), 1);
+
+    // Actual changes are still reported
+    let patch = opts.create_patch(lf, "first\r\nchanged\r\nthird\r\n");
+    let expected = "\
+--- original
++++ modified
+@@ -1,3 +1,3 @@
+ first\r
+-second
++changed\r
+ third\r
+";
+    assert_eq!(patch.to_string(), expected);
+}
+
+#[test]
+fn edit_scripts_are_minimal() {
+    // Length of the longest common subsequence, computed with plain dynamic programming
+    fn lcs_len(a: &[u8], b: &[u8]) -> usize {
+        let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                table[i][j] = if a[i] == b[j] {
+                    table[i + 1][j + 1] + 1
+                } else {
+                    table[i + 1][j].max(table[i][j + 1])
+                };
+            }
+        }
+        table[0][0]
+    }
+
+    let cases: [(&[u8], &[u8]); 5] = [
+        (b"ABCABBA", b"CBABAC"),
+        (b"abcabba", b"cbabac"),
+        (b"xaxbxcxdxexfxgx", b"axbxcxdxexfxgxh"),
+        (b"aaaaabbbbbaaaaa", b"bbbbbaaaaabbbbb"),
+        (b"the quick brown fox", b"a quick brown dog jumps"),
+    ];
+
+    for (a, b) in cases {
+        let solution = DiffOptions::default().diff_slice(a, b);
+        let edits: usize = solution
+            .iter()
+            .map(|range| match range {
+                DiffRange::Equal(..) => 0,
+                DiffRange::Delete(r) | DiffRange::Insert(r) => r.len(),
+            })
+            .sum();
+        assert_eq!(edits, a.len() + b.len() - 2 * lcs_len(a, b));
+    }
+}
+
+#[test]
+fn create_patch_bounded() {
+    let original: String = (0..1000).map(|i| format!("line {i}\n")).collect();
+    let similar = original.replace("line 500\n", "line five hundred\n");
+    let different: String = (0..1000).map(|i| format!("other {i}\n")).collect();
+
+    let options = DiffOptions::default();
+    assert_eq!(
+        options.create_patch_bounded(&original, &similar, 2),
+        Some(options.create_patch(&original, &similar))
+    );
+    assert_eq!(options.create_patch_bounded(&original, &similar, 1), None);
+    assert_eq!(
+        options.create_patch_bounded(&original, &different, 600),
+        None
+    );
+    assert!(
+        options
+            .create_patch_bounded(&original, &different, 2000)
+            .is_some()
+    );
+
+    assert_eq!(
+        options.create_patch_bytes_bounded(original.as_bytes(), similar.as_bytes(), 2),
+        Some(options.create_patch_bytes(original.as_bytes(), similar.as_bytes()))
+    );
+    assert_eq!(
+        options.create_patch_bytes_bounded(original.as_bytes(), different.as_bytes(), 0),
+        None
+    );
+}
+
+#[test]
+fn block_context() {
+    let original = "\
+use std::io;
+
+fn main() {
+    let x = 1;
+    if x > 0 {
+        println!(\"positive\");
+        println!(\"done\");
+    }
+    println!(\"end\");
+}
+
+fn other() {}
+";
+    let modified = original.replace("\"done\"", "\"finished\"");
+
+    let mut options = DiffOptions::new();
+    options.set_context_len(0).set_block_context(|line| {
+        if line.ends_with(b"{") {
+            BlockRole::Open
+        } else if line.trim_ascii_start().starts_with(b"}") {
+            BlockRole::Close
+        } else {
+            BlockRole::Other
+        }
+    });
+
+    // The change is expanded to the innermost block around it
+    let expected = "\
+--- original
++++ modified
+@@ -5,4 +5,4 @@
+     if x > 0 {
+         println!(\"positive\");
+-        println!(\"done\");
++        println!(\"finished\");
+     }
+";
+    let patch = options.create_patch(original, &modified);
+    assert_eq!(patch.to_string(), expected);
+
+    // The opening line belongs to the block
+    let modified = original.replace("x > 0", "x >= 0");
+    let patch = options.create_patch(original, &modified);
+    assert_eq!(patch.hunks().len(), 1);
+    assert_eq!(patch.hunks()[0].old_range().range(), 5..9);
+
+    // A change in the outer block swallows the hunk of the inner one
+    let modified = modified.replace("\"end\"", "\"the end\"");
+    let patch = options.create_patch(original, &modified);
+    assert_eq!(patch.hunks().len(), 1);
+    assert_eq!(patch.hunks()[0].old_range().range(), 3..11);
+    assert_eq!(crate::apply(original, &patch).unwrap().0, modified);
+
+    // Top-level changes cover all the blocks they touch
+    let modified = original.replace("}\n\nfn other", "}\nfn other");
+    let patch = options.create_patch(original, &modified);
+    assert_eq!(patch.hunks()[0].old_range().range(), 11..12);
+    let modified = original.replace("    println!(\"end\");\n}\n\n", "");
+    let patch = options.create_patch(original, &modified);
+    assert_eq!(patch.hunks()[0].old_range().range(), 3..12);
+    assert_eq!(crate::apply(original, &patch).unwrap().0, modified);
+}
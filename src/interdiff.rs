@@ -0,0 +1,147 @@
+use crate::{
+    ApplyError, DiffOptions,
+    apply::{apply, apply_bytes},
+    patch::Diff,
+};
+
+/// The result of comparing two versions of a patch against the same base.
+///
+/// An `Interdiff` owns the base with each of the two patches applied, which allows creating a
+/// patch from "base + old patch" to "base + new patch" that borrows from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interdiff<T> {
+    original: T,
+    modified: T,
+}
+
+impl<T> Interdiff<T> {
+    /// Returns the base with the old patch applied
+    pub fn original(&self) -> &T {
+        &self.original
+    }
+
+    /// Returns the base with the new patch applied
+    pub fn modified(&self) -> &T {
+        &self.modified
+    }
+}
+
+impl Interdiff<String> {
+    /// Create a patch from the base with the old patch applied to the base with the new patch
+    /// applied
+    pub fn create_patch(&self) -> Diff<'_, str> {
+        DiffOptions::default().create_patch(&self.original, &self.modified)
+    }
+}
+
+impl Interdiff<Vec<u8>> {
+    /// Create a patch from the base with the old patch applied to the base with the new patch
+    /// applied
+    pub fn create_patch(&self) -> Diff<'_, [u8]> {
+        DiffOptions::default().create_patch_bytes(&self.original, &self.modified)
+    }
+}
+
+/// Compare two versions of a patch against the same base, like the `interdiff` tool.
+///
+/// Both patches are applied to `base_image` and the results are kept in the returned
+/// [`Interdiff`], from which a patch between them can be created.
+///
+/// ```
+/// # use flickzeug::{Diff, interdiff};
+/// let base = "a\nb\nc\n";
+/// let old_patch = Diff::from_str("@@ -2 +2 @@\n-b\n+B\n").unwrap();
+/// let new_patch = Diff::from_str("@@ -2 +2 @@\n-b\n+beta\n").unwrap();
+///
+/// let interdiff = interdiff(base, &old_patch, &new_patch).unwrap();
+/// let expected = "\
+/// --- original
+/// +++ modified
+/// @@ -1,3 +1,3 @@
+///  a
+/// -B
+/// +beta
+///  c
+/// ";
+/// assert_eq!(interdiff.create_patch().to_string(), expected);
+/// ```
+pub fn interdiff(
+    base_image: &str,
+    old_patch: &Diff<'_, str>,
+    new_patch: &Diff<'_, str>,
+) -> Result<Interdiff<String>, ApplyError> {
+    let (original, _) = apply(base_image, old_patch)?;
+    let (modified, _) = apply(base_image, new_patch)?;
+    Ok(Interdiff { original, modified })
+}
+
+/// Compare two versions of a potentially non-utf8 patch against the same base
+pub fn interdiff_bytes(
+    base_image: &[u8],
+    old_patch: &Diff<'_, [u8]>,
+    new_patch: &Diff<'_, [u8]>,
+) -> Result<Interdiff<Vec<u8>>, ApplyError> {
+    let (original, _) = apply_bytes(base_image, old_patch)?;
+    let (modified, _) = apply_bytes(base_image, new_patch)?;
+    Ok(Interdiff { original, modified })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "\
+fn main() {
+    println!(\"hello\");
+}
+";
+
+    #[test]
+    fn identical_patches_produce_empty_interdiff() {
+        let patch = Diff::from_str(
+            "\
+@@ -2 +2 @@
+-    println!(\"hello\");
++    println!(\"hello world\");
+",
+        )
+        .unwrap();
+
+        let interdiff = interdiff(BASE, &patch, &patch).unwrap();
+        assert_eq!(interdiff.original(), interdiff.modified());
+        assert!(interdiff.create_patch().hunks().is_empty());
+    }
+
+    #[test]
+    fn interdiff_bytes_matches_str() {
+        let old_patch = "@@ -2 +2 @@\n-    println!(\"hello\");\n+    println!(\"hi\");\n";
+        let new_patch = "@@ -3 +3,2 @@\n }\n+// trailing comment\n";
+
+        let str_interdiff = interdiff(
+            BASE,
+            &Diff::from_str(old_patch).unwrap(),
+            &Diff::from_str(new_patch).unwrap(),
+        )
+        .unwrap();
+        let bytes_interdiff = interdiff_bytes(
+            BASE.as_bytes(),
+            &Diff::from_bytes(old_patch.as_bytes()).unwrap(),
+            &Diff::from_bytes(new_patch.as_bytes()).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            str_interdiff.create_patch().to_string().as_bytes(),
+            bytes_interdiff.create_patch().to_bytes()
+        );
+        // Applying the interdiff to "base + old" yields "base + new"
+        let (result, _) = apply(str_interdiff.original(), &str_interdiff.create_patch()).unwrap();
+        assert_eq!(&result, str_interdiff.modified());
+    }
+
+    #[test]
+    fn interdiff_reports_failing_patch() {
+        let patch = Diff::from_str("@@ -1 +1 @@\n-does not exist\n+nope\n").unwrap();
+        assert!(interdiff(BASE, &patch, &patch).is_err());
+    }
+}
@@ -222,6 +222,7 @@
 
 mod apply;
 mod diff;
+mod interdiff;
 mod line_end;
 mod merge;
 mod patch;
@@ -229,15 +230,28 @@ mod range;
 mod utils;
 
 pub use apply::{
-    ApplyConfig, ApplyError, ApplyOutcome, ApplyResult, ApplyStats, FuzzyConfig, LineEndHandling,
-    apply, apply_bytes, apply_bytes_reporting, apply_bytes_with_config, apply_with_config,
-    is_diff_applied_with_config,
+    ApplyConfig, ApplyError, ApplyOutcome, ApplyReport, ApplyResult, ApplyStats, ApplyWarning,
+    FuzzyConfig, HunkReport, HunkState, Indentation, LineEndHandling, MatchOptions, PathReport,
+    apply, apply_bytes, apply_bytes_check, apply_bytes_reporting, apply_bytes_reverse,
+    apply_bytes_with_config, apply_bytes_with_progress, apply_bytes_with_report, apply_check,
+    apply_in_place, apply_reverse, apply_to_path, apply_to_writer, apply_with_config,
+    apply_with_progress, apply_with_report, apply_within, is_diff_applied_with_config,
+    stats_by_file,
 };
-pub use diff::{DiffOptions, create_patch, create_patch_bytes};
+pub use diff::{BlockRole, DiffOptions, create_patch, create_patch_bytes};
+pub use interdiff::{Interdiff, interdiff, interdiff_bytes};
 pub use line_end::*;
-pub use merge::{ConflictStyle, MergeOptions, merge, merge_bytes};
+pub use merge::{
+    Conflict, ConflictStyle, MergeOptions, merge, merge_bytes, merge_three_way,
+    merge_three_way_bytes,
+};
 pub use patch::{
-    Diff, Hunk, HunkRange, HunkRangeStrategy, Line, ParsePatchError, ParserConfig, Patch,
-    PatchFormatter, patch_from_bytes, patch_from_bytes_with_config, patch_from_str,
-    patch_from_str_with_config,
+    BaseFingerprint, Diff, DiffBuilder, Hunk, HunkRange, HunkRangeStrategy, Line, LineOp,
+    LineOpKind, ParsePatchError, ParserConfig, Patch, PatchFormat, PatchFormatter, SerializeError,
+    diff_normal, diff_normal_bytes, extract_fenced_diffs, is_context_diff, is_normal_diff,
+    is_unified_diff, parse_auto, parse_auto_bytes, parse_context, parse_context_bytes, parse_ed,
+    parse_ed_bytes, parse_normal, parse_normal_bytes, parse_normal_bytes_lenient,
+    parse_normal_lenient, patch_from_bytes, patch_from_bytes_lenient, patch_from_bytes_with_config,
+    patch_from_str, patch_from_str_lenient, patch_from_str_with_config, patch_nth_from_bytes,
+    patch_nth_from_str,
 };
@@ -4,7 +4,7 @@ use crate::{
     range::{DiffRange, Range, SliceLike},
     utils::Classifier,
 };
-use std::{cmp, fmt};
+use std::{cmp, fmt, ops};
 
 #[cfg(test)]
 mod tests;
@@ -113,6 +113,23 @@ pub enum ConflictStyle {
     Diff3,
 }
 
+/// A region of a three-way merge where both sides changed the same lines differently, see
+/// [`merge_three_way`]
+///
+/// All ranges are 1-based line numbers. An empty range starts at the line following the position
+/// of the missing lines, e.g. the ancestor range of a conflict between two insertions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Conflict {
+    /// The lines of the merged text covered by the conflict, including its conflict markers
+    pub merged: ops::Range<usize>,
+    /// The lines of the ancestor changed by both sides
+    pub ancestor: ops::Range<usize>,
+    /// The lines of ours replacing the ancestor lines
+    pub ours: ops::Range<usize>,
+    /// The lines of theirs replacing the ancestor lines
+    pub theirs: ops::Range<usize>,
+}
+
 /// A collection of options for modifying the way a merge is performed
 #[derive(Debug)]
 pub struct MergeOptions {
@@ -152,6 +169,22 @@ impl MergeOptions {
         ours: &'a str,
         theirs: &'a str,
     ) -> Result<String, String> {
+        let (output, conflicts) = self.merge_three_way(ancestor, ours, theirs);
+        if conflicts.is_empty() {
+            Ok(output)
+        } else {
+            Err(output)
+        }
+    }
+
+    /// Merge two files, given a common ancestor, returning the merged text along with the
+    /// [`Conflict`]s marked in it, see [`merge_three_way`]
+    pub fn merge_three_way<'a>(
+        &self,
+        ancestor: &'a str,
+        ours: &'a str,
+        theirs: &'a str,
+    ) -> (String, Vec<Conflict>) {
         let mut classifier = Classifier::default();
         let (ancestor_lines, ancestor_ids) = classifier.classify_lines(ancestor);
         let (our_lines, our_ids) = classifier.classify_lines(ours);
@@ -183,6 +216,22 @@ impl MergeOptions {
         ours: &'a [u8],
         theirs: &'a [u8],
     ) -> Result<Vec<u8>, Vec<u8>> {
+        let (output, conflicts) = self.merge_three_way_bytes(ancestor, ours, theirs);
+        if conflicts.is_empty() {
+            Ok(output)
+        } else {
+            Err(output)
+        }
+    }
+
+    /// Perform a 3-way merge between potentially non-utf8 texts, returning the merged text along
+    /// with the [`Conflict`]s marked in it
+    pub fn merge_three_way_bytes<'a>(
+        &self,
+        ancestor: &'a [u8],
+        ours: &'a [u8],
+        theirs: &'a [u8],
+    ) -> (Vec<u8>, Vec<Conflict>) {
         let mut classifier = Classifier::default();
         let (ancestor_lines, ancestor_ids) = classifier.classify_lines(ancestor);
         let (our_lines, our_ids) = classifier.classify_lines(ours);
@@ -278,6 +327,47 @@ pub fn merge_bytes<'a>(
     MergeOptions::default().merge_bytes(ancestor, ours, theirs)
 }
 
+/// Merge two files given a common ancestor, returning the merged text along with its conflicts.
+///
+/// This performs the same merge as [`merge`]: the changes from the ancestor to ours and to theirs
+/// are combined, and regions which both sides changed differently are written with conflict
+/// markers. Each of them is reported as a [`Conflict`] with the lines it covers in the merged
+/// text and in the three inputs, in order. The merge succeeded without conflicts if the list is
+/// empty.
+///
+/// ```
+/// use flickzeug::merge_three_way;
+///
+/// let ancestor = "a\nb\nc\n";
+/// let ours = "a\nB\nc\n";
+/// let theirs = "a\nbee\nc\n";
+///
+/// let (merged, conflicts) = merge_three_way(ancestor, ours, theirs);
+/// assert_eq!(
+///     merged,
+///     "a\n<<<<<<< ours\nB\n||||||| original\nb\n=======\nbee\n>>>>>>> theirs\nc\n"
+/// );
+/// assert_eq!(conflicts[0].merged, 2..9);
+/// assert_eq!(conflicts[0].ancestor, 2..3);
+/// ```
+pub fn merge_three_way<'a>(
+    ancestor: &'a str,
+    ours: &'a str,
+    theirs: &'a str,
+) -> (String, Vec<Conflict>) {
+    MergeOptions::default().merge_three_way(ancestor, ours, theirs)
+}
+
+/// Perform a 3-way merge between potentially non-utf8 texts, returning the merged text along with
+/// its conflicts, see [`merge_three_way`]
+pub fn merge_three_way_bytes<'a>(
+    ancestor: &'a [u8],
+    ours: &'a [u8],
+    theirs: &'a [u8],
+) -> (Vec<u8>, Vec<Conflict>) {
+    MergeOptions::default().merge_three_way_bytes(ancestor, ours, theirs)
+}
+
 fn merge_solutions<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike>(
     our_solution: &[DiffRange<'ancestor, 'ours, T>],
     their_solution: &[DiffRange<'ancestor, 'theirs, T>],
@@ -491,6 +581,53 @@ fn cleanup_conflicts<'ancestor, 'ours, 'theirs, T: ?Sized + SliceLike + PartialE
     }
 }
 
+/// The number of lines written to the merged text so far, and the positions in the inputs after
+/// the last range which is equal in all of them
+#[derive(Default)]
+struct Position {
+    merged: usize,
+    ancestor: usize,
+    ours: usize,
+    theirs: usize,
+}
+
+impl Position {
+    fn equal<T: ?Sized>(&mut self, ancestor: &Range<T>, ours: &Range<T>, theirs: &Range<T>) {
+        self.merged += ancestor.len();
+        self.ancestor = ancestor.range().end;
+        self.ours = ours.range().end;
+        self.theirs = theirs.range().end;
+    }
+
+    /// Record a conflict at the current position.
+    ///
+    /// All changes between two equal ranges are merged into one range, so a conflict starts right
+    /// after the last equal range in every input, including the sides without lines, whose
+    /// ranges don't carry a position.
+    fn conflict<T: ?Sized>(
+        &mut self,
+        ancestor: &Range<T>,
+        ours: &Range<T>,
+        theirs: &Range<T>,
+        style: ConflictStyle,
+    ) -> Conflict {
+        let lines = |start: usize, len: usize| start + 1..start + len + 1;
+        let merged_len = match style {
+            ConflictStyle::Merge => ours.len() + theirs.len() + 3,
+            ConflictStyle::Diff3 => ours.len() + ancestor.len() + theirs.len() + 4,
+        };
+
+        let conflict = Conflict {
+            merged: lines(self.merged, merged_len),
+            ancestor: lines(self.ancestor, ancestor.len()),
+            ours: lines(self.ours, ours.len()),
+            theirs: lines(self.theirs, theirs.len()),
+        };
+        self.merged += merged_len;
+        conflict
+    }
+}
+
 fn output_result<'a, T: ?Sized>(
     ancestor: &[(&'a str, Option<LineEnd>)],
     ours: &[(&'a str, Option<LineEnd>)],
@@ -498,13 +635,15 @@ fn output_result<'a, T: ?Sized>(
     merge: &[MergeRange<T>],
     marker_len: usize,
     style: ConflictStyle,
-) -> Result<String, String> {
-    let mut conflicts = 0;
+) -> (String, Vec<Conflict>) {
+    let mut conflicts = Vec::new();
+    let mut position = Position::default();
     let mut output = String::new();
 
     for merge_range in merge {
         match merge_range {
-            MergeRange::Equal(range, ..) => {
+            MergeRange::Equal(range, our_range, their_range) => {
+                position.equal(range, our_range, their_range);
                 for (line, end) in ancestor[range.range()].iter() {
                     output.push_str(line);
                     if let Some(e) = *end {
@@ -514,6 +653,7 @@ fn output_result<'a, T: ?Sized>(
                 }
             }
             MergeRange::Conflict(ancestor_range, ours_range, theirs_range) => {
+                conflicts.push(position.conflict(ancestor_range, ours_range, theirs_range, style));
                 add_conflict_marker(&mut output, '<', marker_len, Some("ours"));
                 for (line, end) in ours[ours_range.range()].iter() {
                     output.push_str(line);
@@ -543,9 +683,9 @@ fn output_result<'a, T: ?Sized>(
                     }
                 }
                 add_conflict_marker(&mut output, '>', marker_len, Some("theirs"));
-                conflicts += 1;
             }
             MergeRange::Ours(range) => {
+                position.merged += range.len();
                 for (line, end) in ours[range.range()].iter() {
                     output.push_str(line);
                     if let Some(e) = *end {
@@ -555,6 +695,7 @@ fn output_result<'a, T: ?Sized>(
                 }
             }
             MergeRange::Theirs(range) => {
+                position.merged += range.len();
                 for (line, end) in theirs[range.range()].iter() {
                     output.push_str(line);
                     if let Some(e) = *end {
@@ -564,6 +705,7 @@ fn output_result<'a, T: ?Sized>(
                 }
             }
             MergeRange::Both(range, _) => {
+                position.merged += range.len();
                 for (line, end) in ours[range.range()].iter() {
                     output.push_str(line);
                     if let Some(e) = *end {
@@ -575,11 +717,7 @@ fn output_result<'a, T: ?Sized>(
         }
     }
 
-    if conflicts != 0 {
-        Err(output)
-    } else {
-        Ok(output)
-    }
+    (output, conflicts)
 }
 
 fn add_conflict_marker(
@@ -614,18 +752,21 @@ fn output_result_bytes<'a, T: ?Sized>(
     merge: &[MergeRange<T>],
     marker_len: usize,
     style: ConflictStyle,
-) -> Result<Vec<u8>, Vec<u8>> {
-    let mut conflicts = 0;
+) -> (Vec<u8>, Vec<Conflict>) {
+    let mut conflicts = Vec::new();
+    let mut position = Position::default();
     let mut output: Vec<u8> = Vec::new();
 
     for merge_range in merge {
         match merge_range {
-            MergeRange::Equal(range, ..) => {
+            MergeRange::Equal(range, our_range, their_range) => {
+                position.equal(range, our_range, their_range);
                 ancestor[range.range()]
                     .iter()
                     .for_each(|line| output_extend_bytes(&mut output, *line));
             }
             MergeRange::Conflict(ancestor_range, ours_range, theirs_range) => {
+                conflicts.push(position.conflict(ancestor_range, ours_range, theirs_range, style));
                 add_conflict_marker_bytes(&mut output, b'<', marker_len, Some(b"ours"));
                 ours[ours_range.range()]
                     .iter()
@@ -643,19 +784,21 @@ fn output_result_bytes<'a, T: ?Sized>(
                     .iter()
                     .for_each(|line| output_extend_bytes(&mut output, *line));
                 add_conflict_marker_bytes(&mut output, b'>', marker_len, Some(b"theirs"));
-                conflicts += 1;
             }
             MergeRange::Ours(range) => {
+                position.merged += range.len();
                 ours[range.range()]
                     .iter()
                     .for_each(|line| output_extend_bytes(&mut output, *line));
             }
             MergeRange::Theirs(range) => {
+                position.merged += range.len();
                 theirs[range.range()]
                     .iter()
                     .for_each(|line| output_extend_bytes(&mut output, *line));
             }
             MergeRange::Both(range, _) => {
+                position.merged += range.len();
                 ours[range.range()]
                     .iter()
                     .for_each(|line| output_extend_bytes(&mut output, *line));
@@ -663,11 +806,7 @@ fn output_result_bytes<'a, T: ?Sized>(
         }
     }
 
-    if conflicts != 0 {
-        Err(output)
-    } else {
-        Ok(output)
-    }
+    (output, conflicts)
 }
 
 fn add_conflict_marker_bytes(
@@ -404,3 +404,79 @@ fn delete_and_insert_conflict() {
         "MergeRange (Theirs::delete, Ours::insert) conflict"
     );
 }
+
+#[test]
+fn three_way_interleaved_edits() {
+    let ancestor = "1\n2\n3\n4\n5\n6\n7\n";
+    let ours = "1\ntwo\n3\n4\n5\nsix\n7\n";
+    let theirs = "0\n1\n2\n3\nfour\n5\n6\n7\n8\n";
+
+    let (merged, conflicts) = merge_three_way(ancestor, ours, theirs);
+    assert_eq!(merged, "0\n1\ntwo\n3\nfour\n5\nsix\n7\n8\n");
+    assert!(conflicts.is_empty());
+    assert_eq!(merge(ancestor, ours, theirs), Ok(merged));
+}
+
+#[test]
+fn three_way_conflicts() {
+    let ancestor = "a\nb\nc\nd\n";
+    let ours = "a\nB\nc\nd\nours\n";
+    let theirs = "a\nbee\nc\nd\ntheirs\n";
+
+    let (merged, conflicts) = merge_three_way(ancestor, ours, theirs);
+    assert_eq!(
+        merged,
+        "\
+a
+<<<<<<< ours
+B
+||||||| original
+b
+=======
+bee
+>>>>>>> theirs
+c
+d
+<<<<<<< ours
+ours
+||||||| original
+=======
+theirs
+>>>>>>> theirs
+"
+    );
+    assert_eq!(
+        conflicts,
+        [
+            Conflict {
+                merged: 2..9,
+                ancestor: 2..3,
+                ours: 2..3,
+                theirs: 2..3,
+            },
+            // Both sides appended lines, so there are no lines of the ancestor
+            Conflict {
+                merged: 11..17,
+                ancestor: 5..5,
+                ours: 5..6,
+                theirs: 5..6,
+            },
+        ]
+    );
+    assert_eq!(merge(ancestor, ours, theirs), Err(merged.clone()));
+
+    let (merged_bytes, conflicts_bytes) =
+        merge_three_way_bytes(ancestor.as_bytes(), ours.as_bytes(), theirs.as_bytes());
+    assert_eq!(merged_bytes, merged.as_bytes());
+    assert_eq!(conflicts_bytes, conflicts);
+
+    // Without the ancestor lines the conflicts are shorter
+    let (merged, conflicts) = MergeOptions::new()
+        .set_conflict_style(ConflictStyle::Merge)
+        .merge_three_way(ancestor, ours, theirs);
+    let lines: Vec<_> = merged.lines().collect();
+    assert_eq!(lines[conflicts[0].merged.start - 1], "<<<<<<< ours");
+    assert_eq!(lines[conflicts[0].merged.end - 2], ">>>>>>> theirs");
+    assert_eq!(conflicts[0].merged, 2..7);
+    assert_eq!(conflicts[1].merged, 9..14);
+}
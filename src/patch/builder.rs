@@ -0,0 +1,246 @@
+//! Incremental construction of a [`Diff`] from individual lines
+
+use std::{
+    borrow::Cow,
+    fmt::{self, Debug},
+};
+
+use super::{Diff, Hunk, HunkRange, Line};
+use crate::{LineEnd, utils::Text};
+
+/// Builds a [`Diff`] line by line, computing the ranges of its hunks
+///
+/// The builder keeps track of the current position in the old and the new file. Context lines
+/// advance both positions, deleted lines only the old one and inserted lines only the new one.
+/// Lines which aren't part of any hunk are skipped with [`DiffBuilder::skip_lines`].
+///
+/// Lines are given with their line ending, like the lines of a [`Hunk`]. A line without a
+/// line ending is the last line of a file which doesn't end with a newline.
+///
+/// ```
+/// use flickzeug::{DiffBuilder, apply};
+///
+/// let diff = DiffBuilder::new()
+///     .set_original_filename("a/greeting.txt")
+///     .set_modified_filename("b/greeting.txt")
+///     .skip_lines(1)
+///     .context_line("hello\n")
+///     .delete_line("world\n")
+///     .add_line("there\n")
+///     .build();
+///
+/// assert_eq!(diff.hunks()[0].old_range().range(), 2..4);
+/// assert_eq!(apply("#!\nhello\nworld\n", &diff).unwrap().0, "#!\nhello\nthere\n");
+/// ```
+pub struct DiffBuilder<'a, T: ToOwned + ?Sized> {
+    original: Option<Cow<'a, T>>,
+    modified: Option<Cow<'a, T>>,
+    hunks: Vec<Hunk<'a, T>>,
+    lines: Vec<Line<'a, T>>,
+    // 0-based indices of the next line in the old and the new file
+    old_index: usize,
+    new_index: usize,
+}
+
+impl<'a, T: Text + ToOwned + ?Sized> DiffBuilder<'a, T> {
+    /// Construct a new builder positioned at the start of both files, without filenames
+    pub fn new() -> Self {
+        Self {
+            original: None,
+            modified: None,
+            hunks: Vec::new(),
+            lines: Vec::new(),
+            old_index: 0,
+            new_index: 0,
+        }
+    }
+
+    /// Set the filename of the original file
+    pub fn set_original_filename<F>(&mut self, filename: F) -> &mut Self
+    where
+        F: Into<Cow<'a, T>>,
+    {
+        self.original = Some(filename.into());
+        self
+    }
+
+    /// Set the filename of the modified file
+    pub fn set_modified_filename<F>(&mut self, filename: F) -> &mut Self
+    where
+        F: Into<Cow<'a, T>>,
+    {
+        self.modified = Some(filename.into());
+        self
+    }
+
+    /// Returns the 1-based number of the next line of the old file
+    pub fn old_line(&self) -> usize {
+        self.old_index + 1
+    }
+
+    /// Returns the 1-based number of the next line of the new file
+    pub fn new_line(&self) -> usize {
+        self.new_index + 1
+    }
+
+    /// Add a line present in both files to the current hunk
+    pub fn context_line(&mut self, line: &'a T) -> &mut Self {
+        self.old_index += 1;
+        self.new_index += 1;
+        self.lines.push(Line::Context(LineEnd::strip(line)));
+        self
+    }
+
+    /// Add a line deleted from the old file to the current hunk
+    pub fn delete_line(&mut self, line: &'a T) -> &mut Self {
+        self.old_index += 1;
+        self.lines.push(Line::Delete(LineEnd::strip(line)));
+        self
+    }
+
+    /// Add a line inserted into the new file to the current hunk
+    pub fn add_line(&mut self, line: &'a T) -> &mut Self {
+        self.new_index += 1;
+        self.lines.push(Line::Insert(LineEnd::strip(line)));
+        self
+    }
+
+    /// Finish the current hunk, the next line starts a new one
+    ///
+    /// This does nothing if the current hunk has no lines.
+    pub fn finish_hunk(&mut self) -> &mut Self {
+        if let Some(hunk) = self.current_hunk() {
+            self.hunks.push(hunk);
+            self.lines.clear();
+        }
+        self
+    }
+
+    /// Finish the current hunk and skip `n` unchanged lines which aren't part of any hunk
+    pub fn skip_lines(&mut self, n: usize) -> &mut Self {
+        self.finish_hunk();
+        self.old_index += n;
+        self.new_index += n;
+        self
+    }
+
+    /// Build the diff, including the current hunk if it has any lines
+    ///
+    /// The builder is left unchanged, so more lines can be added to build a larger diff.
+    pub fn build(&self) -> Diff<'a, T> {
+        let mut hunks = self.hunks.clone();
+        hunks.extend(self.current_hunk());
+        Diff::new(self.original.clone(), self.modified.clone(), hunks)
+    }
+
+    fn current_hunk(&self) -> Option<Hunk<'a, T>> {
+        if self.lines.is_empty() {
+            return None;
+        }
+
+        let mut old_len = 0;
+        let mut new_len = 0;
+        for line in &self.lines {
+            match line {
+                Line::Context(_) => {
+                    old_len += 1;
+                    new_len += 1;
+                }
+                Line::Delete(_) => old_len += 1,
+                Line::Insert(_) => new_len += 1,
+            }
+        }
+
+        Some(Hunk::new(
+            HunkRange::from_index(self.old_index - old_len, old_len),
+            HunkRange::from_index(self.new_index - new_len, new_len),
+            None,
+            self.lines.clone(),
+        ))
+    }
+}
+
+impl<T: Text + ToOwned + ?Sized> Default for DiffBuilder<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Debug for DiffBuilder<'_, T>
+where
+    T: ?Sized + ToOwned<Owned: Debug> + Debug + Text,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiffBuilder")
+            .field("original", &self.original)
+            .field("modified", &self.modified)
+            .field("hunks", &self.hunks)
+            .field("lines", &self.lines)
+            .field("old_line", &self.old_line())
+            .field("new_line", &self.new_line())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DiffBuilder;
+    use crate::{Diff, HunkRange};
+
+    #[test]
+    fn change_and_insert_hunks() {
+        let expected = Diff::from_str(
+            "\
+--- file.txt
++++ file.txt
+@@ -2,3 +2,3 @@
+ b
+-c
++C
+ d
+@@ -7,0 +8,2 @@
++g.1
++g.2
+",
+        )
+        .unwrap();
+
+        let mut builder = DiffBuilder::new();
+        builder
+            .set_original_filename("file.txt")
+            .set_modified_filename("file.txt")
+            .skip_lines(1)
+            .context_line("b\n")
+            .delete_line("c\n")
+            .add_line("C\n")
+            .context_line("d\n")
+            .finish_hunk()
+            .skip_lines(3);
+        assert_eq!((builder.old_line(), builder.new_line()), (8, 8));
+        builder.add_line("g.1\n").add_line("g.2\n");
+        let diff = builder.build();
+
+        assert_eq!(diff.hunks()[0].old_range(), HunkRange::new(2, 3));
+        assert_eq!(diff.hunks()[1].old_range(), HunkRange::new(7, 0));
+        assert_eq!(diff.hunks()[1].new_range(), HunkRange::new(8, 2));
+        assert_eq!(diff, expected);
+        assert_eq!(diff.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn missing_newline_at_end_of_file() {
+        let expected = Diff::from_str(
+            "\
+@@ -1 +1 @@
+-a
+\\ No newline at end of file
++a
+",
+        )
+        .unwrap();
+
+        let diff = DiffBuilder::new().delete_line("a").add_line("a\n").build();
+
+        assert_eq!(diff, expected);
+    }
+}
@@ -0,0 +1,416 @@
+//! Combine two consecutive diffs into one
+
+use super::{Diff, Hunk, HunkRange, Line};
+use crate::{ApplyError, LineEnd, MatchOptions, utils::Text};
+
+type FunctionContext<'a, T> = Option<(&'a T, Option<LineEnd>)>;
+
+impl<'a, T: Text + ToOwned + AsRef<[u8]> + ?Sized> Diff<'a, T> {
+    /// Combine this diff with `other`, a diff of the text this diff produces, into a single diff
+    /// which turns the original text of this diff into the result of `other`
+    ///
+    /// Hunks of both diffs which overlap or touch in the intermediate text are merged into a single
+    /// hunk. Lines inserted by this diff and deleted again by `other` are left out of it, and hunks
+    /// which don't change anything anymore are dropped. The filenames are taken from the original
+    /// side of this diff and the modified side of `other`.
+    ///
+    /// Where `other` touches lines of the hunks of this diff, its context and deleted lines must
+    /// match them, otherwise an [`ApplyError::ContextMismatch`] for the hunk of `other` is
+    /// returned. If the hunks of either diff overlap in the intermediate text, an
+    /// [`ApplyError::OverlappingHunks`] is returned, and if either diff is for a binary file, an
+    /// [`ApplyError::BinaryNotSupported`].
+    ///
+    /// ```
+    /// use flickzeug::{Diff, apply};
+    ///
+    /// let first = Diff::from_str("@@ -1,2 +1,2 @@\n a\n-b\n+B\n").unwrap();
+    /// let second = Diff::from_str("@@ -2,2 +2,2 @@\n-B\n+bee\n c\n").unwrap();
+    /// let diff = first.compose(&second).unwrap();
+    /// assert_eq!(diff.to_string(), "@@ -1,3 +1,3 @@\n a\n-b\n+bee\n c\n");
+    /// assert_eq!(apply("a\nb\nc\n", &diff).unwrap().0, "a\nbee\nc\n");
+    /// ```
+    pub fn compose(&self, other: &Diff<'a, T>) -> Result<Diff<'a, T>, ApplyError> {
+        if self.binary || other.binary {
+            return Err(ApplyError::BinaryNotSupported);
+        }
+        check_overlapping(&self.hunks, |hunk| hunk.new_range)?;
+        check_overlapping(&other.hunks, |hunk| hunk.old_range)?;
+
+        let mut hunks = Vec::new();
+        // The change in the number of lines caused by the hunks of either diff before the group
+        let mut first_delta = 0;
+        let mut second_delta = 0;
+        for group in groups(&self.hunks, &other.hunks) {
+            if let Some((function_context, lines)) = group.compose()? {
+                let mut hunk = Hunk::new(
+                    HunkRange::from_index(group.start.saturating_add_signed(-first_delta), 0),
+                    HunkRange::from_index(group.start.saturating_add_signed(second_delta), 0),
+                    function_context,
+                    lines,
+                );
+                hunk.recount();
+                hunks.push(hunk);
+            }
+            first_delta += group.first.iter().map(|hunk| delta(hunk)).sum::<isize>();
+            second_delta += group
+                .second
+                .iter()
+                .map(|(_, hunk)| delta(hunk))
+                .sum::<isize>();
+        }
+
+        Ok(Diff {
+            original: self.original.clone(),
+            modified: other.modified.clone(),
+            hunks,
+            requires_verification: false,
+            preamble: None,
+            binary: false,
+            git_index: None,
+            old_mode: self.old_mode,
+            new_mode: other.new_mode,
+            skip_order_check: false,
+        })
+    }
+}
+
+/// Hunks of the first and the second diff which overlap or touch in the intermediate text
+struct Group<'h, 'a, T: ?Sized + ToOwned> {
+    /// The index of the first line of the group in the intermediate text
+    start: usize,
+    /// The index of the line after the group in the intermediate text
+    end: usize,
+    first: Vec<&'h Hunk<'a, T>>,
+    /// The hunks of the second diff along with their index in it
+    second: Vec<(usize, &'h Hunk<'a, T>)>,
+}
+
+impl<'a, T: Text + ToOwned + AsRef<[u8]> + ?Sized> Group<'_, 'a, T> {
+    /// Combine the hunks of the group into the lines of a single hunk, or `None` if they cancel
+    /// each other out
+    #[allow(clippy::type_complexity)]
+    fn compose(&self) -> Result<Option<(FunctionContext<'a, T>, Vec<Line<'a, T>>)>, ApplyError> {
+        let intermediate = self.intermediate_lines()?;
+
+        // Both diffs as a list of lines covering the whole group
+        let first = expand(
+            self.first.iter().map(|hunk| (hunk.new_range, *hunk)),
+            self.start,
+            &intermediate,
+        );
+        let second = expand(
+            self.second.iter().map(|(_, hunk)| (hunk.old_range, *hunk)),
+            self.start,
+            &intermediate,
+        );
+
+        let mut first = first.into_iter().peekable();
+        let mut second = second.into_iter().peekable();
+        let mut lines = Vec::new();
+        loop {
+            // Lines deleted by the first and inserted by the second diff aren't part of the
+            // intermediate text, every other line is one of its lines
+            if let Some(line) = first.next_if(|line| matches!(line, Line::Delete(_))) {
+                lines.push(line);
+                continue;
+            }
+            if let Some(line) = second.next_if(|line| matches!(line, Line::Insert(_))) {
+                lines.push(line);
+                continue;
+            }
+            match (first.next(), second.next()) {
+                (Some(Line::Context(line)), Some(Line::Context(_))) => {
+                    lines.push(Line::Context(line))
+                }
+                (Some(Line::Context(line)), Some(Line::Delete(_))) => {
+                    lines.push(Line::Delete(line))
+                }
+                (Some(Line::Insert(line)), Some(Line::Context(_))) => {
+                    lines.push(Line::Insert(line))
+                }
+                // A line inserted by the first diff is deleted again by the second one
+                (Some(Line::Insert(_)), Some(Line::Delete(_))) => {}
+                _ => break,
+            }
+        }
+
+        // E.g. the second diff may undo the changes of the first one
+        let old_side = lines.iter().filter_map(|line| match line {
+            Line::Context(line) | Line::Delete(line) => Some(line),
+            Line::Insert(_) => None,
+        });
+        let new_side = lines.iter().filter_map(|line| match line {
+            Line::Context(line) | Line::Insert(line) => Some(line),
+            Line::Delete(_) => None,
+        });
+        if old_side.eq(new_side) {
+            return Ok(None);
+        }
+        let function_context = self
+            .first
+            .first()
+            .or(self.second.first().map(|(_, hunk)| hunk))
+            .and_then(|hunk| hunk.function_context);
+        Ok(Some((function_context, lines)))
+    }
+
+    /// Collect the lines of the intermediate text covered by the group from the new side of the
+    /// hunks of the first diff and the old side of the hunks of the second diff, making sure both
+    /// agree on them
+    fn intermediate_lines(&self) -> Result<Vec<(&'a T, Option<LineEnd>)>, ApplyError> {
+        let mut intermediate = vec![None; self.end - self.start];
+        for hunk in &self.first {
+            let new_side = hunk.lines.iter().filter_map(|line| match line {
+                Line::Context(line) | Line::Insert(line) => Some(*line),
+                Line::Delete(_) => None,
+            });
+            let start = hunk.new_range.index() - self.start;
+            for (slot, line) in intermediate[start..].iter_mut().zip(new_side) {
+                *slot = Some(line);
+            }
+        }
+
+        for &(hunk_index, hunk) in &self.second {
+            let old_side: Vec<_> = hunk
+                .lines
+                .iter()
+                .filter_map(|line| match line {
+                    Line::Context(line) | Line::Delete(line) => Some(*line),
+                    Line::Insert(_) => None,
+                })
+                .collect();
+            let start = hunk.old_range.index() - self.start;
+            let known = &intermediate[start..];
+            let matches = known.iter().zip(&old_side).all(|(known, (line, _))| {
+                known.is_none_or(|(known, _)| known.as_bytes() == line.as_bytes())
+            });
+            if !matches {
+                let target = known
+                    .iter()
+                    .zip(&old_side)
+                    .map(|(known, line)| known.unwrap_or(*line).0);
+                return Err(ApplyError::context_mismatch(
+                    hunk_index,
+                    hunk,
+                    target,
+                    &MatchOptions::default(),
+                ));
+            }
+            for (slot, line) in intermediate[start..].iter_mut().zip(old_side) {
+                slot.get_or_insert(line);
+            }
+        }
+
+        // The hunks of a group cover all of its lines
+        Ok(intermediate.into_iter().flatten().collect())
+    }
+}
+
+/// Group the hunks of `first` and `second` which overlap or touch in the intermediate text
+fn groups<'h, 'a, T: ?Sized + ToOwned>(
+    first: &'h [Hunk<'a, T>],
+    second: &'h [Hunk<'a, T>],
+) -> Vec<Group<'h, 'a, T>> {
+    let mut first = first.iter().peekable();
+    let mut second = second.iter().enumerate().peekable();
+    let mut groups: Vec<Group<'h, 'a, T>> = Vec::new();
+    loop {
+        let take_first = match (first.peek(), second.peek()) {
+            (Some(a), Some((_, b))) => a.new_range.index() <= b.old_range.index(),
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break,
+        };
+        let range = if take_first {
+            first.peek().map(|hunk| hunk.new_range)
+        } else {
+            second.peek().map(|(_, hunk)| hunk.old_range)
+        }
+        .unwrap();
+        let (start, end) = (range.index(), range.index() + range.len());
+
+        if groups.last().is_none_or(|group| start > group.end) {
+            groups.push(Group {
+                start,
+                end,
+                first: Vec::new(),
+                second: Vec::new(),
+            });
+        }
+        let group = groups.last_mut().unwrap();
+        group.end = group.end.max(end);
+        if take_first {
+            group.first.extend(first.next());
+        } else {
+            group.second.extend(second.next());
+        }
+    }
+    groups
+}
+
+/// Turn the hunks of a diff within a group into a list of lines covering the whole group, using
+/// context lines from `intermediate` for the lines between the hunks
+///
+/// `hunks` yields every hunk along with its range in the intermediate text.
+fn expand<'h, 'a: 'h, T: ?Sized + ToOwned + 'h>(
+    hunks: impl Iterator<Item = (HunkRange, &'h Hunk<'a, T>)>,
+    group_start: usize,
+    intermediate: &[(&'a T, Option<LineEnd>)],
+) -> Vec<Line<'a, T>> {
+    let mut lines = Vec::new();
+    let mut next = 0;
+    for (range, hunk) in hunks {
+        let start = range.index() - group_start;
+        let between = intermediate.get(next..start).unwrap_or_default();
+        lines.extend(between.iter().copied().map(Line::Context));
+        lines.extend(hunk.lines.iter().copied());
+        next = start + range.len();
+    }
+    let after = intermediate.get(next..).unwrap_or_default();
+    lines.extend(after.iter().copied().map(Line::Context));
+    lines
+}
+
+/// Make sure that no hunk starts before the end of the hunk before it, comparing the ranges of
+/// the hunks returned by `range`
+fn check_overlapping<T: ?Sized + ToOwned>(
+    hunks: &[Hunk<'_, T>],
+    range: impl Fn(&Hunk<'_, T>) -> HunkRange,
+) -> Result<(), ApplyError> {
+    for (first, pair) in hunks.windows(2).enumerate() {
+        let (a, b) = (range(&pair[0]), range(&pair[1]));
+        if a.index() + a.len() > b.index() {
+            return Err(ApplyError::OverlappingHunks {
+                first,
+                second: first + 1,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The change in the number of lines caused by a hunk
+fn delta<T: ?Sized + ToOwned>(hunk: &Hunk<'_, T>) -> isize {
+    hunk.new_range.len() as isize - hunk.old_range.len() as isize
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ApplyError, Diff, apply, create_patch};
+
+    /// Compose the diffs from `old` to `mid` and from `mid` to `new`, checking the result turns
+    /// `old` into `new`
+    fn compose(old: &str, mid: &str, new: &str) -> String {
+        let first = create_patch(old, mid);
+        let second = create_patch(mid, new);
+        let diff = first.compose(&second).unwrap();
+        assert_eq!(apply(old, &diff).unwrap().0, new);
+        diff.to_string()
+    }
+
+    #[test]
+    fn change_then_change() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let mid = "1\n2\n3\nfour\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\n3\nFOUR\nfive\n6\n7\n8\n9\n10\n";
+        assert_eq!(
+            compose(old, mid, new),
+            "\
+--- original
++++ modified
+@@ -1,8 +1,8 @@
+ 1
+ 2
+ 3
+-4
+-5
++FOUR
++five
+ 6
+ 7
+ 8
+"
+        );
+
+        // Changing a line back cancels out the first change
+        assert_eq!(compose(old, mid, old), "--- original\n+++ modified\n");
+    }
+
+    #[test]
+    fn delete_then_insert_elsewhere() {
+        let join =
+            |lines: &[String]| -> String { lines.iter().map(|line| line.clone() + "\n").collect() };
+        let mut lines: Vec<_> = (1..=30).map(|i| i.to_string()).collect();
+        let old = join(&lines);
+        // Delete lines 5 and 6, then insert a line after line 25
+        lines.drain(4..6);
+        let mid = join(&lines);
+        lines.insert(23, "25.5".to_owned());
+        let new = join(&lines);
+        let diff = compose(&old, &mid, &new);
+
+        let diff = Diff::from_str(&diff).unwrap();
+        let ranges: Vec<_> = diff
+            .hunks()
+            .iter()
+            .map(|hunk| (hunk.old_range().to_string(), hunk.new_range().to_string()))
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                ("2,8".to_owned(), "2,6".to_owned()),
+                ("23,6".to_owned(), "21,7".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn delete_inserted_lines() {
+        let old = "a\nb\nc\nd\n";
+        let mid = "a\nb\nx\ny\nc\nd\n";
+        let new = "a\nx\nd\n";
+        assert_eq!(
+            compose(old, mid, new),
+            "--- original\n+++ modified\n@@ -1,4 +1,3 @@\n a\n-b\n-c\n+x\n d\n"
+        );
+
+        // Hunks of the second diff next to the first diff's are merged into one
+        let first = Diff::from_str("@@ -2 +2 @@\n-b\n+B\n").unwrap();
+        let second = Diff::from_str("@@ -1 +1 @@\n-a\n+A\n@@ -2,0 +3 @@\n+b2\n").unwrap();
+        let diff = first.compose(&second).unwrap();
+        assert_eq!(diff.hunks().len(), 1);
+        assert_eq!(apply("a\nb\nc\n", &diff).unwrap().0, "A\nB\nb2\nc\n");
+    }
+
+    #[test]
+    fn mismatched_intermediate_text() {
+        let first = Diff::from_str("@@ -1,2 +1,2 @@\n a\n-b\n+B\n").unwrap();
+        // The hunks of the second diff are out of order
+        let mut second = Diff::from_str("@@ -1 +1 @@\n-a\n+x\n@@ -3 +3 @@\n-c\n+C\n").unwrap();
+        second.hunks_mut().swap(0, 1);
+        assert_eq!(
+            first.compose(&second).unwrap_err(),
+            ApplyError::OverlappingHunks {
+                first: 0,
+                second: 1
+            }
+        );
+
+        let second = Diff::from_str("@@ -2 +2 @@\n-b\n+x\n").unwrap();
+        let err = first.compose(&second).unwrap_err();
+        let ApplyError::ContextMismatch {
+            hunk_index,
+            at_line,
+            expected,
+            found,
+            ..
+        } = err
+        else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(
+            (hunk_index, at_line, expected.as_str(), found.as_str()),
+            (0, 2, "b", "B")
+        );
+    }
+}
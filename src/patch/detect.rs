@@ -0,0 +1,221 @@
+//! Parse patches whose format isn't known in advance
+
+use super::{
+    Diff, ParsePatchError, is_context_diff, is_normal_diff, is_unified_diff, parse,
+    parse::GIT_EXTENDED_HEADERS, parse_context, parse_context::is_hunk_separator,
+    parse_context_bytes, parse_normal, parse_normal_bytes,
+};
+use crate::utils::{LineIter, Text, strip_bom};
+
+/// Prefixes of the lines which can precede the first file header or hunk of a patch, like the
+/// `Index:` headers of svn, besides the extended headers of git
+const PREAMBLE_PREFIXES: &[&str] = &["#", "diff ", "Index: ", "===="];
+
+/// The formats recognized by [`parse_auto`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Unified,
+    Context,
+    Normal,
+}
+
+/// Parse a patch in the unified, context or normal diff format
+///
+/// The format is detected from the first line which isn't empty, a `#` comment or part of a header
+/// like `diff --git` or `Index:`. If that line doesn't start a patch in any of the supported
+/// formats, [`ParsePatchError::UnknownFormat`] is returned. The lines before it are kept as the
+/// [preamble](Diff::preamble) of the returned diff, while a UTF-8 byte order mark at the start of
+/// the patch is ignored.
+///
+/// ```
+/// use flickzeug::parse_auto;
+///
+/// let unified = parse_auto("--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n+b\n").unwrap();
+/// let normal = parse_auto("1c1\n< a\n---\n> b\n").unwrap();
+/// assert_eq!(unified.hunks()[0].lines(), normal.hunks()[0].lines());
+/// ```
+pub fn parse_auto(input: &str) -> Result<Diff<'_, str>, ParsePatchError> {
+    let input = strip_bom(input);
+    let (format, rest) = detect(input).ok_or(ParsePatchError::UnknownFormat)?;
+    match format {
+        Format::Unified => parse::parse(input),
+        Format::Context => parse_context(input),
+        Format::Normal => with_preamble(parse_normal(rest), input, rest),
+    }
+}
+
+/// Parse a potentially non-utf8 patch in the unified, context or normal diff format, see
+/// [`parse_auto`]
+pub fn parse_auto_bytes(input: &[u8]) -> Result<Diff<'_, [u8]>, ParsePatchError> {
+    let input = strip_bom(input);
+    let (format, rest) = detect(input).ok_or(ParsePatchError::UnknownFormat)?;
+    match format {
+        Format::Unified => parse::parse_bytes(input),
+        Format::Context => parse_context_bytes(input),
+        Format::Normal => with_preamble(parse_normal_bytes(rest), input, rest),
+    }
+}
+
+/// Set the preamble of a diff parsed from `rest` to the part of `input` before it, as the normal
+/// diff parser only sees `rest`
+fn with_preamble<'a, T: Text + ToOwned + ?Sized>(
+    diff: Result<Diff<'a, T>, ParsePatchError>,
+    input: &T,
+    rest: &T,
+) -> Result<Diff<'a, T>, ParsePatchError> {
+    let skipped = input.split_at(input.len() - rest.len()).0;
+    let lines: Vec<_> = LineIter::new(skipped).collect();
+    let mut diff = diff?;
+    diff.preamble = parse::preamble(&lines);
+    Ok(diff)
+}
+
+/// Detect the format of a patch, returning it along with the patch without its preamble
+fn detect<T: Text + ?Sized>(input: &T) -> Option<(Format, &T)> {
+    let (line, start, _) = LineIter::new(input).with_offsets().find(|(line, _, _)| {
+        let is_preamble = line.as_bytes().iter().all(u8::is_ascii_whitespace)
+            || PREAMBLE_PREFIXES
+                .iter()
+                .chain(GIT_EXTENDED_HEADERS)
+                .any(|prefix| line.starts_with(prefix));
+        !is_preamble
+    })?;
+    let rest = input.split_at(start).1;
+
+    let format = if is_unified_diff(rest) {
+        Format::Unified
+    } else if (line.starts_with("*** ") || is_hunk_separator(line)) && is_context_diff(rest) {
+        Format::Context
+    } else if is_normal_diff(rest) {
+        Format::Normal
+    } else {
+        return None;
+    };
+    Some((format, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{parse_auto, parse_auto_bytes};
+    use crate::{
+        Diff, ParsePatchError, is_context_diff, is_normal_diff, is_unified_diff, parse_context,
+        parse_context_bytes, parse_normal, parse_normal_bytes,
+    };
+
+    fn read_test_data(path: &[&str]) -> String {
+        let path = path.iter().fold(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-data"),
+            |path, part| path.join(part),
+        );
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn detect_fixtures() {
+        let fixtures: [(&[&str], usize); 5] = [
+            (&["context-diff", "change", "patch.diff"], 1),
+            (&["context-diff", "delete", "patch.diff"], 3),
+            (&["normal-diff", "change", "patch.diff"], 2),
+            (&["normal-diff", "mixed", "patch.diff"], 3),
+            (&["fuzzy", "patch.patch"], 2),
+        ];
+        for (path, hunks) in fixtures {
+            let patch = read_test_data(path);
+            assert_eq!(parse_auto(&patch).unwrap().hunks().len(), hunks, "{path:?}");
+            let diff = parse_auto_bytes(patch.as_bytes()).unwrap();
+            assert_eq!(diff.hunks().len(), hunks, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let fixtures: [&[&str]; 3] = [
+            &["context-diff", "change", "patch.diff"],
+            &["normal-diff", "mixed", "patch.diff"],
+            &["fuzzy", "patch.patch"],
+        ];
+        for path in fixtures {
+            let patch = read_test_data(path);
+            let with_bom = format!("\u{feff}{patch}");
+            assert_eq!(parse_auto(&with_bom).unwrap(), parse_auto(&patch).unwrap());
+            assert_eq!(
+                parse_auto_bytes(with_bom.as_bytes()).unwrap(),
+                parse_auto_bytes(patch.as_bytes()).unwrap()
+            );
+        }
+
+        let patch = format!("\u{feff}{}", read_test_data(&["fuzzy", "patch.patch"]));
+        assert!(is_unified_diff(patch.as_str()));
+        assert_eq!(Diff::from_str(&patch).unwrap().preamble(), None);
+        assert!(Diff::from_bytes(patch.as_bytes()).is_ok());
+
+        let patch = format!(
+            "\u{feff}{}",
+            read_test_data(&["normal-diff", "mixed", "patch.diff"])
+        );
+        assert!(is_normal_diff(patch.as_bytes()));
+        assert!(parse_normal(&patch).is_ok());
+        assert!(parse_normal_bytes(patch.as_bytes()).is_ok());
+
+        let patch = format!(
+            "\u{feff}{}",
+            read_test_data(&["context-diff", "change", "patch.diff"])
+        );
+        assert!(is_context_diff(patch.as_str()));
+        assert_eq!(parse_context(&patch).unwrap().preamble(), None);
+        assert!(parse_context_bytes(patch.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn skip_headers() {
+        let unified = "\
+diff --git a/f b/f
+index 1234567..89abcde 100644
+--- a/f
++++ b/f
+@@ -1 +1 @@
+-a
++b
+";
+        let diff = parse_auto(unified).unwrap();
+        assert_eq!(diff.original(), Some("f"));
+
+        let normal = "# generated by diff\nIndex: f\n===================================================================\n1c1\n< a\n---\n> b\n";
+        let diff = parse_auto(normal).unwrap();
+        assert_eq!(diff.hunks().len(), 1);
+        assert_eq!(
+            diff.preamble(),
+            Some(
+                "# generated by diff\nIndex: f\n===================================================================\n"
+            )
+        );
+        assert_eq!(
+            parse_auto_bytes(normal.as_bytes()).unwrap().preamble(),
+            diff.preamble()
+        );
+
+        let context =
+            "diff -c a b\n*** a\n--- b\n***************\n*** 1 ****\n! a\n--- 1 ----\n! b\n";
+        assert_eq!(parse_auto(context).unwrap().original(), Some("a"));
+    }
+
+    #[test]
+    fn unknown_format() {
+        for patch in [
+            "",
+            "\n\n",
+            "just some text\n",
+            "diff --git a/f b/f\n",
+            "*** a\n",
+            // Not a unified diff according to is_unified_diff either
+            "@@ -a +1 @@\n",
+        ] {
+            assert!(
+                matches!(parse_auto(patch), Err(ParsePatchError::UnknownFormat)),
+                "{patch:?}"
+            );
+        }
+    }
+}
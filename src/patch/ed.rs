@@ -0,0 +1,198 @@
+//! Parse patches in the ed script format
+//!
+//! The ed script format is the output of `diff -e`. Like the normal format it has no file headers
+//! and no context lines, but its commands are instructions for the `ed` editor:
+//!
+//! * `Na` adds the following lines after line `N`
+//! * `Rc` replaces the lines `R` with the following lines
+//! * `Rd` deletes the lines `R`
+//!
+//! where a range `R` is either a single line number or two line numbers separated by a comma. The
+//! lines added by `a` and `c` commands are terminated by a line containing a single `.`. The
+//! commands refer to the lines of the old file, so `diff` lists them from the end of the file to
+//! its start, keeping the line numbers of the remaining commands valid as each one is applied.
+//!
+//! Deleted lines aren't part of the script, so parsing one requires the old file as well.
+
+use super::{Diff, Hunk, HunkRange, Line, ParsePatchError, normal::range};
+use crate::utils::{LineIter, Text, strip_bom};
+
+type Result<T, E = ParsePatchError> = std::result::Result<T, E>;
+
+/// Parse a patch in the ed script format, like the output of `diff -e`
+///
+/// As the script doesn't contain the lines it deletes, they are taken from `original`, the text
+/// the script applies to. The returned `Diff` has no filenames, its hunks have no context lines
+/// and they are sorted by their position in the file, the reverse of the order of the commands
+/// in the script. Errors are wrapped in [`ParsePatchError::Location`] with the line of the script
+/// they were found at.
+///
+/// Scripts which `diff` can only express with substitute commands, like ones adding a line
+/// consisting of a single `.`, aren't supported.
+///
+/// ```
+/// use flickzeug::{apply, parse_ed};
+///
+/// let original = "a\nb\nc\n";
+/// let diff = parse_ed("3a\nd\n.\n2c\nB\n.\n", original).unwrap();
+/// assert_eq!(diff.hunks().len(), 2);
+/// assert_eq!(apply(original, &diff).unwrap().0, "a\nB\nc\nd\n");
+/// ```
+pub fn parse_ed<'a>(script: &'a str, original: &'a str) -> Result<Diff<'a, str>> {
+    let hunks = parse_ed_hunks(script, original)?;
+    Ok(Diff::new(None::<&str>, None::<&str>, hunks))
+}
+
+/// Parse a potentially non-utf8 patch in the ed script format, see [`parse_ed`]
+pub fn parse_ed_bytes<'a>(script: &'a [u8], original: &'a [u8]) -> Result<Diff<'a, [u8]>> {
+    let hunks = parse_ed_hunks(script, original)?;
+    Ok(Diff::new(None::<&[u8]>, None::<&[u8]>, hunks))
+}
+
+fn parse_ed_hunks<'a, T: Text + ToOwned + ?Sized>(
+    script: &'a T,
+    original: &'a T,
+) -> Result<Vec<Hunk<'a, T>>> {
+    let original: Vec<_> = LineIter::new(original).collect();
+    let mut lines = LineIter::new(strip_bom(script)).enumerate();
+    let mut hunks = Vec::new();
+
+    while let Some((index, (line, _))) = lines.next() {
+        let at_command = |error: ParsePatchError| error.at_line(index + 1);
+        let (command, old_range) = command_line(line).map_err(at_command)?;
+
+        let deleted = original
+            .get(old_range.index()..old_range.index() + old_range.len())
+            .ok_or_else(|| at_command(ParsePatchError::Range))?;
+        let mut hunk_lines: Vec<_> = deleted.iter().copied().map(Line::Delete).collect();
+
+        if command != b'd' {
+            loop {
+                match lines.next() {
+                    Some((_, (line, _))) if line.as_bytes() == b"." => break,
+                    Some((_, line)) => hunk_lines.push(Line::Insert(line)),
+                    None => return Err(at_command(ParsePatchError::UnexpectedEof)),
+                }
+            }
+        }
+
+        // The commands are listed from the end of the file to its start
+        hunks.push(Hunk::new(old_range, old_range, None, hunk_lines));
+        if let [.., next, hunk] = hunks.as_slice()
+            && hunk.old_range.index() + hunk.old_range.len() > next.old_range.index()
+        {
+            return Err(at_command(ParsePatchError::HunksOrder));
+        }
+    }
+
+    hunks.reverse();
+    let mut delta = 0isize;
+    for hunk in &mut hunks {
+        let new_index = hunk.old_range.index().checked_add_signed(delta).unwrap();
+        hunk.new_range = HunkRange::from_index(new_index, 0);
+        hunk.recount();
+        delta += hunk.new_range.len() as isize - hunk.old_range.len() as isize;
+    }
+
+    Ok(hunks)
+}
+
+/// Parse a command line like `3,5c` into its command and the lines of the old file it affects
+fn command_line<T: Text + ?Sized>(line: &T) -> Result<(u8, HunkRange)> {
+    let (&command, line_range) = line
+        .as_bytes()
+        .split_last()
+        .ok_or(ParsePatchError::HunkHeader)?;
+    let (start, end) = range(line_range)?;
+
+    let old_range = match command {
+        b'a' if start == end => HunkRange::new(start, 0),
+        b'c' | b'd' if start > 0 => HunkRange::new(start, end - start + 1),
+        _ => return Err(ParsePatchError::HunkHeader),
+    };
+    Ok((command, old_range))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{parse_ed, parse_ed_bytes};
+    use crate::{Line, LineEnd, ParsePatchError, apply, apply_bytes, diff_normal};
+
+    fn load_fixture(name: &str) -> (String, String, String) {
+        let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join("ed-diff")
+            .join(name);
+        let read = |file| std::fs::read_to_string(folder.join(file)).unwrap();
+        (read("target.txt"), read("patch.ed"), read("result.txt"))
+    }
+
+    #[test]
+    fn fixtures_apply() {
+        for name in ["add", "change", "delete", "mixed"] {
+            let (target, script, result) = load_fixture(name);
+
+            let diff = parse_ed(&script, &target).unwrap();
+            assert_eq!(apply(&target, &diff).unwrap().0, result, "{name}");
+            // The hunks are the same as the ones of the normal diff
+            assert_eq!(
+                diff.hunks(),
+                diff_normal(&target, &result).hunks(),
+                "{name}"
+            );
+
+            let diff = parse_ed_bytes(script.as_bytes(), target.as_bytes()).unwrap();
+            assert_eq!(
+                apply_bytes(target.as_bytes(), &diff).unwrap().0,
+                result.as_bytes(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn deleted_lines_from_original() {
+        let diff = parse_ed("4d\n1,2c\nx\n.\n", "a\nb\nc\nd").unwrap();
+        let hunks = diff.hunks();
+        assert_eq!(
+            hunks[0].lines(),
+            [
+                Line::Delete(("a", Some(LineEnd::Lf))),
+                Line::Delete(("b", Some(LineEnd::Lf))),
+                Line::Insert(("x", Some(LineEnd::Lf))),
+            ]
+        );
+        assert_eq!(hunks[1].lines(), [Line::Delete(("d", None))]);
+        assert_eq!(
+            (
+                hunks[1].old_range().to_string(),
+                hunks[1].new_range().to_string()
+            ),
+            ("4".to_owned(), "2,0".to_owned())
+        );
+        assert_eq!(apply("a\nb\nc\nd", &diff).unwrap().0, "x\nc\n");
+    }
+
+    #[test]
+    fn invalid_scripts() {
+        let original = "a\nb\nc\n";
+        for (script, line, error) in [
+            ("2x\n", 1, ParsePatchError::HunkHeader),
+            ("1,2a\nx\n.\n", 1, ParsePatchError::HunkHeader),
+            ("0d\n", 1, ParsePatchError::HunkHeader),
+            ("3d\n3,4d\n", 2, ParsePatchError::Range),
+            ("1d\n3d\n", 2, ParsePatchError::HunksOrder),
+            ("3d\n2a\nx\n", 2, ParsePatchError::UnexpectedEof),
+        ] {
+            let err = parse_ed(script, original).unwrap_err();
+            assert_eq!(err.line(), Some(line), "{script:?}");
+            assert_eq!(
+                std::mem::discriminant(err.kind()),
+                std::mem::discriminant(&error),
+                "{script:?}"
+            );
+        }
+    }
+}
@@ -1,6 +1,8 @@
-use super::{Diff, Hunk, Line, NO_NEWLINE_AT_EOF};
+use super::{Diff, Filename, Hunk, Line, NO_NEWLINE_AT_EOF};
+use crate::{LineEnd, utils, utils::Text};
 use nu_ansi_term::{Color, Style};
 use std::{
+    borrow::Cow,
     fmt::{Display, Formatter, Result},
     io,
 };
@@ -11,6 +13,10 @@ pub struct PatchFormatter {
     with_color: bool,
     with_missing_newline_message: bool,
     suppress_blank_empty: bool,
+    tab_width: Option<usize>,
+    line_numbers: bool,
+    final_newline: bool,
+    line_terminator: LineEnd,
 
     context: Style,
     delete: Style,
@@ -30,6 +36,10 @@ impl PatchFormatter {
             // TODO the default in git-diff and GNU diff is to have this set to false, on the next
             // semver breaking release we should contemplate switching this to be false by default
             suppress_blank_empty: true,
+            tab_width: None,
+            line_numbers: false,
+            final_newline: true,
+            line_terminator: LineEnd::Lf,
 
             context: Style::new(),
             delete: Color::Red.normal(),
@@ -73,6 +83,78 @@ impl PatchFormatter {
         self
     }
 
+    /// Expand tabs in the content of lines to spaces, using tab stops every `width` columns.
+    ///
+    /// This is meant for displaying a patch, e.g. in a terminal where tab stops may differ, and is
+    /// disabled by default. Columns are counted from the start of the line content, after the
+    /// `+`/`-`/` ` prefix, so that lines keep their alignment relative to each other. A formatted
+    /// patch with expanded tabs generally no longer applies to the original content. A width of
+    /// `8` matches the conventional terminal tab stops.
+    pub fn tab_width(mut self, width: usize) -> Self {
+        self.tab_width = Some(width);
+        self
+    }
+
+    /// Prefix every line of a hunk with a gutter containing its line number in the old and new
+    /// file, e.g. `12 13| context`.
+    ///
+    /// This is meant for displaying a patch, e.g. in logs, and is disabled by default. Deleted
+    /// lines only have an old and inserted lines only have a new line number. The output is not a
+    /// valid unified diff anymore and can't be parsed back into a patch.
+    ///
+    /// ```
+    /// use flickzeug::{Diff, PatchFormatter};
+    ///
+    /// let patch = Diff::from_str("@@ -9,2 +9,2 @@\n context\n-old\n+new\n").unwrap();
+    /// let f = PatchFormatter::new().line_numbers(true);
+    /// assert_eq!(
+    ///     f.fmt_patch(&patch).to_string(),
+    ///     "@@ -9,2 +9,2 @@\n 9  9| context\n10   |-old\n   10|+new\n",
+    /// );
+    /// ```
+    pub fn line_numbers(mut self, enable: bool) -> Self {
+        self.line_numbers = enable;
+        self
+    }
+
+    /// Sets whether a formatted patch ends with a newline.
+    ///
+    /// Defaults to `true`, which matches the output of `git diff`. When disabled, the line ending
+    /// of the last line of a patch is left out, which is useful when joining multiple formatted
+    /// patches with a separator. This only affects [`fmt_patch`](Self::fmt_patch) and
+    /// [`write_patch_into`](Self::write_patch_into).
+    ///
+    /// ```
+    /// use flickzeug::{Diff, PatchFormatter};
+    ///
+    /// let patch = Diff::from_str("@@ -1 +1 @@\n-a\n+b\n").unwrap();
+    /// let f = PatchFormatter::new().final_newline(false);
+    /// assert_eq!(f.fmt_patch(&patch).to_string(), "@@ -1 +1 @@\n-a\n+b");
+    /// ```
+    pub fn final_newline(mut self, enable: bool) -> Self {
+        self.final_newline = enable;
+        self
+    }
+
+    /// Sets the line ending used to terminate the lines making up the structure of a patch.
+    ///
+    /// This applies to the file headers, the hunk headers and the "No newline at end of file"
+    /// markers, while the content lines keep their own line endings. Defaults to
+    /// [`LineEnd::Lf`]. Use [`LineEnd::CrLf`] to produce patches for tools which expect Windows
+    /// line endings.
+    ///
+    /// ```
+    /// use flickzeug::{Diff, LineEnd, PatchFormatter};
+    ///
+    /// let patch = Diff::from_str("@@ -1 +1 @@\n-a\r\n+b\r\n").unwrap();
+    /// let f = PatchFormatter::new().line_terminator(LineEnd::CrLf);
+    /// assert_eq!(f.fmt_patch(&patch).to_string(), "@@ -1 +1 @@\r\n-a\r\n+b\r\n");
+    /// ```
+    pub fn line_terminator(mut self, line_terminator: LineEnd) -> Self {
+        self.line_terminator = line_terminator;
+        self
+    }
+
     /// Returns a `Display` impl which can be used to print a Patch
     pub fn fmt_patch<'a>(&'a self, patch: &'a Diff<'a, str>) -> impl Display + 'a {
         PatchDisplay { f: self, patch }
@@ -104,6 +186,19 @@ impl PatchFormatter {
         LineDisplay { f: self, line }
     }
 
+    /// Expands the tabs of a line if a tab width is set
+    fn expand_tabs<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        match self.tab_width {
+            Some(width) => utils::expand_tabs(line, width),
+            None => Cow::Borrowed(line),
+        }
+    }
+
+    /// The line ending of the structural lines of a patch
+    fn terminator(&self) -> &'static str {
+        self.line_terminator.into()
+    }
+
     fn write_line_into<T: AsRef<[u8]> + ?Sized + ToOwned, W: io::Write>(
         &self,
         line: &Line<'_, T>,
@@ -119,26 +214,196 @@ impl Default for PatchFormatter {
     }
 }
 
+/// The formats a [`Diff`] can be written in, see [`Diff::to_patch_bytes`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    /// The unified format of `diff -u` and git, written by [`PatchFormatter`]
+    Unified,
+    /// The normal format of `diff` without options, see [`Diff::to_normal_bytes`]
+    Normal,
+}
+
+/// An error returned when a diff can't be written in a [`PatchFormat`]
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerializeError {
+    /// The diff renames or copies a file, which the format has no file names to record in
+    ///
+    /// Only git patches, i.e. ones with an `index` line or file modes, are taken to rename a file
+    /// when the old and the new path differ, as other patches often use different names for the
+    /// same file.
+    #[error("the {0:?} format can't rename files")]
+    Rename(PatchFormat),
+    /// The diff changes the mode of a file
+    #[error("the {0:?} format can't change file modes")]
+    ModeChange(PatchFormat),
+    /// The diff is for a binary file, see [`Diff::is_binary`]
+    #[error("the {0:?} format can't represent binary files")]
+    Binary(PatchFormat),
+}
+
+impl<T: Text + ToOwned + AsRef<[u8]> + ?Sized> Diff<'_, T> {
+    /// Convert a `Diff` into a patch in `format`
+    ///
+    /// The unified format is written with `formatter`, while the normal format only uses its
+    /// [line terminator](PatchFormatter::line_terminator). Filenames and context lines are left
+    /// out of normal diffs, but renames, mode changes and binary files would be lost and return a
+    /// [`SerializeError`] instead.
+    ///
+    /// ```
+    /// use flickzeug::{PatchFormat, PatchFormatter, create_patch};
+    ///
+    /// let diff = create_patch("a\nb\n", "a\nB\n");
+    /// let f = PatchFormatter::new();
+    /// assert_eq!(diff.to_patch_bytes(PatchFormat::Unified, &f).unwrap(), diff.to_bytes());
+    /// assert_eq!(diff.to_patch_bytes(PatchFormat::Normal, &f).unwrap(), b"2c2\n< b\n---\n> B\n");
+    /// ```
+    pub fn to_patch_bytes(
+        &self,
+        format: PatchFormat,
+        formatter: &PatchFormatter,
+    ) -> std::result::Result<Vec<u8>, SerializeError> {
+        match format {
+            PatchFormat::Unified => {
+                let mut bytes = Vec::new();
+                formatter.write_patch_into(self, &mut bytes).unwrap();
+                Ok(bytes)
+            }
+            PatchFormat::Normal => {
+                self.check_representable(format)?;
+                Ok(self.to_normal_bytes_with_terminator(formatter.line_terminator))
+            }
+        }
+    }
+
+    /// Returns an error if the diff has changes besides its hunks, which `format` can't record
+    fn check_representable(&self, format: PatchFormat) -> std::result::Result<(), SerializeError> {
+        if self.binary {
+            return Err(SerializeError::Binary(format));
+        }
+        let is_git = self.git_index.is_some() || self.old_mode.is_some() || self.new_mode.is_some();
+        if is_git
+            && let (Some(old), Some(new)) = (self.old_path(), self.new_path())
+            && old != new
+        {
+            return Err(SerializeError::Rename(format));
+        }
+        if let (Some(old), Some(new)) = (self.old_mode(), self.new_mode())
+            && old != new
+        {
+            return Err(SerializeError::ModeChange(format));
+        }
+        Ok(())
+    }
+}
+
+impl Diff<'_, str> {
+    /// Convert a `Diff` into a patch in `format`, see [`Diff::to_patch_bytes`]
+    pub fn to_patch_string(
+        &self,
+        format: PatchFormat,
+        formatter: &PatchFormatter,
+    ) -> std::result::Result<String, SerializeError> {
+        let bytes = self.to_patch_bytes(format, formatter)?;
+        Ok(String::from_utf8(bytes).unwrap())
+    }
+}
+
 struct PatchDisplay<'a, T: ToOwned + ?Sized> {
     f: &'a PatchFormatter,
     patch: &'a Diff<'a, T>,
 }
 
+impl<'a, T: ToOwned + ?Sized> PatchDisplay<'a, T> {
+    /// The names written on the `diff --git` line, if the patch has git metadata to write after it
+    fn git_names(&self) -> Option<(&'a Filename<'a, T>, &'a Filename<'a, T>)> {
+        let patch = self.patch;
+        if patch.git_index.is_none() && patch.old_mode.is_none() && patch.new_mode.is_none() {
+            return None;
+        }
+        let original = patch.original.as_ref().or(patch.modified.as_ref())?;
+        let modified = patch.modified.as_ref().or(patch.original.as_ref())?;
+        Some((original, modified))
+    }
+
+    /// The extended header lines of git following the `diff --git` line, without line endings
+    fn git_extended_headers(&self) -> Vec<String> {
+        let patch = self.patch;
+        // A mode change has both modes, while new and deleted files only have one of them
+        let mut headers = match (patch.old_mode, patch.new_mode) {
+            (Some(old), Some(new)) => {
+                vec![format!("old mode {old:06o}"), format!("new mode {new:06o}")]
+            }
+            (None, Some(new)) => vec![format!("new file mode {new:06o}")],
+            (Some(old), None) => vec![format!("deleted file mode {old:06o}")],
+            (None, None) => vec![],
+        };
+        if let Some(index) = &patch.git_index {
+            headers.push(format!("index {index}"));
+        }
+        headers
+    }
+
+    /// Returns `true` if the `---` and `+++` lines are written
+    ///
+    /// Git leaves them out of patches without hunks, like mode changes, where the `diff --git`
+    /// line names the file instead.
+    fn has_file_headers(&self, git_header: bool) -> bool {
+        (self.patch.original.is_some() || self.patch.modified.is_some())
+            && !(git_header && self.patch.hunks.is_empty())
+    }
+}
+
+/// The prefixes written in front of the old and the new name
+///
+/// Git expects the names of all its headers to start with `a/` and `b/`, while other patches
+/// keep the names as they are.
+fn name_prefixes(git_header: bool) -> (Option<&'static str>, Option<&'static str>) {
+    if git_header {
+        (Some("a/"), Some("b/"))
+    } else {
+        (None, None)
+    }
+}
+
 impl<T: ToOwned + AsRef<[u8]> + ?Sized> PatchDisplay<'_, T> {
-    fn write_into<W: io::Write>(&self, mut w: W) -> io::Result<()> {
-        if self.patch.original.is_some() || self.patch.modified.is_some() {
+    fn write_into<W: io::Write>(&self, w: W) -> io::Result<()> {
+        use io::Write as _;
+
+        let mut w = FinalNewline::new(w, self.f.final_newline);
+        if let Some(preamble) = &self.patch.preamble {
+            w.write_all(preamble.as_bytes())?;
+        }
+        let git_names = self.git_names();
+        let file_headers = self.has_file_headers(git_names.is_some());
+        if git_names.is_some() || file_headers {
             if self.f.with_color {
                 write!(w, "{}", self.f.patch_header.prefix())?;
             }
-            if let Some(original) = &self.patch.original {
-                write!(w, "--- ")?;
-                original.write_into(&mut w)?;
-                writeln!(w)?;
+            let (old_prefix, new_prefix) = name_prefixes(git_names.is_some());
+            if let Some((original, modified)) = git_names {
+                write!(w, "diff --git ")?;
+                original.write_into(old_prefix, &mut w)?;
+                write!(w, " ")?;
+                modified.write_into(new_prefix, &mut w)?;
+                write!(w, "{}", self.f.terminator())?;
+                for header in self.git_extended_headers() {
+                    write!(w, "{header}{}", self.f.terminator())?;
+                }
             }
-            if let Some(modified) = &self.patch.modified {
+            // A missing file is written as /dev/null, like created and deleted files are
+            if file_headers {
+                write!(w, "--- ")?;
+                match &self.patch.original {
+                    Some(original) => original.write_into(old_prefix, &mut w)?,
+                    None => write!(w, "/dev/null")?,
+                }
+                write!(w, "{}", self.f.terminator())?;
                 write!(w, "+++ ")?;
-                modified.write_into(&mut w)?;
-                writeln!(w)?;
+                match &self.patch.modified {
+                    Some(modified) => modified.write_into(new_prefix, &mut w)?,
+                    None => write!(w, "/dev/null")?,
+                }
+                write!(w, "{}", self.f.terminator())?;
             }
             if self.f.with_color {
                 write!(w, "{}", self.f.patch_header.suffix())?;
@@ -155,15 +420,43 @@ impl<T: ToOwned + AsRef<[u8]> + ?Sized> PatchDisplay<'_, T> {
 
 impl Display for PatchDisplay<'_, str> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        if self.patch.original.is_some() || self.patch.modified.is_some() {
+        use std::fmt::Write as _;
+
+        let mut f = FinalNewline::new(f, self.f.final_newline);
+        if let Some(preamble) = &self.patch.preamble {
+            f.write_str(preamble)?;
+        }
+        let git_names = self.git_names();
+        let file_headers = self.has_file_headers(git_names.is_some());
+        if git_names.is_some() || file_headers {
             if self.f.with_color {
                 write!(f, "{}", self.f.patch_header.prefix())?;
             }
-            if let Some(original) = &self.patch.original {
-                writeln!(f, "--- {}", original)?;
+            let (old_prefix, new_prefix) = name_prefixes(git_names.is_some());
+            if let Some((original, modified)) = git_names {
+                write!(f, "diff --git ")?;
+                original.fmt_into(old_prefix, &mut f)?;
+                write!(f, " ")?;
+                modified.fmt_into(new_prefix, &mut f)?;
+                write!(f, "{}", self.f.terminator())?;
+                for header in self.git_extended_headers() {
+                    write!(f, "{header}{}", self.f.terminator())?;
+                }
             }
-            if let Some(modified) = &self.patch.modified {
-                writeln!(f, "+++ {}", modified)?;
+            // A missing file is written as /dev/null, like created and deleted files are
+            if file_headers {
+                write!(f, "--- ")?;
+                match &self.patch.original {
+                    Some(original) => original.fmt_into(old_prefix, &mut f)?,
+                    None => write!(f, "/dev/null")?,
+                }
+                write!(f, "{}", self.f.terminator())?;
+                write!(f, "+++ ")?;
+                match &self.patch.modified {
+                    Some(modified) => modified.fmt_into(new_prefix, &mut f)?,
+                    None => write!(f, "/dev/null")?,
+                }
+                write!(f, "{}", self.f.terminator())?;
             }
             if self.f.with_color {
                 write!(f, "{}", self.f.patch_header.suffix())?;
@@ -193,23 +486,24 @@ impl<T: AsRef<[u8]> + ?Sized + ToOwned> HunkDisplay<'_, T> {
             write!(w, "{}", self.f.hunk_header.suffix())?;
         }
 
-        if let Some((ctx, ending)) = self.hunk.function_context {
+        // The line ending of the heading is the one of the header line, which is written below
+        if let Some((ctx, _)) = self.hunk.function_context {
             write!(w, " ")?;
             if self.f.with_color {
                 write!(w, "{}", self.f.function_context.prefix())?;
             }
-            write!(w, " ")?;
             w.write_all(ctx.as_ref())?;
-            if let Some(end) = ending {
-                w.write_all(end.into())?;
-            }
             if self.f.with_color {
                 write!(w, "{}", self.f.function_context.suffix())?;
             }
         }
-        writeln!(w)?;
+        write!(w, "{}", self.f.terminator())?;
 
-        for line in &self.hunk.lines {
+        let gutter = self.f.line_numbers.then(|| Gutter::new(self.hunk));
+        for (line, numbers) in numbered_lines(self.hunk) {
+            if let Some(gutter) = &gutter {
+                write!(w, "{}", gutter.fmt(numbers))?;
+            }
             self.f.write_line_into(line, &mut w)?;
         }
 
@@ -227,23 +521,24 @@ impl Display for HunkDisplay<'_, str> {
             write!(f, "{}", self.f.hunk_header.suffix())?;
         }
 
-        if let Some((ctx, ending)) = self.hunk.function_context {
+        // The line ending of the heading is the one of the header line, which is written below
+        if let Some((ctx, _)) = self.hunk.function_context {
             write!(f, " ")?;
             if self.f.with_color {
                 write!(f, "{}", self.f.function_context.prefix())?;
             }
-            write!(f, " {}", ctx)?;
-            if let Some(end) = ending {
-                let s: &str = end.into();
-                write!(f, "{}", s)?;
-            }
+            write!(f, "{}", ctx)?;
             if self.f.with_color {
                 write!(f, "{}", self.f.function_context.suffix())?;
             }
         }
-        writeln!(f)?;
+        write!(f, "{}", self.f.terminator())?;
 
-        for line in &self.hunk.lines {
+        let gutter = self.f.line_numbers.then(|| Gutter::new(self.hunk));
+        for (line, numbers) in numbered_lines(self.hunk) {
+            if let Some(gutter) = &gutter {
+                write!(f, "{}", gutter.fmt(numbers))?;
+            }
             write!(f, "{}", self.f.fmt_line(line))?;
         }
 
@@ -268,19 +563,17 @@ impl<T: AsRef<[u8]> + ?Sized + ToOwned> LineDisplay<'_, T> {
             write!(w, "{}", style.prefix())?;
         }
 
-        if self.f.suppress_blank_empty
-            && sign == ' '
-            && line.as_ref().is_empty()
-            && ending.is_some()
-        {
-            w.write_all(line.as_ref())?;
+        let line = self.f.expand_tabs(line.as_ref());
+
+        if self.f.suppress_blank_empty && sign == ' ' && line.is_empty() && ending.is_some() {
+            w.write_all(&line)?;
             if let Some(end) = *ending {
                 let e: &[u8] = end.into();
                 w.write_all(e)?;
             }
         } else {
             write!(w, "{}", sign)?;
-            w.write_all(line.as_ref())?;
+            w.write_all(&line)?;
             if let Some(end) = *ending {
                 let e: &[u8] = end.into();
                 w.write_all(e)?;
@@ -292,9 +585,9 @@ impl<T: AsRef<[u8]> + ?Sized + ToOwned> LineDisplay<'_, T> {
         }
 
         if ending.is_none() {
-            writeln!(w)?;
+            write!(w, "{}", self.f.terminator())?;
             if self.f.with_missing_newline_message {
-                writeln!(w, "{}", NO_NEWLINE_AT_EOF)?;
+                write!(w, "{}{}", NO_NEWLINE_AT_EOF, self.f.terminator())?;
             }
         }
 
@@ -314,6 +607,12 @@ impl Display for LineDisplay<'_, str> {
             write!(f, "{}", style.prefix())?;
         }
 
+        let line = match self.f.expand_tabs(line.as_bytes()) {
+            Cow::Borrowed(_) => Cow::Borrowed(*line),
+            // Only tabs have been replaced with spaces, so this is still valid utf8
+            Cow::Owned(expanded) => Cow::Owned(String::from_utf8(expanded).unwrap()),
+        };
+
         if self.f.suppress_blank_empty && sign == ' ' && line.is_empty() && ending.is_some() {
             write!(f, "{}", line)?;
             if let Some(end) = *ending {
@@ -333,12 +632,421 @@ impl Display for LineDisplay<'_, str> {
         }
 
         if ending.is_none() {
-            writeln!(f)?;
+            write!(f, "{}", self.f.terminator())?;
             if self.f.with_missing_newline_message {
-                writeln!(f, "{}", NO_NEWLINE_AT_EOF)?;
+                write!(f, "{}{}", NO_NEWLINE_AT_EOF, self.f.terminator())?;
             }
         }
 
         Ok(())
     }
 }
+
+/// A writer which leaves out the line ending at the very end of the output unless `keep` is set,
+/// see [`PatchFormatter::final_newline`]
+struct FinalNewline<W> {
+    inner: W,
+    keep: bool,
+    /// The line ending at the end of the output written so far, which hasn't been passed on yet
+    pending: &'static str,
+}
+
+impl<W> FinalNewline<W> {
+    fn new(inner: W, keep: bool) -> Self {
+        Self {
+            inner,
+            keep,
+            pending: "",
+        }
+    }
+
+    /// Split `buf` into the part which can be written right away and the line ending to hold back
+    fn split<'b>(&self, buf: &'b [u8]) -> (&'b [u8], &'static str) {
+        if self.keep {
+            (buf, "")
+        } else if let Some(rest) = buf.strip_suffix(b"\r\n") {
+            (rest, "\r\n")
+        } else if let Some(rest) = buf.strip_suffix(b"\n") {
+            (rest, "\n")
+        } else {
+            (buf, "")
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for FinalNewline<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let (content, ending) = self.split(buf);
+        self.inner.write_all(self.pending.as_bytes())?;
+        self.inner.write_all(content)?;
+        self.pending = ending;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: std::fmt::Write> std::fmt::Write for FinalNewline<W> {
+    fn write_str(&mut self, s: &str) -> Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        let (content, ending) = self.split(s.as_bytes());
+        self.inner.write_str(self.pending)?;
+        // Only an ASCII line ending has been split off
+        self.inner.write_str(&s[..content.len()])?;
+        self.pending = ending;
+        Ok(())
+    }
+}
+
+/// Iterate over the lines of a hunk such that each block of changes between context lines lists
+/// all of its deletions before its insertions, regardless of how they are interleaved in the hunk
+fn canonical_order<'a, 'b, T: ?Sized + ToOwned>(
+    lines: &'b [Line<'a, T>],
+) -> impl Iterator<Item = &'b Line<'a, T>> {
+    lines
+        .chunk_by(|a, b| !matches!(a, Line::Context(_)) && !matches!(b, Line::Context(_)))
+        .flat_map(|block| {
+            let deletes = block.iter().filter(|line| !matches!(line, Line::Insert(_)));
+            let inserts = block.iter().filter(|line| matches!(line, Line::Insert(_)));
+            deletes.chain(inserts)
+        })
+}
+
+/// Iterate over the lines of a hunk in canonical order, together with their line numbers in the old
+/// and new file
+fn numbered_lines<'a, 'b, T: ?Sized + ToOwned>(
+    hunk: &'b Hunk<'a, T>,
+) -> impl Iterator<Item = (&'b Line<'a, T>, (Option<usize>, Option<usize>))> {
+    let mut old = hunk.old_range.index() + 1;
+    let mut new = hunk.new_range.index() + 1;
+    canonical_order(&hunk.lines).map(move |line| {
+        let numbers = match line {
+            Line::Context(_) => (Some(old), Some(new)),
+            Line::Delete(_) => (Some(old), None),
+            Line::Insert(_) => (None, Some(new)),
+        };
+        old += numbers.0.is_some() as usize;
+        new += numbers.1.is_some() as usize;
+        (line, numbers)
+    })
+}
+
+/// The line number gutter of a hunk, wide enough for the largest line number in it
+struct Gutter {
+    width: usize,
+}
+
+impl Gutter {
+    fn new<T: ?Sized + ToOwned>(hunk: &Hunk<'_, T>) -> Self {
+        // An empty hunk like `@@ -0,0 +0,0 @@` has no lines to number
+        let last = hunk
+            .old_range
+            .end()
+            .max(hunk.new_range.end())
+            .saturating_sub(1);
+        Self {
+            width: last.to_string().len(),
+        }
+    }
+
+    fn fmt(&self, (old, new): (Option<usize>, Option<usize>)) -> String {
+        let number = |n: Option<usize>| n.map_or_else(String::new, |n| n.to_string());
+        format!(
+            "{:>width$} {:>width$}|",
+            number(old),
+            number(new),
+            width = self.width
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LineEnd, patch::HunkRange};
+
+    #[test]
+    fn to_patch_round_trips() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni";
+        let diff = crate::create_patch(old, new);
+        let f = PatchFormatter::new();
+
+        for format in [PatchFormat::Unified, PatchFormat::Normal] {
+            let patch = diff.to_patch_string(format, &f).unwrap();
+            assert_eq!(patch.as_bytes(), diff.to_patch_bytes(format, &f).unwrap());
+            let parsed = crate::parse_auto(&patch).unwrap();
+            assert_eq!(crate::apply(old, &parsed).unwrap().0, new, "{format:?}");
+        }
+        assert_eq!(
+            diff.to_patch_string(PatchFormat::Unified, &f).unwrap(),
+            diff.to_string()
+        );
+        assert_eq!(
+            diff.to_patch_string(PatchFormat::Normal, &f).unwrap(),
+            diff.to_normal_string()
+        );
+
+        // The formatter's options carry over to both formats
+        let f = PatchFormatter::new().line_terminator(LineEnd::CrLf);
+        assert_eq!(
+            diff.to_patch_bytes(PatchFormat::Normal, &f).unwrap(),
+            diff.to_normal_bytes_with_terminator(LineEnd::CrLf)
+        );
+        let mut bytes = Vec::new();
+        f.write_patch_into(&diff, &mut bytes).unwrap();
+        assert_eq!(
+            diff.to_patch_bytes(PatchFormat::Unified, &f).unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    fn to_patch_rejects_unrepresentable_diffs() {
+        let f = PatchFormatter::new();
+        let renamed = Diff::from_str(
+            "diff --git a/old b/new\nsimilarity index 50%\nrename from old\nrename to new\n\
+             index 1234567..89abcde 100644\n--- a/old\n+++ b/new\n@@ -1 +1 @@\n-a\n+b\n",
+        )
+        .unwrap();
+        let mode_change = Diff::from_str(
+            "diff --git a/f b/f\nold mode 100644\nnew mode 100755\n\
+             --- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n+b\n",
+        )
+        .unwrap();
+        let binary = Diff::from_str(
+            "diff --git a/f b/f\nindex 1234567..89abcde 100644\nBinary files a/f and b/f differ\n",
+        )
+        .unwrap();
+        let cases = [
+            (renamed, SerializeError::Rename(PatchFormat::Normal)),
+            (mode_change, SerializeError::ModeChange(PatchFormat::Normal)),
+            (binary, SerializeError::Binary(PatchFormat::Normal)),
+        ];
+        for (diff, error) in cases {
+            assert_eq!(diff.to_patch_string(PatchFormat::Normal, &f), Err(error));
+            assert_eq!(
+                diff.to_patch_string(PatchFormat::Unified, &f).unwrap(),
+                diff.to_string()
+            );
+        }
+
+        // Created and deleted files only lose their names, like every normal diff
+        let created = Diff::from_str("--- /dev/null\n+++ b/f\n@@ -0,0 +1 @@\n+a\n").unwrap();
+        assert_eq!(
+            created.to_patch_string(PatchFormat::Normal, &f).unwrap(),
+            "0a1\n> a\n"
+        );
+    }
+
+    #[test]
+    fn tab_width() {
+        let patch = Diff::from_str(
+            "\
+@@ -1,2 +1,2 @@
+-\t\tindented
++abc\tindented
+ \u{e9}\tx
+",
+        )
+        .unwrap();
+
+        let expected_4 = "\
+@@ -1,2 +1,2 @@
+-        indented
++abc indented
+ \u{e9}   x
+";
+        let expected_8 = "\
+@@ -1,2 +1,2 @@
+-                indented
++abc     indented
+ \u{e9}       x
+";
+
+        for (width, expected) in [(4, expected_4), (8, expected_8)] {
+            let f = PatchFormatter::new().tab_width(width);
+            assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+
+            let mut bytes = Vec::new();
+            f.write_patch_into(&patch, &mut bytes).unwrap();
+            assert_eq!(bytes, expected.as_bytes());
+        }
+
+        // Tabs are kept as is by default
+        assert!(patch.to_string().contains("-\t\tindented"));
+    }
+
+    #[test]
+    fn interleaved_changes_are_serialized_deletes_first() {
+        let patch = Diff::new(
+            Some("a"),
+            Some("b"),
+            vec![Hunk::new(
+                HunkRange::new(1, 4),
+                HunkRange::new(1, 4),
+                None,
+                vec![
+                    Line::Insert(("one", Some(LineEnd::Lf))),
+                    Line::Delete(("1", Some(LineEnd::Lf))),
+                    Line::Insert(("two", Some(LineEnd::Lf))),
+                    Line::Delete(("2", Some(LineEnd::Lf))),
+                    Line::Context(("3", Some(LineEnd::Lf))),
+                    Line::Delete(("4", Some(LineEnd::Lf))),
+                    Line::Insert(("four", Some(LineEnd::Lf))),
+                ],
+            )],
+        );
+
+        let expected = "\
+--- a
++++ b
+@@ -1,4 +1,4 @@
+-1
+-2
++one
++two
+ 3
+-4
++four
+";
+        assert_eq!(patch.to_string(), expected);
+
+        let mut bytes = Vec::new();
+        PatchFormatter::new()
+            .write_patch_into(&patch, &mut bytes)
+            .unwrap();
+        assert_eq!(bytes, expected.as_bytes());
+
+        // The canonical output parses back into the same lines, in canonical order
+        assert_eq!(Diff::from_str(expected).unwrap().to_string(), expected);
+    }
+
+    #[test]
+    fn line_number_gutter() {
+        let patch = Diff::from_str(
+            "\
+--- a/file
++++ b/file
+@@ -0,0 +1 @@
++first
+@@ -8,4 +9,4 @@
+ 8
+-9
++nine
++9.5
+ 10
+-11
+\\ No newline at end of file
+",
+        )
+        .unwrap();
+
+        let expected = "\
+--- file
++++ file
+@@ -0,0 +1 @@
+  1|+first
+@@ -8,4 +9,4 @@
+ 8  9| 8
+ 9   |-9
+   10|+nine
+   11|+9.5
+10 12| 10
+11   |-11
+\\ No newline at end of file
+";
+
+        let f = PatchFormatter::new().line_numbers(true);
+        assert_eq!(f.fmt_patch(&patch).to_string(), expected);
+
+        let mut bytes = Vec::new();
+        f.write_patch_into(&patch, &mut bytes).unwrap();
+        assert_eq!(bytes, expected.as_bytes());
+
+        // The gutter is only added on request
+        assert!(!patch.to_string().contains('|'));
+
+        let empty = Diff::from_str("@@ -0,0 +0,0 @@\n").unwrap();
+        assert_eq!(f.fmt_patch(&empty).to_string(), "@@ -0,0 +0,0 @@\n");
+    }
+
+    #[test]
+    fn final_newline() {
+        for (patch, without_final_newline) in [
+            (
+                "--- a\n+++ b\n@@ -1 +1 @@\n-a\n+b\n",
+                "--- a\n+++ b\n@@ -1 +1 @@\n-a\n+b",
+            ),
+            (
+                "@@ -1,2 +1,2 @@\n-a\r\n+b\r\n c\r\n",
+                "@@ -1,2 +1,2 @@\n-a\r\n+b\r\n c",
+            ),
+            (
+                "@@ -1 +1 @@\n-a\n+b\n\\ No newline at end of file\n",
+                "@@ -1 +1 @@\n-a\n+b\n\\ No newline at end of file",
+            ),
+        ] {
+            let diff = Diff::from_str(patch).unwrap();
+            for (enable, expected) in [(true, patch), (false, without_final_newline)] {
+                let f = PatchFormatter::new().final_newline(enable);
+                assert_eq!(f.fmt_patch(&diff).to_string(), expected);
+
+                let mut bytes = Vec::new();
+                f.write_patch_into(&diff, &mut bytes).unwrap();
+                assert_eq!(bytes, expected.as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn line_terminator() {
+        let patch = "\
+--- file
++++ file
+@@ -1,2 +1,2 @@ fn main() {
+ a\r
+-b
+\\ No newline at end of file
++B
+\\ No newline at end of file
+";
+        let diff = Diff::from_str(patch).unwrap();
+        assert_eq!(diff.to_string(), patch);
+
+        let f = PatchFormatter::new().line_terminator(LineEnd::CrLf);
+        let crlf = f.fmt_patch(&diff).to_string();
+        assert_eq!(
+            crlf,
+            "--- file\r\n+++ file\r\n@@ -1,2 +1,2 @@ fn main() {\r\n a\r\n-b\r\n\
+             \\ No newline at end of file\r\n+B\r\n\\ No newline at end of file\r\n"
+        );
+        let mut bytes = Vec::new();
+        f.write_patch_into(&diff, &mut bytes).unwrap();
+        assert_eq!(bytes, crlf.as_bytes());
+
+        // The lines are parsed back unchanged, only the hunk header ends with the new terminator
+        let reparsed = Diff::from_str(&crlf).unwrap();
+        assert_eq!(reparsed.original(), diff.original());
+        assert_eq!(reparsed.modified(), diff.modified());
+        let (hunk, reparsed) = (&diff.hunks()[0], &reparsed.hunks()[0]);
+        assert_eq!(reparsed.lines(), hunk.lines());
+        assert_eq!(reparsed.old_range(), hunk.old_range());
+        assert_eq!(
+            reparsed.function_context(),
+            Some(("fn main() {", Some(LineEnd::CrLf)))
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! A flat, owned representation of the lines of a diff
+
+use super::{Diff, Line};
+use crate::{
+    LineEnd,
+    utils::{LineIter, Text},
+};
+
+/// The kind of a [`LineOp`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LineOpKind {
+    /// The line is the same in the old and the new file
+    Equal,
+    /// The line only exists in the new file
+    Insert,
+    /// The line only exists in the old file
+    Delete,
+}
+
+/// A single line of a diff along with its position in the old and the new file, see
+/// [`Diff::into_line_ops`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineOp<T: ?Sized + ToOwned> {
+    /// The 1-based line number in the old file, or `None` for inserted lines
+    pub old_line: Option<usize>,
+    /// The 1-based line number in the new file, or `None` for deleted lines
+    pub new_line: Option<usize>,
+    /// Whether the line is unchanged, inserted or deleted
+    pub kind: LineOpKind,
+    /// The content of the line, without its line ending
+    pub content: T::Owned,
+    /// The line ending of the line, or `None` if it's the last line of a file without a trailing
+    /// newline
+    pub line_end: Option<LineEnd>,
+}
+
+impl<T: Text + ToOwned + ?Sized> Diff<'_, T> {
+    /// Convert the lines of all hunks into a list of [`LineOp`]s, in order
+    ///
+    /// Context lines become [`LineOpKind::Equal`] operations. Lines between hunks are not
+    /// included, see [`Diff::into_line_ops_with_base`] for filling them in.
+    ///
+    /// ```
+    /// use flickzeug::{Diff, LineOpKind};
+    ///
+    /// let diff = Diff::from_str("@@ -2,2 +2,2 @@\n b\n-c\n+C\n").unwrap();
+    /// let ops = diff.into_line_ops();
+    ///
+    /// let ops: Vec<_> = ops
+    ///     .iter()
+    ///     .map(|op| (op.old_line, op.new_line, op.kind, op.content.as_str()))
+    ///     .collect();
+    /// assert_eq!(
+    ///     ops,
+    ///     [
+    ///         (Some(2), Some(2), LineOpKind::Equal, "b"),
+    ///         (Some(3), None, LineOpKind::Delete, "c"),
+    ///         (None, Some(3), LineOpKind::Insert, "C"),
+    ///     ]
+    /// );
+    /// ```
+    pub fn into_line_ops(self) -> Vec<LineOp<T>> {
+        let mut ops = Vec::new();
+        for hunk in &self.hunks {
+            let mut old_line = hunk.old_range.index() + 1;
+            let mut new_line = hunk.new_range.index() + 1;
+            for line in &hunk.lines {
+                ops.push(line_op(*line, &mut old_line, &mut new_line));
+            }
+        }
+        ops
+    }
+
+    /// Convert the diff into a list of [`LineOp`]s covering every line of the old file `old`
+    ///
+    /// This is like [`Diff::into_line_ops`], but the lines of `old` before, between and after the
+    /// hunks are included as [`LineOpKind::Equal`] operations, so the result describes the whole
+    /// old and new file.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let diff = Diff::from_str("@@ -2 +2 @@\n-b\n+B\n").unwrap();
+    /// let ops = diff.into_line_ops_with_base("a\nb\nc\n");
+    /// assert_eq!(ops.len(), 4);
+    /// assert_eq!((ops[3].old_line, ops[3].new_line), (Some(3), Some(3)));
+    /// ```
+    pub fn into_line_ops_with_base(self, old: &T) -> Vec<LineOp<T>> {
+        let old: Vec<_> = LineIter::new(old).collect();
+
+        let mut ops = Vec::new();
+        let mut old_line = 1;
+        let mut new_line = 1;
+        for hunk in &self.hunks {
+            let gap = old
+                .get(old_line - 1..hunk.old_range.index())
+                .unwrap_or_default();
+            for line in gap {
+                ops.push(line_op(Line::Context(*line), &mut old_line, &mut new_line));
+            }
+
+            old_line = hunk.old_range.index() + 1;
+            new_line = hunk.new_range.index() + 1;
+            for line in &hunk.lines {
+                ops.push(line_op(*line, &mut old_line, &mut new_line));
+            }
+        }
+        for line in old.get(old_line - 1..).unwrap_or_default() {
+            ops.push(line_op(Line::Context(*line), &mut old_line, &mut new_line));
+        }
+        ops
+    }
+}
+
+impl<T: Text + ToOwned + ?Sized> From<Diff<'_, T>> for Vec<LineOp<T>> {
+    fn from(diff: Diff<'_, T>) -> Self {
+        diff.into_line_ops()
+    }
+}
+
+/// Convert a line at the given line numbers into a `LineOp`, advancing the line numbers past it
+fn line_op<T: ToOwned + ?Sized>(
+    line: Line<'_, T>,
+    old_line: &mut usize,
+    new_line: &mut usize,
+) -> LineOp<T> {
+    let (kind, (content, line_end)) = match line {
+        Line::Context(line) => (LineOpKind::Equal, line),
+        Line::Insert(line) => (LineOpKind::Insert, line),
+        Line::Delete(line) => (LineOpKind::Delete, line),
+    };
+
+    let old = (kind != LineOpKind::Insert).then(|| {
+        *old_line += 1;
+        *old_line - 1
+    });
+    let new = (kind != LineOpKind::Delete).then(|| {
+        *new_line += 1;
+        *new_line - 1
+    });
+
+    LineOp {
+        old_line: old,
+        new_line: new,
+        kind,
+        content: content.to_owned(),
+        line_end,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineOpKind;
+    use crate::{Diff, LineEnd};
+
+    #[test]
+    fn line_ops_with_base() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8";
+        let diff = Diff::from_str(
+            "\
+@@ -2,2 +2,3 @@
+ 2
++2.5
+ 3
+@@ -6 +6,0 @@
+-6
+",
+        )
+        .unwrap();
+
+        let ops: Vec<_> = diff
+            .clone()
+            .into_line_ops_with_base(old)
+            .into_iter()
+            .map(|op| (op.old_line, op.new_line, op.kind, op.content))
+            .collect();
+        let equal =
+            |old, new, content: &str| (Some(old), Some(new), LineOpKind::Equal, content.to_owned());
+        assert_eq!(
+            ops,
+            [
+                equal(1, 1, "1"),
+                equal(2, 2, "2"),
+                (None, Some(3), LineOpKind::Insert, "2.5".to_owned()),
+                equal(3, 4, "3"),
+                equal(4, 5, "4"),
+                equal(5, 6, "5"),
+                (Some(6), None, LineOpKind::Delete, "6".to_owned()),
+                equal(7, 7, "7"),
+                equal(8, 8, "8"),
+            ]
+        );
+
+        // Only the hunks without a base
+        let ops: Vec<_> = diff.clone().into();
+        assert_eq!(ops.len(), 4);
+        assert_eq!(ops[0].line_end, Some(LineEnd::Lf));
+        assert_eq!(
+            (ops[3].old_line, ops[3].kind),
+            (Some(6), LineOpKind::Delete)
+        );
+
+        // The last line of the base has no line ending
+        let ops = diff.into_line_ops_with_base(old);
+        assert_eq!(ops.last().unwrap().line_end, None);
+    }
+}
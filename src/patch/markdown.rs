@@ -0,0 +1,284 @@
+//! Extract patches embedded in Markdown
+
+use std::borrow::Cow;
+
+/// Extract the contents of all ` ```diff ` and ` ```patch ` fenced code blocks in a Markdown
+/// document, in the order they appear.
+///
+/// Fences follow the CommonMark rules: they are opened by at least three backticks or tildes,
+/// indented by at most three spaces, and closed by a fence of the same character that is at least
+/// as long as the opening one. This means a diff containing a ` ``` ` line can be embedded by
+/// using a longer fence, and code blocks nested inside other fenced blocks (e.g. a Markdown example
+/// inside a ` ````markdown ` block) are not extracted. A block which is never closed extends to the
+/// end of the document. Trailing whitespace on fence lines is ignored.
+///
+/// If the opening fence is indented, e.g. because the block is part of a list item, up to the
+/// same number of spaces is removed from the start of every line of the contents. Only the
+/// contents of such blocks are copied, the others are borrowed from `markdown`.
+///
+/// The returned contents can be passed to [`patch_from_str`](crate::patch_from_str) or
+/// [`Diff::from_str`](crate::Diff::from_str).
+///
+/// ````
+/// use flickzeug::{Diff, extract_fenced_diffs};
+///
+/// let markdown = "\
+/// Apply this fix:
+///
+/// ```diff
+/// --- a/greeting.txt
+/// +++ b/greeting.txt
+/// @@ -1 +1 @@
+/// -hello
+/// +hello world
+/// ```
+/// ";
+///
+/// let diffs = extract_fenced_diffs(markdown);
+/// assert_eq!(diffs.len(), 1);
+/// let patch = Diff::from_str(&diffs[0]).unwrap();
+/// assert_eq!(patch.hunks().len(), 1);
+/// ````
+pub fn extract_fenced_diffs(markdown: &str) -> Vec<Cow<'_, str>> {
+    let mut diffs = Vec::new();
+    // The currently open fence, the offset its content starts at and whether it contains a diff
+    let mut open: Option<(Fence, usize, bool)> = None;
+    let mut offset = 0;
+
+    for line in markdown.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        match open {
+            None => {
+                if let Some((fence, info)) = Fence::parse_opening(line) {
+                    open = Some((fence, offset, is_diff_info_string(info)));
+                }
+            }
+            Some((fence, content_start, is_diff)) => {
+                if fence.is_closed_by(line) {
+                    if is_diff {
+                        diffs.push(fence.content(&markdown[content_start..line_start]));
+                    }
+                    open = None;
+                }
+            }
+        }
+    }
+
+    if let Some((fence, content_start, true)) = open {
+        diffs.push(fence.content(&markdown[content_start..]));
+    }
+
+    diffs
+}
+
+/// Returns `true` if the info string of a fenced code block marks it as a diff
+fn is_diff_info_string(info: &str) -> bool {
+    info.split_whitespace()
+        .next()
+        .is_some_and(|lang| lang.eq_ignore_ascii_case("diff") || lang.eq_ignore_ascii_case("patch"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Fence {
+    marker: u8,
+    len: usize,
+    /// The number of spaces the fence is indented by
+    indent: usize,
+}
+
+impl Fence {
+    /// Parse a fence line into the fence and the (untrimmed) remainder of the line
+    fn parse(line: &str) -> Option<(Self, &str)> {
+        let trimmed = line.trim_start_matches(' ');
+        let indent = line.len() - trimmed.len();
+        if indent > 3 {
+            return None;
+        }
+
+        let marker = *trimmed.as_bytes().first()?;
+        if marker != b'`' && marker != b'~' {
+            return None;
+        }
+        let len = trimmed.bytes().take_while(|&b| b == marker).count();
+        if len < 3 {
+            return None;
+        }
+
+        Some((
+            Self {
+                marker,
+                len,
+                indent,
+            },
+            &trimmed[len..],
+        ))
+    }
+
+    /// Parse an opening fence, returning the fence and its trimmed info string
+    fn parse_opening(line: &str) -> Option<(Self, &str)> {
+        let (fence, info) = Self::parse(line)?;
+        // The info string of a backtick fence may not contain backticks, otherwise this is inline
+        // code rather than a fence
+        if fence.marker == b'`' && info.contains('`') {
+            return None;
+        }
+        Some((fence, info.trim()))
+    }
+
+    fn is_closed_by(&self, line: &str) -> bool {
+        Self::parse(line).is_some_and(|(closing, rest)| {
+            closing.marker == self.marker && closing.len >= self.len && rest.trim().is_empty()
+        })
+    }
+
+    /// Remove up to the indentation of the fence from the start of every line of `content`
+    fn content<'a>(&self, content: &'a str) -> Cow<'a, str> {
+        if self.indent == 0 {
+            return Cow::Borrowed(content);
+        }
+        let lines = content.split_inclusive('\n').map(|line| {
+            let spaces = line.bytes().take(self.indent).take_while(|&b| b == b' ');
+            &line[spaces.count()..]
+        });
+        Cow::Owned(lines.collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Diff;
+
+    #[test]
+    fn multiple_fences() {
+        let markdown = "\
+# Changes
+
+```diff
+@@ -1 +1 @@
+-a
++b
+```
+
+Some code that isn't a diff:
+
+```rust
+fn main() {}
+```
+
+~~~ patch
+--- a/file
++++ b/file
+@@ -1 +1 @@
+-c
++d
+~~~~
+
+```Diff title=\"example\"
+@@ -2 +2 @@
+-e
++f
+```
+";
+
+        let diffs = extract_fenced_diffs(markdown);
+        assert_eq!(
+            diffs,
+            [
+                "@@ -1 +1 @@\n-a\n+b\n",
+                "--- a/file\n+++ b/file\n@@ -1 +1 @@\n-c\n+d\n",
+                "@@ -2 +2 @@\n-e\n+f\n",
+            ]
+        );
+        for diff in &diffs {
+            Diff::from_str(diff).unwrap();
+        }
+    }
+
+    #[test]
+    fn nested_fences() {
+        let markdown = "\
+````diff
+@@ -1,3 +1,3 @@
+ ```
+-old
++new
+ ```
+````
+
+````markdown
+```diff
+@@ -1 +1 @@
+-not extracted
++since it's nested
+```
+````
+";
+
+        assert_eq!(
+            extract_fenced_diffs(markdown),
+            ["@@ -1,3 +1,3 @@\n ```\n-old\n+new\n ```\n"]
+        );
+    }
+
+    #[test]
+    fn fence_in_list_item() {
+        let markdown = "\
+1. Apply this fix:
+
+   ```diff
+   --- a/main.c
+   +++ b/main.c
+   @@ -1,3 +1,3 @@
+    int main() {
+   -    return 1;
+   +    return 0;
+    }
+   ```
+2. Rebuild
+";
+
+        let diffs = extract_fenced_diffs(markdown);
+        assert_eq!(
+            diffs,
+            [
+                "--- a/main.c\n+++ b/main.c\n@@ -1,3 +1,3 @@\n int main() {\n-    return 1;\n+    return 0;\n }\n"
+            ]
+        );
+        let diff = Diff::from_str(&diffs[0]).unwrap();
+        let (patched, _) = crate::apply("int main() {\n    return 1;\n}\n", &diff).unwrap();
+        assert_eq!(patched, "int main() {\n    return 0;\n}\n");
+    }
+
+    #[test]
+    fn fence_edge_cases() {
+        // Closing fences may not have an info string and must use the same marker
+        let markdown = "```diff\n-a\n``` diff\n~~~\n+b\n```\n";
+        assert_eq!(extract_fenced_diffs(markdown), ["-a\n``` diff\n~~~\n+b\n"]);
+
+        // Trailing whitespace on fence lines is ignored
+        assert_eq!(
+            extract_fenced_diffs("```diff \t\n-a\n```  \t\r\n"),
+            ["-a\n"]
+        );
+
+        // Fences indented by four spaces are indented code blocks, not fences
+        assert!(extract_fenced_diffs("    ```diff\n-a\n    ```\n").is_empty());
+
+        // Unclosed fences extend to the end of the document
+        assert_eq!(extract_fenced_diffs("```diff\r\n-a\r\n+b"), ["-a\r\n+b"]);
+
+        // The indentation of the opening fence is removed from the contents, even if the closing
+        // fence is indented differently
+        assert_eq!(
+            extract_fenced_diffs("  ```diff\n    -a\n +b\nc\n```\n"),
+            ["  -a\n+b\nc\n"]
+        );
+
+        // Empty blocks
+        assert_eq!(extract_fenced_diffs("```diff\n```"), [""]);
+        assert!(extract_fenced_diffs("no fences here\n").is_empty());
+    }
+}
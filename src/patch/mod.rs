@@ -1,23 +1,48 @@
+mod builder;
+mod compose;
+mod detect;
+mod ed;
 mod format;
+mod line_ops;
+mod markdown;
+mod normal;
 mod parse;
-
-pub use format::PatchFormatter;
-pub use parse::{HunkRangeStrategy, ParsePatchError, ParserConfig};
+mod parse_context;
+#[cfg(feature = "similar-compat")]
+mod similar_compat;
+
+pub use builder::DiffBuilder;
+pub use detect::{parse_auto, parse_auto_bytes};
+pub use ed::{parse_ed, parse_ed_bytes};
+pub use format::{PatchFormat, PatchFormatter, SerializeError};
+pub use line_ops::{LineOp, LineOpKind};
+pub use markdown::extract_fenced_diffs;
+pub use normal::{
+    diff_normal, diff_normal_bytes, is_normal_diff, parse_normal, parse_normal_bytes,
+    parse_normal_bytes_lenient, parse_normal_lenient,
+};
+pub use parse::{HunkRangeStrategy, ParsePatchError, ParserConfig, is_unified_diff};
+pub use parse_context::{is_context_diff, parse_context, parse_context_bytes};
 
 use std::{
-    borrow::Cow,
+    borrow::{Borrow, Cow},
     fmt::{self, Debug},
+    hash::{DefaultHasher, Hash, Hasher},
     ops,
+    path::PathBuf,
 };
 
-use crate::{LineEnd, utils::Text};
+use crate::{
+    ApplyError, LineEnd, MatchOptions,
+    utils::{LineIter, Text},
+};
 
 const NO_NEWLINE_AT_EOF: &str = "\\ No newline at end of file";
 
 pub type Patch<'a, T> = Vec<Diff<'a, T>>;
 
 /// Representation of all the differences between two files
-#[derive(Clone, PartialEq, PartialOrd, Ord, Eq)]
+#[derive(PartialEq, PartialOrd, Ord, Eq)]
 pub struct Diff<'a, T: ToOwned + ?Sized> {
     // TODO GNU patch is able to parse patches without filename headers.
     // This should be changed to an `Option` type to reflect this instead of setting this to ""
@@ -25,6 +50,33 @@ pub struct Diff<'a, T: ToOwned + ?Sized> {
     original: Option<Filename<'a, T>>,
     modified: Option<Filename<'a, T>>,
     hunks: Vec<Hunk<'a, T>>,
+    requires_verification: bool,
+    preamble: Option<String>,
+    binary: bool,
+    git_index: Option<String>,
+    old_mode: Option<u32>,
+    new_mode: Option<u32>,
+    // Whether the hunks were parsed with `ParserConfig::skip_order_check`, so they may be out of
+    // order or overlap by their headers
+    skip_order_check: bool,
+}
+
+// Implemented manually since deriving would require `T: Clone`, which unsized types can't satisfy
+impl<T: ToOwned + ?Sized> Clone for Diff<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            hunks: self.hunks.clone(),
+            requires_verification: self.requires_verification,
+            preamble: self.preamble.clone(),
+            binary: self.binary,
+            git_index: self.git_index.clone(),
+            old_mode: self.old_mode,
+            new_mode: self.new_mode,
+            skip_order_check: self.skip_order_check,
+        }
+    }
 }
 
 impl<'a, T: Text + ToOwned + ?Sized> Diff<'a, T> {
@@ -37,12 +89,19 @@ impl<'a, T: Text + ToOwned + ?Sized> Diff<'a, T> {
         O: Into<Cow<'a, T>>,
         M: Into<Cow<'a, T>>,
     {
-        let original = original.map(|o| Filename(o.into()));
-        let modified = modified.map(|m| Filename(m.into()));
+        let original = original.map(|o| Filename(o.into(), false));
+        let modified = modified.map(|m| Filename(m.into(), false));
         Self {
             original,
             modified,
             hunks,
+            requires_verification: false,
+            preamble: None,
+            binary: false,
+            git_index: None,
+            old_mode: None,
+            new_mode: None,
+            skip_order_check: false,
         }
     }
 
@@ -60,19 +119,746 @@ impl<'a, T: Text + ToOwned + ?Sized> Diff<'a, T> {
         self.modified.as_ref().map(AsRef::as_ref)
     }
 
+    /// Return the path of the old file, without the `a/` prefix
+    ///
+    /// For git patches this is the source of a rename or copy if there is one. The prefix is only
+    /// removed if it was kept when parsing, see [`ParserConfig::strip_ab_prefix`], so a directory
+    /// named `a` at the start of the path is left alone.
+    pub fn old_path(&self) -> Option<&T> {
+        self.original.as_ref().map(Filename::path)
+    }
+
+    /// Return the path of the new file, without the `b/` prefix
+    ///
+    /// For git patches this is the destination of a rename or copy if there is one. Like
+    /// [`Diff::old_path`], the prefix is only removed if it was kept when parsing.
+    pub fn new_path(&self) -> Option<&T> {
+        self.modified.as_ref().map(Filename::path)
+    }
+
+    /// Removes the first `n` slash-separated components from the names of both files, like the
+    /// `-p` option of `patch`
+    ///
+    /// With `n` set to `0` the names are left unchanged. Names with `n` components or less are
+    /// reduced to their last component. Note that the `a/` and `b/` prefixes of git are already
+    /// removed when parsing, unless [`ParserConfig::strip_ab_prefix`] is disabled.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let patch = "--- old/src/lib.rs\n+++ new/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\n";
+    /// let mut diff = Diff::from_str(patch).unwrap();
+    /// diff.strip_path_prefix(1);
+    /// assert_eq!(diff.original(), Some("src/lib.rs"));
+    /// assert_eq!(diff.modified(), Some("src/lib.rs"));
+    /// ```
+    pub fn strip_path_prefix(&mut self, n: usize) {
+        for filename in [&mut self.original, &mut self.modified]
+            .into_iter()
+            .flatten()
+        {
+            filename.0 = match &filename.0 {
+                Cow::Borrowed(path) => Cow::Borrowed(strip_path_components(path, n)),
+                Cow::Owned(path) => Cow::Owned(strip_path_components(path.borrow(), n).to_owned()),
+            };
+            filename.1 &= n == 0;
+        }
+    }
+
+    /// Returns the text preceding the file headers of the patch, if any
+    ///
+    /// This is e.g. the commit message of a patch created by `git format-patch`, or a comment
+    /// describing the patch. Parsing a patch keeps everything before its first `diff --git` line,
+    /// file header or hunk here, and formatting it writes the preamble back verbatim in front of
+    /// the headers.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let patch = "# Fix the greeting\n--- f\n+++ f\n@@ -1 +1 @@\n-hello\n+Hello\n";
+    /// let diff = Diff::from_str(patch).unwrap();
+    /// assert_eq!(diff.preamble(), Some("# Fix the greeting\n"));
+    /// assert_eq!(diff.to_string(), patch);
+    /// ```
+    pub fn preamble(&self) -> Option<&str> {
+        self.preamble.as_deref()
+    }
+
+    /// Sets the text written in front of the file headers when formatting the patch, see
+    /// [`Diff::preamble`]
+    ///
+    /// The preamble is written as is, so it should end with a line ending.
+    pub fn with_preamble(mut self, preamble: impl Into<String>) -> Self {
+        self.preamble = Some(preamble.into());
+        self
+    }
+
+    /// Returns `true` if the patch is for a binary file
+    ///
+    /// Binary files are recognized from the `Binary files a/x and b/x differ` line written by git
+    /// and GNU diff and from `GIT binary patch` sections. Their changes aren't parsed, so the patch
+    /// has no hunks and applying it fails with [`ApplyError::BinaryNotSupported`].
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    /// Returns the value of the `index` line of a git patch, like `1234567..89abcde 100644`
+    ///
+    /// The line names the blobs of the file before and after the change, which git uses to fall
+    /// back to a three-way merge, and is written back when formatting the patch.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let patch = "\
+    /// diff --git a/f b/f
+    /// index 1234567..89abcde 100644
+    /// --- a/f
+    /// +++ b/f
+    /// @@ -1 +1 @@
+    /// -a
+    /// +b
+    /// ";
+    /// let diff = Diff::from_str(patch).unwrap();
+    /// assert_eq!(diff.git_index(), Some("1234567..89abcde 100644"));
+    /// assert_eq!((diff.old_mode(), diff.new_mode()), (Some(0o100644), Some(0o100644)));
+    /// ```
+    pub fn git_index(&self) -> Option<&str> {
+        self.git_index.as_deref()
+    }
+
+    /// Returns the mode of the old file of a git patch, like `0o100644`
+    ///
+    /// This is taken from the `old mode` or `deleted file mode` line, or the `index` line if the
+    /// mode doesn't change.
+    pub fn old_mode(&self) -> Option<u32> {
+        self.old_mode.or_else(|| self.index_mode())
+    }
+
+    /// Returns the mode of the new file of a git patch, like `0o100755`
+    ///
+    /// This is taken from the `new mode` or `new file mode` line, or the `index` line if the mode
+    /// doesn't change.
+    pub fn new_mode(&self) -> Option<u32> {
+        self.new_mode.or_else(|| self.index_mode())
+    }
+
+    /// The mode of an unchanged file, which git adds to the end of the `index` line
+    fn index_mode(&self) -> Option<u32> {
+        let (_, mode) = self.git_index.as_deref()?.split_once(' ')?;
+        parse_mode(mode)
+    }
+
     /// Returns the hunks in the patch
-    pub fn hunks(&self) -> &[Hunk<'_, T>] {
+    pub fn hunks(&self) -> &[Hunk<'a, T>] {
         &self.hunks
     }
 
+    /// Returns the hunks in the patch for adjusting them, e.g. with [`Hunk::set_old_range`].
+    ///
+    /// Callers need to make sure the hunks stay sorted and don't overlap.
+    pub fn hunks_mut(&mut self) -> &mut [Hunk<'a, T>] {
+        &mut self.hunks
+    }
+
+    /// Moves the old and new ranges of every hunk by `delta` lines, see [`HunkRange::shift`]
+    ///
+    /// This turns a diff of a part of a file, starting after its first `delta` lines, into a diff
+    /// of the whole file. Hunks moved past the start of the file by a negative `delta` all end up
+    /// at its first line, so callers need to make sure they don't overlap afterwards.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let mut diff = Diff::from_str("@@ -1,2 +1,2 @@\n a\n-b\n+B\n").unwrap();
+    /// diff.shift_all(10);
+    /// assert_eq!(diff.to_string(), "@@ -11,2 +11,2 @@\n a\n-b\n+B\n");
+    /// ```
+    pub fn shift_all(&mut self, delta: isize) {
+        for hunk in &mut self.hunks {
+            hunk.old_range.shift(delta);
+            hunk.new_range.shift(delta);
+        }
+    }
+
+    /// Returns the lines of all hunks in the patch, in order
+    ///
+    /// ```
+    /// use flickzeug::{Diff, Line};
+    ///
+    /// let diff = Diff::from_str("@@ -1 +1 @@\n-a\n+b\n@@ -5 +5,0 @@\n-e\n").unwrap();
+    /// assert_eq!(diff.lines().count(), 3);
+    /// assert_eq!(diff.inserted_lines().count(), 1);
+    /// assert!(diff.deleted_lines().all(|line| matches!(line, Line::Delete(_))));
+    /// ```
+    pub fn lines(&self) -> impl Iterator<Item = &Line<'a, T>> {
+        self.hunks.iter().flat_map(Hunk::lines)
+    }
+
+    /// Returns the inserted lines of all hunks in the patch, in order
+    pub fn inserted_lines(&self) -> impl Iterator<Item = &Line<'a, T>> {
+        self.lines().filter(|line| line.is_insert())
+    }
+
+    /// Returns the deleted lines of all hunks in the patch, in order
+    pub fn deleted_lines(&self) -> impl Iterator<Item = &Line<'a, T>> {
+        self.lines().filter(|line| line.is_delete())
+    }
+
+    /// Returns the number of hunks in the patch
+    pub fn hunk_count(&self) -> usize {
+        self.hunks.len()
+    }
+
+    /// Returns the number of lines inserted by all hunks in the patch
+    ///
+    /// Like [`Diff::stats`] this only needs the patch, not the text it applies to.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let diff = Diff::from_str("@@ -1,2 +1 @@\n-a\n-b\n+c\n@@ -5 +4,0 @@\n-e\n").unwrap();
+    /// assert_eq!(diff.hunk_count(), 2);
+    /// assert_eq!(diff.added_line_count(), 1);
+    /// assert_eq!(diff.deleted_line_count(), 3);
+    /// ```
+    pub fn added_line_count(&self) -> usize {
+        self.inserted_lines().count()
+    }
+
+    /// Returns the number of lines deleted by all hunks in the patch, see
+    /// [`Diff::added_line_count`]
+    pub fn deleted_line_count(&self) -> usize {
+        self.deleted_lines().count()
+    }
+
+    /// Returns `true` if the patch doesn't change the contents of the file, as it has no hunks or
+    /// its hunks only have context lines
+    ///
+    /// Applying an empty diff returns the text unchanged, so it can be skipped. Binary diffs are
+    /// never empty, as their changes aren't parsed into hunks.
+    ///
+    /// ```
+    /// use flickzeug::{Diff, create_patch};
+    ///
+    /// assert!(create_patch("a\n", "a\n").is_empty());
+    /// assert!(Diff::from_str("@@ -1 +1 @@\n a\n").unwrap().is_empty());
+    /// assert!(!Diff::from_str("@@ -1 +1 @@\n-a\n+b\n").unwrap().is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        !self.binary && self.lines().all(Line::is_context)
+    }
+
+    /// Returns a diff which undoes this one, turning the modified text back into the original
+    ///
+    /// Inserted and deleted lines are swapped, as are the old and new ranges of every hunk and the
+    /// filenames and file modes. The preamble and the git index line are dropped, as they
+    /// describe the original change. See also [`apply_reverse`](crate::apply_reverse).
     pub fn reverse(&self) -> Diff<'_, T> {
         let hunks = self.hunks.iter().map(Hunk::reverse).collect();
         Diff {
             original: self.modified.clone(),
             modified: self.original.clone(),
             hunks,
+            requires_verification: self.requires_verification,
+            preamble: None,
+            binary: self.binary,
+            git_index: None,
+            old_mode: self.new_mode,
+            new_mode: self.old_mode,
+            skip_order_check: self.skip_order_check,
         }
     }
+
+    /// Returns a copy of this diff with its hunks replaced by `hunks`
+    pub(crate) fn with_hunks(&self, hunks: Vec<Hunk<'a, T>>) -> Self {
+        Self {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            hunks,
+            requires_verification: self.requires_verification,
+            preamble: self.preamble.clone(),
+            binary: self.binary,
+            git_index: self.git_index.clone(),
+            old_mode: self.old_mode,
+            new_mode: self.new_mode,
+            skip_order_check: self.skip_order_check,
+        }
+    }
+
+    /// Returns `true` if the hunks were parsed without checking their order, see
+    /// [`ParserConfig::skip_order_check`]
+    pub(crate) fn skip_order_check(&self) -> bool {
+        self.skip_order_check
+    }
+
+    /// Returns `true` if the context of this diff must match exactly when applying it, see
+    /// [`Diff::harden`].
+    pub fn requires_verification(&self) -> bool {
+        self.requires_verification
+    }
+
+    /// Returns a copy of this diff with up to `context` lines of context from `old` added around
+    /// every hunk, which must match exactly when applying the returned diff.
+    ///
+    /// Diffs with little or no context, like ones with their context trimmed or converted from
+    /// formats without context, apply purely by line number and can silently be applied at the
+    /// wrong position. Hardening a diff against the file it was created for allows detecting this:
+    /// applying a hardened diff disables fuzzy matching and relaxed [`MatchOptions`], so it fails
+    /// unless the surrounding lines are unchanged. Hunks whose context overlaps are merged.
+    ///
+    /// Every hunk must match `old` at the position given by its header, otherwise an
+    /// [`ApplyError::ContextMismatch`] is returned. The verification requirement is not part of
+    /// the formatted diff.
+    ///
+    /// [`MatchOptions`]: crate::MatchOptions
+    pub fn harden(&self, old: &'a T, context: usize) -> Result<Diff<'a, T>, ApplyError>
+    where
+        T: AsRef<[u8]>,
+    {
+        let old: Vec<_> = LineIter::new(old).collect();
+
+        // Verify every hunk against `old` before adding any context
+        let mut prev_end = 0;
+        for (hunk_index, hunk) in self.hunks.iter().enumerate() {
+            let start = hunk.old_range.index();
+            let pre_image = hunk.lines.iter().filter_map(|line| match line {
+                Line::Context(line) | Line::Delete(line) => Some(line),
+                Line::Insert(_) => None,
+            });
+            // Hunks also have to be in order for the context between them to be well defined
+            let matches = start >= prev_end
+                && old
+                    .get(start..start + hunk.old_range.len)
+                    .is_some_and(|old| pre_image.eq(old.iter()));
+            prev_end = start + hunk.old_range.len;
+            if !matches {
+                let target = old.get(start..).unwrap_or_default().iter();
+                return Err(ApplyError::context_mismatch(
+                    hunk_index,
+                    hunk,
+                    target.map(|(line, _)| *line),
+                    &MatchOptions::default(),
+                ));
+            }
+        }
+
+        let mut hunks: Vec<Hunk<'a, T>> = Vec::with_capacity(self.hunks.len());
+        // The end of the old side of the last hunk in `hunks`, excluding its added trailing context
+        let mut last_end = 0;
+        for hunk in &self.hunks {
+            let start = hunk.old_range.index();
+            let end = start + hunk.old_range.len;
+
+            match hunks.last_mut() {
+                // Merge hunks which are close enough for their context to overlap
+                Some(last) if start.saturating_sub(context) <= last_end + context => {
+                    let between = old[last_end..start].iter().copied().map(Line::Context);
+                    last.lines.extend(between);
+                    last.lines.extend(hunk.lines.iter().copied());
+                }
+                _ => {
+                    if let Some(last) = hunks.last_mut() {
+                        add_trailing_context(last, &old, last_end, context);
+                    }
+                    let leading_start = start.saturating_sub(context).max(last_end);
+                    let mut lines: Vec<_> = old[leading_start..start]
+                        .iter()
+                        .copied()
+                        .map(Line::Context)
+                        .collect();
+                    lines.extend(hunk.lines.iter().copied());
+                    let new_start = hunk.new_range.index().saturating_sub(start - leading_start);
+                    hunks.push(Hunk::new(
+                        HunkRange::from_index(leading_start, 0),
+                        HunkRange::from_index(new_start, 0),
+                        hunk.function_context,
+                        lines,
+                    ));
+                }
+            }
+            last_end = end;
+        }
+        if let Some(last) = hunks.last_mut() {
+            add_trailing_context(last, &old, last_end, context);
+        }
+
+        // Fix up the ranges now that the lines are known
+        for hunk in &mut hunks {
+            let (old_len, new_len) = hunk_lines_count(&hunk.lines);
+            hunk.old_range = HunkRange::from_index(hunk.old_range.index(), old_len);
+            hunk.new_range = HunkRange::from_index(hunk.new_range.index(), new_len);
+        }
+
+        Ok(Diff {
+            original: self.original.clone(),
+            modified: self.modified.clone(),
+            hunks,
+            requires_verification: true,
+            preamble: self.preamble.clone(),
+            binary: self.binary,
+            git_index: self.git_index.clone(),
+            old_mode: self.old_mode,
+            new_mode: self.new_mode,
+            skip_order_check: self.skip_order_check,
+        })
+    }
+
+    /// Merge hunks which are separated by at most `max_gap` unchanged lines into a single hunk,
+    /// filling the gap between them with context lines read from `old`.
+    ///
+    /// This is useful to clean up diffs assembled from several sources, or after moving hunks
+    /// around, where hunks may end up next to each other. Hunks which overlap are merged as well if
+    /// the overlapping lines are identical context lines in both hunks. Hunks are left as is if
+    /// `old` doesn't contain the lines between them.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let old = "a\nb\nc\nd\n";
+    /// let mut patch = Diff::from_str("@@ -1 +1 @@\n-a\n+A\n@@ -4 +4 @@\n-d\n+D\n").unwrap();
+    /// patch.coalesce_hunks(old, 2);
+    /// assert_eq!(
+    ///     patch.to_string(),
+    ///     "@@ -1,4 +1,4 @@\n-a\n+A\n b\n c\n-d\n+D\n",
+    /// );
+    /// ```
+    pub fn coalesce_hunks(&mut self, old: &'a T, max_gap: usize) {
+        let old: Vec<_> = LineIter::new(old).collect();
+
+        let mut hunks: Vec<Hunk<'a, T>> = Vec::with_capacity(self.hunks.len());
+        for hunk in self.hunks.drain(..) {
+            if let Some(last) = hunks.last_mut() {
+                let last_start = last.old_range.index();
+                let last_end = last_start + last.old_range.len;
+                let start = hunk.old_range.index();
+
+                if start >= last_end {
+                    let gap = old.get(last_end..start).filter(|gap| gap.len() <= max_gap);
+                    if let Some(gap) = gap {
+                        last.lines.extend(gap.iter().copied().map(Line::Context));
+                        last.lines.extend(hunk.lines);
+                        last.recount();
+                        continue;
+                    }
+                } else if start >= last_start && last_end - start <= hunk.old_range.len {
+                    let overlap = last_end - start;
+                    let trailing = last
+                        .lines
+                        .len()
+                        .checked_sub(overlap)
+                        .map(|i| &last.lines[i..]);
+                    let leading = hunk.lines.get(..overlap);
+                    let is_context = |lines: &[Line<'a, T>]| {
+                        lines.iter().all(|line| matches!(line, Line::Context(_)))
+                    };
+                    if trailing.is_some_and(is_context) && trailing == leading {
+                        last.lines.extend(hunk.lines.into_iter().skip(overlap));
+                        last.recount();
+                        continue;
+                    }
+                }
+            }
+            hunks.push(hunk);
+        }
+        self.hunks = hunks;
+    }
+
+    /// Returns a copy of this diff restricted to the lines of the old file in `range`, given as
+    /// 1-based line numbers like [`HunkRange::range`].
+    ///
+    /// Hunks outside of the range are dropped, and hunks partially inside of it are trimmed to the
+    /// context and deleted lines within the range. Inserted lines belong to the line they follow,
+    /// or to the first line of the file if they are inserted at its start. When a block of changes
+    /// crosses the boundary of the range, its inserted lines are kept if any of its deleted lines
+    /// are. Hunks without changes after trimming are dropped, and the new ranges are recomputed to
+    /// account for dropped changes.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let diff = Diff::from_str("@@ -1,4 +1,4 @@\n-a\n+A\n b\n c\n-d\n+D\n").unwrap();
+    /// assert_eq!(diff.clamp_to_range(2..5).to_string(), "@@ -2,3 +2,3 @@\n b\n c\n-d\n+D\n");
+    /// ```
+    pub fn clamp_to_range(&self, range: ops::Range<usize>) -> Diff<'a, T> {
+        let is_context = |line: &Line<'a, T>| matches!(line, Line::Context(_));
+
+        // The difference between the number of lines in the new and the old file before the
+        // current hunk, counting only kept changes
+        let mut delta = 0isize;
+        let mut hunks = Vec::new();
+        for hunk in &self.hunks {
+            let mut old_line = hunk.old_range.index() + 1;
+            let mut start = None;
+            let mut lines = Vec::new();
+
+            for block in hunk.lines.chunk_by(|a, b| !is_context(a) && !is_context(b)) {
+                let old_len = block
+                    .iter()
+                    .filter(|l| !matches!(l, Line::Insert(_)))
+                    .count();
+                let keep_inserted = if old_len > 0 {
+                    (old_line..old_line + old_len).any(|n| range.contains(&n))
+                } else {
+                    range.contains(&old_line.saturating_sub(1).max(1))
+                };
+
+                for line in block {
+                    let keep = match line {
+                        Line::Context(_) | Line::Delete(_) => range.contains(&old_line),
+                        Line::Insert(_) => keep_inserted,
+                    };
+                    if keep {
+                        start.get_or_insert(old_line - 1);
+                        lines.push(*line);
+                    }
+                    if !matches!(line, Line::Insert(_)) {
+                        old_line += 1;
+                    }
+                }
+            }
+
+            let Some(start) = start.filter(|_| !lines.iter().all(is_context)) else {
+                continue;
+            };
+            let (old_len, new_len) = hunk_lines_count(&lines);
+            hunks.push(Hunk::new(
+                HunkRange::from_index(start, old_len),
+                HunkRange::from_index(start.saturating_add_signed(delta), new_len),
+                hunk.function_context,
+                lines,
+            ));
+            delta += new_len as isize - old_len as isize;
+        }
+
+        self.with_hunks(hunks)
+    }
+
+    /// Returns a copy of this diff with at most `context` lines of context around every change.
+    ///
+    /// Context lines further away from changes are dropped, and hunks are split where there are
+    /// more than `2 * context` context lines between two changes. A split hunk keeps its section
+    /// heading on its first part only. Hunks without changes are dropped.
+    ///
+    /// Applying a diff with less context is less reliable, as there are fewer lines to verify
+    /// that a hunk is applied at the right position.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let diff = Diff::from_str("@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n-d\n+D\n e\n").unwrap();
+    /// assert_eq!(
+    ///     diff.trim_context(0).to_string(),
+    ///     "@@ -2 +2 @@\n-b\n+B\n@@ -4 +4 @@\n-d\n+D\n",
+    /// );
+    /// ```
+    pub fn trim_context(&self, context: usize) -> Diff<'a, T> {
+        let is_context = |line: &Line<'a, T>| matches!(line, Line::Context(_));
+
+        let mut hunks = Vec::new();
+        for hunk in &self.hunks {
+            let blocks: Vec<_> = hunk
+                .lines
+                .chunk_by(|a, b| is_context(a) == is_context(b))
+                .collect();
+            let mut old_index = hunk.old_range.index();
+            let mut new_index = hunk.new_range.index();
+            // The start of the current part in the old and new file, and its lines
+            let mut part: Option<(usize, usize, Vec<Line<'a, T>>)> = None;
+            let mut function_context = hunk.function_context;
+
+            for (i, block) in blocks.iter().enumerate() {
+                let (old_len, new_len) = hunk_lines_count(block);
+                if !is_context(&block[0]) {
+                    let (_, _, lines) = part.get_or_insert((old_index, new_index, Vec::new()));
+                    lines.extend_from_slice(block);
+                } else {
+                    let is_last = i + 1 == blocks.len();
+                    // Trailing context of the current part
+                    let trailing = if is_last || block.len() > 2 * context {
+                        context.min(block.len())
+                    } else {
+                        block.len()
+                    };
+                    if let Some((old_start, new_start, mut lines)) = part.take() {
+                        lines.extend_from_slice(&block[..trailing]);
+                        if trailing == block.len() && !is_last {
+                            part = Some((old_start, new_start, lines));
+                        } else {
+                            let (old_count, new_count) = hunk_lines_count(&lines);
+                            hunks.push(Hunk::new(
+                                HunkRange::from_index(old_start, old_count),
+                                HunkRange::from_index(new_start, new_count),
+                                function_context.take(),
+                                lines,
+                            ));
+                        }
+                    }
+                    // Leading context of the next part
+                    if part.is_none() && !is_last {
+                        let leading = context.min(block.len());
+                        let skipped = block.len() - leading;
+                        part = Some((
+                            old_index + skipped,
+                            new_index + skipped,
+                            block[skipped..].to_vec(),
+                        ));
+                    }
+                }
+                old_index += old_len;
+                new_index += new_len;
+            }
+
+            if let Some((old_start, new_start, lines)) = part {
+                let (old_count, new_count) = hunk_lines_count(&lines);
+                hunks.push(Hunk::new(
+                    HunkRange::from_index(old_start, old_count),
+                    HunkRange::from_index(new_start, new_count),
+                    function_context,
+                    lines,
+                ));
+            }
+        }
+
+        self.with_hunks(hunks)
+    }
+
+    /// Convert a `Patch` into the smallest unified diff applying the same changes
+    ///
+    /// The hunks of the result have no context lines (see [`Diff::trim_context`]) and no section
+    /// headings, like `diff -U0` output. Without context, hunks are only found at their exact
+    /// position or where their deleted lines appear, so such patches should only be applied to
+    /// the exact file they were created against.
+    ///
+    /// ```
+    /// use flickzeug::{Diff, apply_bytes};
+    ///
+    /// let base = b"a\nb\nc\nd\n";
+    /// let diff = Diff::from_str("@@ -1,4 +1,4 @@\n a\n b\n-c\n+C\n d\n").unwrap();
+    ///
+    /// let minimal = diff.to_minimal_unified();
+    /// assert_eq!(minimal, b"@@ -3 +3 @@\n-c\n+C\n");
+    ///
+    /// let minimal = Diff::from_bytes(&minimal).unwrap();
+    /// assert_eq!(apply_bytes(base, &minimal).unwrap().0, b"a\nb\nC\nd\n");
+    /// ```
+    pub fn to_minimal_unified(&self) -> Vec<u8>
+    where
+        T: AsRef<[u8]>,
+    {
+        let mut diff = self.trim_context(0);
+        for hunk in &mut diff.hunks {
+            hunk.function_context = None;
+        }
+        diff.to_bytes()
+    }
+
+    /// Returns the ranges of lines of the old file which are not part of any hunk, given as
+    /// 1-based line numbers like [`HunkRange::range`].
+    ///
+    /// Lines are part of a hunk if they are within its old range, including its context lines.
+    /// `old_line_count` is the number of lines of the old file, which determines the range after
+    /// the last hunk. Hunks which only insert lines don't cover any lines of the old file.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let diff = Diff::from_str("@@ -3,2 +3,2 @@\n c\n-d\n+D\n").unwrap();
+    /// assert_eq!(diff.unchanged_ranges(10), [1..3, 5..11]);
+    /// ```
+    pub fn unchanged_ranges(&self, old_line_count: usize) -> Vec<ops::Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut start = 1;
+        for hunk in self.hunks.iter().filter(|hunk| !hunk.old_range.is_empty()) {
+            let range = hunk.old_range.range();
+            let end = range.start.min(old_line_count + 1);
+            if start < end {
+                ranges.push(start..end);
+            }
+            start = start.max(range.end);
+        }
+        if start <= old_line_count {
+            ranges.push(start..old_line_count + 1);
+        }
+        ranges
+    }
+
+    /// Returns a fingerprint of the base this diff was created against.
+    ///
+    /// The fingerprint covers the content and line number of every context and deleted line,
+    /// which are the lines the base is expected to contain. Two diffs with the same fingerprint
+    /// expect the same lines at the same positions.
+    pub fn base_fingerprint(&self) -> BaseFingerprint {
+        let mut hasher = DefaultHasher::new();
+        for (line_number, (line, ending)) in self.base_lines() {
+            line_number.hash(&mut hasher);
+            line.as_bytes().hash(&mut hasher);
+            ending.hash(&mut hasher);
+        }
+        BaseFingerprint(hasher.finish())
+    }
+
+    /// Returns `true` if `old` contains every context and deleted line of this diff at the line
+    /// numbers given by the hunk headers.
+    ///
+    /// This is a quick check for whether the diff was created against `old`. Unlike applying the
+    /// diff, no fuzzy matching is performed and hunks are not searched for at other offsets, so a
+    /// diff may still apply to a base for which this returns `false`.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let patch = Diff::from_str("@@ -2,2 +2,2 @@\n b\n-c\n+C\n").unwrap();
+    /// assert!(patch.matches_base("a\nb\nc\n"));
+    /// assert!(!patch.matches_base("b\nc\n"));
+    /// ```
+    pub fn matches_base(&self, old: &T) -> bool {
+        let old: Vec<_> = LineIter::new(old).collect();
+        self.base_lines().all(|(line_number, line)| {
+            line_number.checked_sub(1).and_then(|i| old.get(i)) == Some(&line)
+        })
+    }
+
+    /// Iterate over the lines of the base expected by the hunks, along with their 1-based line
+    /// numbers
+    fn base_lines(&self) -> impl Iterator<Item = (usize, (&'a T, Option<LineEnd>))> + '_ {
+        self.hunks.iter().flat_map(|hunk| {
+            hunk.lines
+                .iter()
+                .filter_map(|line| match line {
+                    Line::Context(line) | Line::Delete(line) => Some(*line),
+                    Line::Insert(_) => None,
+                })
+                .enumerate()
+                .map(|(i, line)| (hunk.old_range.start + i, line))
+        })
+    }
+}
+
+/// Append up to `context` lines of `old` starting at `end` to `hunk` as context
+fn add_trailing_context<'a, T: ?Sized + ToOwned>(
+    hunk: &mut Hunk<'a, T>,
+    old: &[(&'a T, Option<LineEnd>)],
+    end: usize,
+    context: usize,
+) {
+    let trailing_end = (end + context).min(old.len());
+    hunk.lines
+        .extend(old[end..trailing_end].iter().copied().map(Line::Context));
+}
+
+/// A fingerprint of the base a [`Diff`] was created against, see [`Diff::base_fingerprint`].
+///
+/// Fingerprints are meant for comparing diffs within a single program and should not be
+/// persisted, as the underlying hash may change between releases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BaseFingerprint(u64);
+
+impl BaseFingerprint {
+    /// Returns the fingerprint as an integer
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
 }
 
 impl<T: AsRef<[u8]> + ToOwned + ?Sized> Diff<'_, T> {
@@ -111,6 +897,86 @@ pub fn patch_from_bytes_with_config(
     parse::parse_bytes_multiple_with_config(input, config)
 }
 
+/// Parse a multi-file patch, collecting the errors of the files which fail to parse instead of
+/// returning the first one.
+///
+/// The patch of a file with an error is dropped and parsing continues at the next `diff ` or `--- `
+/// line. Each error is wrapped in [`ParsePatchError::Location`] with the line of the patch it was
+/// found at, which makes this useful for reporting all problems of a patch at once.
+///
+/// ```
+/// use flickzeug::patch_from_str_lenient;
+///
+/// let patch = "\
+/// --- a/first.txt
+/// +++ b/first.txt
+/// @@ -1 +1 @@
+/// -a
+/// +b
+/// --- a/second.txt
+/// +++ b/second.txt
+/// @@ -1 +1 @
+/// -c
+/// +d
+/// ";
+///
+/// let (diffs, errors) = patch_from_str_lenient(patch);
+/// assert_eq!(diffs.len(), 1);
+/// assert_eq!(errors[0].line(), Some(8));
+/// ```
+pub fn patch_from_str_lenient(input: &str) -> (Vec<Diff<'_, str>>, Vec<ParsePatchError>) {
+    parse::parse_multiple_lenient(input, parse::convert_cow_to_str)
+}
+
+/// Parse a potentially non-utf8 multi-file patch, collecting the errors of the files which fail
+/// to parse, see [`patch_from_str_lenient`]
+pub fn patch_from_bytes_lenient(input: &[u8]) -> (Vec<Diff<'_, [u8]>>, Vec<ParsePatchError>) {
+    parse::parse_multiple_lenient(input, |line| line)
+}
+
+/// Parse only the diff of the `n`th file (starting at `0`) of a multi-file git patch.
+///
+/// Files are found by their `diff --git` lines, without parsing the hunks of the other files, which
+/// makes this much cheaper than [`patch_from_str`] when only a single file of a large patch is
+/// needed. Returns `None` if the patch has `n` files or less. Patches without `diff --git` lines
+/// are not supported.
+///
+/// ```
+/// use flickzeug::patch_nth_from_str;
+///
+/// let patch = "\
+/// diff --git a/first.txt b/first.txt
+/// --- a/first.txt
+/// +++ b/first.txt
+/// @@ -1 +1 @@
+/// -a
+/// +b
+/// diff --git a/second.txt b/second.txt
+/// --- a/second.txt
+/// +++ b/second.txt
+/// @@ -1 +1 @@
+/// -c
+/// +d
+/// ";
+///
+/// let diff = patch_nth_from_str(patch, 1).unwrap().unwrap();
+/// assert_eq!(diff.modified(), Some("second.txt"));
+/// assert!(patch_nth_from_str(patch, 2).unwrap().is_none());
+/// ```
+pub fn patch_nth_from_str(input: &str, n: usize) -> Result<Option<Diff<'_, str>>, ParsePatchError> {
+    parse::parse_nth(input, n)
+}
+
+/// Parse only the diff of the `n`th file (starting at `0`) of a non-utf8 multi-file git patch.
+///
+/// See [`patch_nth_from_str`].
+pub fn patch_nth_from_bytes(
+    input: &[u8],
+    n: usize,
+) -> Result<Option<Diff<'_, [u8]>>, ParsePatchError> {
+    parse::parse_bytes_nth(input, n)
+}
+
 impl<'a> Diff<'a, str> {
     /// Parse a `Patch` from a string
     ///
@@ -138,6 +1004,24 @@ impl<'a> Diff<'a, str> {
     pub fn from_str(s: &'a str) -> Result<Diff<'a, str>, ParsePatchError> {
         parse::parse(s)
     }
+
+    /// Returns the path of the reject file for this diff, following the convention of `patch` and
+    /// `git apply --reject` of appending `.rej` to the path of the target file.
+    ///
+    /// The target is the modified file, or the original file if the diff deletes it. Returns
+    /// `None` if the diff doesn't name a file.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    /// use std::path::Path;
+    ///
+    /// let patch = Diff::from_str("--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\n").unwrap();
+    /// assert_eq!(patch.reject_path().as_deref(), Some(Path::new("src/lib.rs.rej")));
+    /// ```
+    pub fn reject_path(&self) -> Option<PathBuf> {
+        let target = self.new_path().or(self.old_path())?;
+        Some(PathBuf::from(format!("{target}.rej")))
+    }
 }
 
 impl<'a> Diff<'a, [u8]> {
@@ -166,32 +1050,81 @@ where
     }
 }
 
+/// The name of a file, and whether it still starts with the `a/` or `b/` prefix of git, which
+/// is only the case if the prefixes weren't stripped when parsing
 #[derive(PartialEq, Eq, PartialOrd, Ord)]
-struct Filename<'a, T: ToOwned + ?Sized>(Cow<'a, T>);
+struct Filename<'a, T: ToOwned + ?Sized>(Cow<'a, T>, bool);
+
+impl<T: Text + ToOwned + ?Sized> Filename<'_, T> {
+    /// Returns the name without the `a/` or `b/` prefix
+    fn path(&self) -> &T {
+        if self.1 {
+            self.0.split_at(2).1
+        } else {
+            &self.0
+        }
+    }
+}
+
+/// Parse an octal file mode like `100644`
+fn parse_mode(mode: &str) -> Option<u32> {
+    u32::from_str_radix(mode.trim_ascii_end(), 8).ok()
+}
+
+/// Remove the first `n` components of `path`, keeping at least its last one
+fn strip_path_components<T: Text + ?Sized>(mut path: &T, n: usize) -> &T {
+    for _ in 0..n {
+        match path.find("/") {
+            Some(slash) => path = path.split_at(slash + 1).1,
+            None => break,
+        }
+    }
+    path
+}
 
 const ESCAPED_CHARS: &[char] = &['\n', '\t', '\0', '\r', '\"', '\\'];
 #[allow(clippy::byte_char_slices)]
 const ESCAPED_CHARS_BYTES: &[u8] = &[b'\n', b'\t', b'\0', b'\r', b'\"', b'\\'];
 
 impl Filename<'_, str> {
-    fn needs_to_be_escaped(&self) -> bool {
-        self.0.contains(ESCAPED_CHARS)
+    /// Write the name, replacing its `a/` or `b/` prefix with `prefix` if one is given
+    fn fmt_into<W: fmt::Write>(&self, prefix: Option<&str>, w: &mut W) -> fmt::Result {
+        let name = match prefix {
+            Some(_) => self.path(),
+            None => &self.0,
+        };
+        let prefix = prefix.unwrap_or_default();
+        if name.contains(ESCAPED_CHARS) {
+            w.write_char('\"')?;
+            w.write_str(prefix)?;
+            for c in name.chars() {
+                if ESCAPED_CHARS.contains(&c) {
+                    w.write_char('\\')?;
+                }
+                w.write_char(c)?;
+            }
+            w.write_char('\"')?;
+        } else {
+            w.write_str(prefix)?;
+            w.write_str(name)?;
+        }
+
+        Ok(())
     }
 }
 
 impl<T: ToOwned + AsRef<[u8]> + ?Sized> Filename<'_, T> {
-    fn needs_to_be_escaped_bytes(&self) -> bool {
-        self.0
-            .as_ref()
-            .as_ref()
-            .iter()
-            .any(|b| ESCAPED_CHARS_BYTES.contains(b))
-    }
-
-    fn write_into<W: std::io::Write>(&self, mut w: W) -> std::io::Result<()> {
-        if self.needs_to_be_escaped_bytes() {
+    /// Write the name, replacing its `a/` or `b/` prefix with `prefix` if one is given
+    fn write_into<W: std::io::Write>(&self, prefix: Option<&str>, mut w: W) -> std::io::Result<()> {
+        let mut name = self.0.as_ref().as_ref();
+        if prefix.is_some() && self.1 {
+            name = &name[2..];
+        }
+        let prefix = prefix.unwrap_or_default().as_bytes();
+        if name.iter().any(|b| ESCAPED_CHARS_BYTES.contains(b)) {
             w.write_all(b"\"")?;
-            for b in self.0.as_ref().as_ref() {
+            w.write_all(prefix)?;
+            for b in name {
                 if ESCAPED_CHARS_BYTES.contains(b) {
                     w.write_all(b"\\")?;
                 }
@@ -199,7 +1132,8 @@ impl<T: ToOwned + AsRef<[u8]> + ?Sized> Filename<'_, T> {
             }
             w.write_all(b"\"")?;
         } else {
-            w.write_all(self.0.as_ref().as_ref())?;
+            w.write_all(prefix)?;
+            w.write_all(name)?;
         }
 
         Ok(())
@@ -222,27 +1156,13 @@ impl<T: ToOwned + ?Sized> ops::Deref for Filename<'_, T> {
 
 impl<T: ToOwned + ?Sized> Clone for Filename<'_, T> {
     fn clone(&self) -> Self {
-        Self(self.0.clone())
+        Self(self.0.clone(), self.1)
     }
 }
 
 impl fmt::Display for Filename<'_, str> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        use std::fmt::Write;
-        if self.needs_to_be_escaped() {
-            f.write_char('\"')?;
-            for c in self.0.chars() {
-                if ESCAPED_CHARS.contains(&c) {
-                    f.write_char('\\')?;
-                }
-                f.write_char(c)?;
-            }
-            f.write_char('\"')?;
-        } else {
-            f.write_str(&self.0)?;
-        }
-
-        Ok(())
+        self.fmt_into(None, f)
     }
 }
 
@@ -261,7 +1181,7 @@ where
 }
 
 /// Represents a group of differing lines between two files
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub struct Hunk<'a, T: ?Sized + ToOwned> {
     old_range: HunkRange,
     new_range: HunkRange,
@@ -271,6 +1191,18 @@ pub struct Hunk<'a, T: ?Sized + ToOwned> {
     lines: Vec<Line<'a, T>>,
 }
 
+// Implemented manually since deriving would require `T: Clone`, which unsized types can't satisfy
+impl<T: ?Sized + ToOwned> Clone for Hunk<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            old_range: self.old_range,
+            new_range: self.new_range,
+            function_context: self.function_context,
+            lines: self.lines.clone(),
+        }
+    }
+}
+
 impl fmt::Display for Hunk<'_, str> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         PatchFormatter::new().fmt_hunk(self).fmt(f)
@@ -352,15 +1284,78 @@ impl<'a, T: Text + ?Sized + ToOwned> Hunk<'a, T> {
     }
 
     /// Returns the function context (if any) for the hunk
-    pub fn function_context(&self) -> Option<(&T, Option<LineEnd>)> {
+    pub fn function_context(&self) -> Option<(&'a T, Option<LineEnd>)> {
         self.function_context
     }
 
+    /// Sets the function context of the hunk, which follows the ranges in its header
+    ///
+    /// The line ending is the one of the header line when the hunk was parsed, it isn't used when
+    /// formatting the hunk. `None` removes the function context.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let mut diff = Diff::from_str("@@ -1 +1 @@ fn main()\n-a\n+b\n").unwrap();
+    /// diff.hunks_mut()[0].set_function_context(Some(("fn run()", None)));
+    /// assert_eq!(diff.to_string(), "@@ -1 +1 @@ fn run()\n-a\n+b\n");
+    /// diff.hunks_mut()[0].set_function_context(None);
+    /// assert_eq!(diff.to_string(), "@@ -1 +1 @@\n-a\n+b\n");
+    /// ```
+    pub fn set_function_context(&mut self, function_context: Option<(&'a T, Option<LineEnd>)>) {
+        self.function_context = function_context;
+    }
+
     /// Returns the lines in the hunk
     pub fn lines(&self) -> &[Line<'a, T>] {
         &self.lines
     }
 
+    /// Returns the lines in the hunk for adjusting them, e.g. with [`Line::map_content`].
+    ///
+    /// Callers changing the kind of a line need to update the ranges of the hunk, e.g. with
+    /// [`Hunk::recount`].
+    pub fn lines_mut(&mut self) -> &mut [Line<'a, T>] {
+        &mut self.lines
+    }
+
+    /// Sets the range of the hunk in the old file.
+    ///
+    /// The range isn't validated. Callers need to make sure that its length matches the number of
+    /// context and deleted lines of the hunk, and that the hunks of a diff stay sorted and don't
+    /// overlap, otherwise formatting or applying the diff produces unexpected results.
+    pub fn set_old_range(&mut self, range: HunkRange) {
+        self.old_range = range;
+    }
+
+    /// Sets the range of the hunk in the new file.
+    ///
+    /// The range isn't validated. Callers need to make sure that its length matches the number of
+    /// context and inserted lines of the hunk, and that the hunks of a diff stay sorted and don't
+    /// overlap, otherwise formatting or applying the diff produces unexpected results.
+    pub fn set_new_range(&mut self, range: HunkRange) {
+        self.new_range = range;
+    }
+
+    /// Recompute the lengths of the old and new ranges from the lines of the hunk, keeping the
+    /// position of the ranges.
+    ///
+    /// ```
+    /// use flickzeug::{Diff, HunkRange};
+    ///
+    /// let diff = Diff::from_str("@@ -3,2 +3,2 @@\n a\n-b\n+c\n").unwrap();
+    /// let mut hunk = diff.hunks()[0].clone();
+    /// hunk.set_old_range(HunkRange::new(10, 0));
+    /// hunk.recount();
+    /// assert_eq!(hunk.old_range(), HunkRange::new(11, 2));
+    /// assert_eq!(hunk.new_range(), HunkRange::new(3, 2));
+    /// ```
+    pub fn recount(&mut self) {
+        let (old_len, new_len) = hunk_lines_count(&self.lines);
+        self.old_range = HunkRange::from_index(self.old_range.index(), old_len);
+        self.new_range = HunkRange::from_index(self.new_range.index(), new_len);
+    }
+
     /// Creates a reverse patch for the hunk.  This is equivalent to what
     /// XDL_PATCH_REVERSE would apply in libxdiff.
     pub fn reverse(&self) -> Self {
@@ -384,10 +1379,35 @@ pub struct HunkRange {
 }
 
 impl HunkRange {
-    pub(crate) fn new(start: usize, len: usize) -> Self {
+    /// Construct a new range.
+    ///
+    /// Like in unified diffs, `start` is the 1-based number of the first line of the range, or
+    /// the number of the line before the range if `len` is `0`.
+    pub fn new(start: usize, len: usize) -> Self {
         Self { start, len }
     }
 
+    /// Construct a range from the 0-based index of its first line.
+    ///
+    /// Empty ranges refer to the line before the index, following the unified diff convention.
+    pub(crate) fn from_index(index: usize, len: usize) -> Self {
+        let start = if len > 0 { index + 1 } else { index };
+        Self::new(start, len)
+    }
+
+    /// Returns the 0-based index of the first line of the range, or of the line following the
+    /// range if it is empty
+    ///
+    /// Parsed ranges like `-0,1` which start at the non-existent line 0 are treated as starting at
+    /// the first line.
+    pub(crate) fn index(&self) -> usize {
+        if self.len > 0 {
+            self.start.saturating_sub(1)
+        } else {
+            self.start
+        }
+    }
+
     /// Returns the range as a `ops::Range`
     pub fn range(&self) -> ops::Range<usize> {
         self.start..self.end()
@@ -399,10 +1419,45 @@ impl HunkRange {
     }
 
     /// Returns the ending line number of the range (exclusive)
+    ///
+    /// For empty ranges this is the same as [`HunkRange::start`].
     pub fn end(&self) -> usize {
         self.start + self.len
     }
 
+    /// Returns `true` if the 1-based line number `line` is part of the range
+    ///
+    /// Empty ranges don't contain any lines.
+    ///
+    /// ```
+    /// use flickzeug::HunkRange;
+    ///
+    /// let range = HunkRange::new(3, 2);
+    /// assert!(range.contains(3) && range.contains(4));
+    /// assert!(!range.contains(range.end()));
+    /// assert!(!HunkRange::new(3, 0).contains(3));
+    /// ```
+    pub fn contains(&self, line: usize) -> bool {
+        self.range().contains(&line)
+    }
+
+    /// Moves the range by `delta` lines
+    ///
+    /// The start saturates at line `1`, or at `0` for empty ranges, which refers to the position
+    /// before the first line.
+    ///
+    /// ```
+    /// use flickzeug::HunkRange;
+    ///
+    /// let mut range = HunkRange::new(3, 2);
+    /// range.shift(-5);
+    /// assert_eq!(range, HunkRange::new(1, 2));
+    /// ```
+    pub fn shift(&mut self, delta: isize) {
+        let min = if self.len > 0 { 1 } else { 0 };
+        self.start = self.start.saturating_add_signed(delta).max(min);
+    }
+
     /// Returns the number of lines in the range
     pub fn len(&self) -> usize {
         self.len
@@ -428,7 +1483,7 @@ impl fmt::Display for HunkRange {
 ///
 /// A `Line` contains the terminating newline character `\n` unless it is the final
 /// line in the file and the file does not end with a newline character.
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub enum Line<'a, T: ?Sized> {
     /// A line providing context in the diff which is present in both the old and new file
     Context((&'a T, Option<LineEnd>)),
@@ -438,6 +1493,15 @@ pub enum Line<'a, T: ?Sized> {
     Insert((&'a T, Option<LineEnd>)),
 }
 
+// Implemented manually since deriving would require `T: Copy`, which unsized types can't satisfy
+impl<T: ?Sized> Clone for Line<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Line<'_, T> {}
+
 // We want to have strings in the output whenever possible.
 impl<T: ?Sized + Text> fmt::Debug for Line<'_, T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -479,7 +1543,71 @@ impl<T: ?Sized + Text> fmt::Debug for Line<'_, T> {
     }
 }
 
-impl<T: ?Sized> Line<'_, T> {
+impl<'a, T: ?Sized> Line<'a, T> {
+    /// Returns `true` if the line is a context line, present in both the old and new file
+    pub fn is_context(&self) -> bool {
+        matches!(self, Line::Context(_))
+    }
+
+    /// Returns `true` if the line is inserted to the new file
+    pub fn is_insert(&self) -> bool {
+        matches!(self, Line::Insert(_))
+    }
+
+    /// Returns `true` if the line is deleted from the old file
+    pub fn is_delete(&self) -> bool {
+        matches!(self, Line::Delete(_))
+    }
+
+    /// Returns the content of the line without its line ending, regardless of its kind
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let diff = Diff::from_str("@@ -1,2 +1,2 @@\n a\n-b\n+c\n").unwrap();
+    /// let inserted: Vec<_> = diff.lines().filter(|line| line.is_insert()).map(|line| line.content()).collect();
+    /// assert_eq!(inserted, ["c"]);
+    /// ```
+    pub fn content(&self) -> &'a T {
+        match self {
+            Line::Context(l) | Line::Delete(l) | Line::Insert(l) => l.0,
+        }
+    }
+
+    /// Returns the line with its content replaced by the result of `f`, keeping its kind
+    ///
+    /// Lines borrow their content, so the new content has to be stored outside of the diff, e.g.
+    /// in a `Vec<String>` which outlives the diff. The line ending is kept as is, while
+    /// [`Line::span_in`] refers to the new content from then on, so it returns `None` for the
+    /// patch the line was parsed from.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let redacted = "password = <redacted>";
+    /// let patch = "@@ -1 +1 @@\n-password = old\n+password = hunter2\n";
+    /// let mut diff = Diff::from_str(patch).unwrap();
+    /// for line in diff.hunks_mut()[0].lines_mut() {
+    ///     if line.is_insert() {
+    ///         *line = line.map_content(|_| redacted);
+    ///     }
+    /// }
+    /// assert_eq!(
+    ///     diff.to_string(),
+    ///     "@@ -1 +1 @@\n-password = old\n+password = <redacted>\n"
+    /// );
+    /// ```
+    pub fn map_content<'b>(self, f: impl FnOnce(&'a T) -> &'b T) -> Line<'b, T>
+    where
+        'a: 'b,
+    {
+        match self {
+            Line::Context((line, end)) => Line::Context((f(line), end)),
+            Line::Delete((line, end)) => Line::Delete((f(line), end)),
+            Line::Insert((line, end)) => Line::Insert((f(line), end)),
+        }
+    }
+
     pub fn reverse(&self) -> Self {
         match *self {
             Line::Context(l) => Line::Context(l),
@@ -496,3 +1624,652 @@ impl<T: ?Sized> Line<'_, T> {
         }
     }
 }
+
+impl<T: ?Sized + Text> Line<'_, T> {
+    /// Returns the content of the line, without its line ending, as raw bytes.
+    ///
+    /// This is available for both `str` and `[u8]` diffs, which makes it possible to process
+    /// line content uniformly regardless of the underlying text type.
+    pub fn content_bytes(&self) -> &[u8] {
+        self.content().as_bytes()
+    }
+
+    /// Returns the byte range of the content of the line in `patch`, the text it was parsed from.
+    ///
+    /// The range covers the content without the marker in front of it (like `+` or `> `) and
+    /// without its line ending. Returns `None` if the line doesn't borrow its content from `patch`,
+    /// e.g. because it was parsed from another text.
+    ///
+    /// ```
+    /// use flickzeug::Diff;
+    ///
+    /// let patch = "@@ -1 +1 @@\n-old\n+new\n";
+    /// let diff = Diff::from_str(patch).unwrap();
+    /// let span = diff.hunks()[0].lines()[1].span_in(patch).unwrap();
+    /// assert_eq!(&patch[span], "new");
+    /// ```
+    pub fn span_in(&self, patch: &T) -> Option<ops::Range<usize>> {
+        let (content, patch) = (self.content_bytes(), patch.as_bytes());
+        let start = (content.as_ptr() as usize).checked_sub(patch.as_ptr() as usize)?;
+        let end = start + content.len();
+        (end <= patch.len()).then_some(start..end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn content_bytes_is_identical_for_str_and_bytes() {
+        let patch = "\
+--- a/file
++++ b/file
+@@ -1,3 +1,3 @@
+ context
+-deleted
++inserted \u{1f980}
+ more context
+";
+
+        fn hash_lines<T: ?Sized + Text + ToOwned>(diff: &Diff<'_, T>) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            for line in diff.hunks().iter().flat_map(|h| h.lines()) {
+                line.content_bytes().hash(&mut hasher);
+            }
+            hasher.finish()
+        }
+
+        let str_diff = Diff::from_str(patch).unwrap();
+        let bytes_diff = Diff::from_bytes(patch.as_bytes()).unwrap();
+
+        assert_eq!(
+            str_diff.hunks()[0].lines()[2].content_bytes(),
+            "inserted \u{1f980}".as_bytes()
+        );
+        assert_eq!(hash_lines(&str_diff), hash_lines(&bytes_diff));
+    }
+
+    #[test]
+    fn base_fingerprint() {
+        let base = "fn main() {\n    let x = 1;\n    println!(\"{x}\");\n}\n";
+        let patch = crate::create_patch(
+            base,
+            "fn main() {\n    let x = 2;\n    println!(\"{x}\");\n}\n",
+        );
+
+        assert!(patch.matches_base(base));
+        // Different content at an expected line
+        assert!(!patch.matches_base("fn main() {\n    let x = 3;\n    println!(\"{x}\");\n}\n"));
+        // Same content shifted by a line
+        assert!(!patch.matches_base("\nfn main() {\n    let x = 1;\n    println!(\"{x}\");\n}\n"));
+        // Missing final newline
+        assert!(!patch.matches_base("fn main() {\n    let x = 1;\n    println!(\"{x}\");\n}"));
+        // Truncated base
+        assert!(!patch.matches_base("fn main() {\n"));
+
+        let same_base = crate::create_patch(
+            base,
+            "fn main() {\n    let x = 2;\n    println!(\"{x}\");\n}\n",
+        );
+        let other_base = crate::create_patch(
+            "fn main() {\n    let x = 0;\n    println!(\"{x}\");\n}\n",
+            "fn main() {\n    let x = 2;\n    println!(\"{x}\");\n}\n",
+        );
+        assert_eq!(patch.base_fingerprint(), same_base.base_fingerprint());
+        assert_ne!(patch.base_fingerprint(), other_base.base_fingerprint());
+
+        // The fingerprint only depends on the base, not on the inserted lines
+        let bytes_patch =
+            Diff::from_bytes(b"@@ -2 +2 @@\n-    let x = 1;\n+    let x = 42;\n").unwrap();
+        let str_patch = Diff::from_str("@@ -2 +2 @@\n-    let x = 1;\n+    let y = 1;\n").unwrap();
+        assert_eq!(bytes_patch.base_fingerprint(), str_patch.base_fingerprint());
+        assert!(bytes_patch.matches_base(base.as_bytes()));
+    }
+
+    #[test]
+    fn harden() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let diff = Diff::from_str("@@ -3 +3 @@\n-3\n+three\n@@ -6 +6 @@\n-6\n+six\n").unwrap();
+        assert!(!diff.requires_verification());
+
+        // Hunks whose context overlaps are merged
+        let hardened = diff.harden(old, 1).unwrap();
+        assert!(hardened.requires_verification());
+        assert_eq!(
+            hardened.to_string(),
+            "@@ -2,6 +2,6 @@\n 2\n-3\n+three\n 4\n 5\n-6\n+six\n 7\n"
+        );
+        assert_eq!(
+            crate::apply(old, &hardened).unwrap().0,
+            crate::apply(old, &diff).unwrap().0
+        );
+
+        let hardened = diff.harden(old, 3).unwrap();
+        assert_eq!(
+            hardened.to_string(),
+            "@@ -1,9 +1,9 @@\n 1\n 2\n-3\n+three\n 4\n 5\n-6\n+six\n 7\n 8\n 9\n"
+        );
+
+        let insertion = Diff::from_str("@@ -0,0 +1 @@\n+0\n").unwrap();
+        assert_eq!(
+            insertion.harden(old, 2).unwrap().to_string(),
+            "@@ -1,2 +1,3 @@\n+0\n 1\n 2\n"
+        );
+
+        // Hunks have to match the base at their declared position
+        let err = Diff::from_str("@@ -4 +4 @@\n-3\n+three\n")
+            .unwrap()
+            .harden(old, 1)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ApplyError::ContextMismatch { hunk_index: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn hardened_diff_refuses_drifted_file() {
+        let old = "1\n2\n3\n4\n5\n";
+        let drifted = "0\n1\n2 changed\n3\n4\n5\n";
+        let diff = Diff::from_str("@@ -3 +3 @@\n-3\n+three\n").unwrap();
+        let hardened = diff.harden(old, 1).unwrap();
+
+        // Without verification the same hunk applies fuzzily, ignoring the changed line
+        let unverified = hardened.to_string();
+        let unverified = Diff::from_str(&unverified).unwrap();
+        assert!(crate::apply(drifted, &diff).is_ok());
+        assert!(crate::apply(drifted, &unverified).is_ok());
+
+        let err = crate::apply(drifted, &hardened).unwrap_err();
+        assert!(matches!(
+            err,
+            ApplyError::ContextMismatch { hunk_index: 0, .. }
+        ));
+
+        // Offsets are still allowed as long as the context matches exactly
+        let (result, _) = crate::apply("0\n1\n2\n3\n4\n5\n", &hardened).unwrap();
+        assert_eq!(result, "0\n1\n2\nthree\n4\n5\n");
+    }
+
+    #[test]
+    fn adjust_hunk_ranges() {
+        let mut diff = Diff::from_str("@@ -1,2 +1,2 @@\n a\n-b\n+c\n@@ -5 +5,0 @@\n-e\n").unwrap();
+
+        // Move all hunks down by two lines, e.g. after prepending lines to the file
+        for hunk in diff.hunks_mut() {
+            let (old, new) = (hunk.old_range(), hunk.new_range());
+            hunk.set_old_range(HunkRange::new(old.start() + 2, old.len()));
+            hunk.set_new_range(HunkRange::new(new.start() + 2, new.len()));
+        }
+        assert_eq!(
+            diff.to_string(),
+            "@@ -3,2 +3,2 @@\n a\n-b\n+c\n@@ -7 +7,0 @@\n-e\n"
+        );
+
+        // Recounting keeps the position of ranges, also when their length changes from or to 0
+        let hunk = &mut diff.hunks_mut()[1];
+        hunk.set_old_range(HunkRange::new(6, 0));
+        hunk.set_new_range(HunkRange::new(7, 3));
+        hunk.recount();
+        assert_eq!(hunk.old_range(), HunkRange::new(7, 1));
+        assert_eq!(hunk.new_range(), HunkRange::new(6, 0));
+    }
+
+    #[test]
+    fn coalesce_hunks() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let patch = "\
+@@ -1,2 +1,2 @@
+-1
++one
+ 2
+@@ -5,2 +5,3 @@
+ 5
++5.5
+ 6
+@@ -9 +10 @@
+-9
++nine
+";
+
+        // The gaps of two and one lines are too large
+        let mut diff = Diff::from_str(patch).unwrap();
+        diff.coalesce_hunks(old, 1);
+        assert_eq!(diff.hunks().len(), 3);
+
+        let mut diff = Diff::from_str(patch).unwrap();
+        diff.coalesce_hunks(old, 2);
+        let expected = "\
+@@ -1,9 +1,10 @@
+-1
++one
+ 2
+ 3
+ 4
+ 5
++5.5
+ 6
+ 7
+ 8
+-9
++nine
+";
+        assert_eq!(diff.to_string(), expected);
+        assert_eq!(
+            crate::apply(old, &diff).unwrap().0,
+            "one\n2\n3\n4\n5\n5.5\n6\n7\n8\nnine\n10\n"
+        );
+
+        // Hunks which were moved to overlap are merged without duplicating the shared context
+        let mut diff = Diff::from_str(
+            "@@ -1,3 +1,3 @@\n-1\n+one\n 2\n 3\n@@ -5,3 +5,3 @@\n 2\n 3\n-4\n+four\n",
+        )
+        .unwrap();
+        diff.hunks_mut()[1].set_old_range(HunkRange::new(2, 3));
+        diff.hunks_mut()[1].set_new_range(HunkRange::new(2, 3));
+        diff.coalesce_hunks(old, 0);
+        assert_eq!(
+            diff.to_string(),
+            "@@ -1,4 +1,4 @@\n-1\n+one\n 2\n 3\n-4\n+four\n"
+        );
+
+        // Conflicting overlaps and gaps beyond the end of the base are left alone
+        let mut diff =
+            Diff::from_str("@@ -1,2 +1,2 @@\n-1\n+one\n 2\n@@ -5 +5 @@\n-2\n+two\n").unwrap();
+        diff.hunks_mut()[1].set_old_range(HunkRange::new(2, 1));
+        diff.coalesce_hunks(old, 10);
+        assert_eq!(diff.hunks().len(), 2);
+        let mut diff =
+            Diff::from_str("@@ -1 +1 @@\n-1\n+one\n@@ -20 +20 @@\n-20\n+twenty\n").unwrap();
+        diff.coalesce_hunks(old, 100);
+        assert_eq!(diff.hunks().len(), 2);
+    }
+
+    #[test]
+    fn clamp_to_range() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n16\n17\n18\n19\n20\n";
+        let diff = Diff::from_str(
+            "\
+@@ -1,3 +1,4 @@
++0
+ 1
+ 2
+ 3
+@@ -8,5 +9,3 @@
+ 8
+-9
+-10
+-11
++ten
+ 12
+@@ -17,3 +16,3 @@
+ 17
+-18
++eighteen
+ 19
+",
+        )
+        .unwrap();
+
+        // Fully inside
+        assert_eq!(diff.clamp_to_range(1..21).to_string(), diff.to_string());
+        // Fully outside
+        assert!(diff.clamp_to_range(4..8).hunks().is_empty());
+        assert!(diff.clamp_to_range(21..30).hunks().is_empty());
+
+        // The first and last hunks are trimmed to their changes within the range, and the change
+        // spanning the boundary of the second hunk only keeps its deletions within the range
+        let clamped = diff.clamp_to_range(1..11);
+        let expected = "\
+@@ -1,3 +1,4 @@
++0
+ 1
+ 2
+ 3
+@@ -8,3 +9,2 @@
+ 8
+-9
+-10
++ten
+";
+        assert_eq!(clamped.to_string(), expected);
+        assert_eq!(
+            crate::apply(old, &clamped).unwrap().0,
+            "0\n1\n2\n3\n4\n5\n6\n7\n8\nten\n11\n12\n13\n14\n15\n16\n17\n18\n19\n20\n"
+        );
+
+        // Dropping changes shifts the new ranges of later hunks
+        let clamped = diff.clamp_to_range(11..19);
+        let expected = "\
+@@ -11,2 +11,2 @@
+-11
++ten
+ 12
+@@ -17,2 +17,2 @@
+ 17
+-18
++eighteen
+";
+        assert_eq!(clamped.to_string(), expected);
+        assert_eq!(
+            crate::apply(old, &clamped).unwrap().0,
+            old.replace("11\n", "ten\n").replace("18", "eighteen")
+        );
+
+        // Insertions at the start of the file belong to the first line
+        assert_eq!(diff.clamp_to_range(2..4).hunks().len(), 0);
+        assert_eq!(
+            diff.clamp_to_range(1..2).to_string(),
+            "@@ -1 +1,2 @@\n+0\n 1\n"
+        );
+    }
+
+    #[test]
+    fn trim_context() {
+        let old: String = (1..=30).map(|i| format!("{i}\n")).collect();
+        let new = old
+            .replace("\n2\n", "\ntwo\n")
+            .replace("\n5\n", "\n5\nfive and a half\n")
+            .replace("\n20\n", "\n")
+            .replace("30\n", "thirty");
+        let diff = crate::create_patch(&old, &new);
+        assert_eq!(diff.hunks().len(), 3);
+
+        // Hunks are split where the context between changes is too long
+        assert_eq!(diff.trim_context(3).to_string(), diff.to_string());
+        assert_eq!(diff.trim_context(1).hunks().len(), 4);
+        let trimmed = diff.trim_context(0);
+        let ranges: Vec<_> = trimmed
+            .hunks()
+            .iter()
+            .map(|hunk| (hunk.old_range().to_string(), hunk.new_range().to_string()))
+            .collect();
+        assert_eq!(
+            ranges,
+            [("2", "2"), ("5,0", "6"), ("20", "20,0"), ("30", "30")]
+                .map(|(old, new)| (old.to_owned(), new.to_owned()))
+        );
+
+        // The minimal patch is smaller and still applies to the original
+        let minimal = diff.to_minimal_unified();
+        assert!(minimal.len() < diff.to_bytes().len());
+        let minimal = Diff::from_bytes(&minimal).unwrap();
+        assert_eq!(
+            crate::apply_bytes(old.as_bytes(), &minimal).unwrap().0,
+            new.as_bytes()
+        );
+    }
+
+    #[test]
+    fn unchanged_ranges() {
+        let diff = Diff::from_str(
+            "\
+@@ -1,2 +1,2 @@
+-1
++one
+ 2
+@@ -8,3 +8,4 @@
+ 8
++8.5
+ 9
+ 10
+@@ -19,2 +20,2 @@
+ 19
+-20
++twenty
+",
+        )
+        .unwrap();
+        assert_eq!(diff.unchanged_ranges(20), [3..8, 11..19]);
+        // The old file continues after the last hunk
+        assert_eq!(diff.unchanged_ranges(25), [3..8, 11..19, 21..26]);
+
+        // Pure insertions don't cover any lines
+        let diff = Diff::from_str("@@ -2,0 +3 @@\n+new\n").unwrap();
+        assert_eq!(diff.unchanged_ranges(4), vec![1..5]);
+        let empty = Diff::<str>::new(None::<&str>, None::<&str>, Vec::new());
+        assert!(empty.unchanged_ranges(0).is_empty());
+    }
+
+    #[test]
+    fn reject_path() {
+        let modified = Diff::from_str("--- a/foo.c\n+++ b/foo.c\n@@ -1 +1 @@\n-a\n+b\n").unwrap();
+        assert_eq!(modified.reject_path(), Some(PathBuf::from("foo.c.rej")));
+
+        // A deleted file's reject file is named after the original
+        let deleted = Diff::<str>::new(Some("foo.c"), None::<&str>, Vec::new());
+        assert_eq!(deleted.reject_path(), Some(PathBuf::from("foo.c.rej")));
+
+        let unnamed = Diff::from_str("@@ -1 +1 @@\n-a\n+b\n").unwrap();
+        assert_eq!(unnamed.reject_path(), None);
+    }
+
+    #[test]
+    fn lines_across_hunks() {
+        let patch = std::fs::read_to_string("src/patch/test-data/CVE-2019-12211-13.patch").unwrap();
+        let diff = Diff::from_str(&patch).unwrap();
+        assert_eq!(diff.hunks().len(), 11);
+        assert_eq!(diff.inserted_lines().count(), 58);
+        assert_eq!(diff.deleted_lines().count(), 12);
+
+        // The lines are borrowed from the hunks, in order
+        assert!(
+            diff.lines()
+                .zip(diff.hunks().iter().flat_map(Hunk::lines))
+                .all(|(a, b)| std::ptr::eq(a, b))
+        );
+    }
+
+    #[test]
+    fn line_counts() {
+        let patch = std::fs::read_to_string("src/patch/test-data/CVE-2019-12211-13.patch").unwrap();
+        let diff = Diff::from_str(&patch).unwrap();
+        let stats = diff.stats();
+        assert_eq!(
+            (
+                diff.hunk_count(),
+                diff.added_line_count(),
+                diff.deleted_line_count()
+            ),
+            (stats.hunks_applied, stats.lines_added, stats.lines_deleted)
+        );
+        assert_eq!(diff.added_line_count(), 58);
+
+        // Deletions on both sides of an insertion
+        let diff = Diff::from_str("@@ -1,4 +1,3 @@\n-a\n+b\n-c\n d\n-e\n+f\n").unwrap();
+        assert_eq!(diff.hunk_count(), 1);
+        assert_eq!(diff.added_line_count(), 2);
+        assert_eq!(diff.deleted_line_count(), 3);
+        assert_eq!(diff.reverse().deleted_line_count(), 2);
+    }
+
+    #[test]
+    fn empty_diffs() {
+        let diff = crate::create_patch("a\nb\n", "a\nb\n");
+        assert_eq!(diff.hunk_count(), 0);
+        assert!(diff.is_empty());
+
+        let diff = Diff::from_str("--- a/f\n+++ b/f\n@@ -1,2 +1,2 @@\n a\n b\n").unwrap();
+        assert_eq!(diff.hunk_count(), 1);
+        assert!(diff.is_empty());
+        assert_eq!(crate::apply("a\nb\n", &diff).unwrap().0, "a\nb\n");
+
+        let diff = Diff::from_str("@@ -1,2 +1,2 @@\n a\n-b\n+b\n").unwrap();
+        assert!(!diff.is_empty());
+
+        let diff = Diff::from_str("diff --git a/f b/f\nBinary files a/f and b/f differ\n").unwrap();
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn set_function_context() {
+        let patch = "@@ -1,2 +1,2 @@ fn main() {\n a\n-b\n+c\n@@ -9 +9 @@\n-x\n+y\n";
+        let mut diff = Diff::from_str(patch).unwrap();
+        assert_eq!(diff.to_string(), patch);
+
+        let hunks = diff.hunks_mut();
+        hunks[0].set_function_context(None);
+        hunks[1].set_function_context(Some(("impl Foo {", Some(LineEnd::Lf))));
+        let modified = diff.to_string();
+        assert_eq!(
+            modified,
+            "@@ -1,2 +1,2 @@\n a\n-b\n+c\n@@ -9 +9 @@ impl Foo {\n-x\n+y\n"
+        );
+
+        let reparsed = Diff::from_str(&modified).unwrap();
+        assert_eq!(reparsed.hunks()[0].function_context(), None);
+        assert_eq!(
+            reparsed.hunks()[1].function_context(),
+            Some(("impl Foo {", Some(LineEnd::Lf)))
+        );
+        assert_eq!(reparsed.to_string(), modified);
+    }
+
+    #[test]
+    fn line_kind_and_content() {
+        let context: Line<'_, str> = Line::Context(("a", Some(LineEnd::Lf)));
+        let delete: Line<'_, str> = Line::Delete(("b", Some(LineEnd::CrLf)));
+        let insert: Line<'_, str> = Line::Insert(("c", None));
+
+        let kinds = |line: Line<'_, str>| (line.is_context(), line.is_delete(), line.is_insert());
+        assert_eq!(kinds(context), (true, false, false));
+        assert_eq!(kinds(delete), (false, true, false));
+        assert_eq!(kinds(insert), (false, false, true));
+
+        assert_eq!(
+            [context, delete, insert].map(|line| line.content()),
+            ["a", "b", "c"]
+        );
+        let bytes: Line<'_, [u8]> = Line::Delete((b"\xff", None));
+        assert_eq!(bytes.content(), b"\xff");
+        assert!(bytes.is_delete());
+    }
+
+    #[test]
+    fn map_content() {
+        let patch = "\
+@@ -1,3 +1,3 @@
+ context
+-deleted
++inserted
+ more context
+";
+        let uppercase: Vec<_> = Diff::from_str(patch)
+            .unwrap()
+            .inserted_lines()
+            .map(|line| line.content().to_uppercase())
+            .collect();
+        let mut uppercase = uppercase.iter();
+
+        let mut diff = Diff::from_str(patch).unwrap();
+        for line in diff.hunks_mut()[0].lines_mut() {
+            if line.is_insert() {
+                *line = line.map_content(|_| uppercase.next().unwrap());
+            }
+        }
+        assert_eq!(
+            diff.to_string(),
+            "@@ -1,3 +1,3 @@\n context\n-deleted\n+INSERTED\n more context\n"
+        );
+
+        let lines = diff.hunks()[0].lines();
+        assert_eq!(lines[2], Line::Insert(("INSERTED", Some(LineEnd::Lf))));
+        assert!(lines[2].span_in(patch).is_none());
+        assert_eq!(&patch[lines[1].span_in(patch).unwrap()], "deleted");
+    }
+
+    #[test]
+    fn hunk_range_end_and_contains() {
+        let range = HunkRange::new(5, 3);
+        assert_eq!(range.end(), 8);
+        assert!(!range.contains(4));
+        assert!((5..8).all(|line| range.contains(line)));
+        assert!(!range.contains(range.end()));
+
+        // Pure insertions
+        let range = HunkRange::new(5, 0);
+        assert_eq!(range.end(), range.start());
+        assert!((0..10).all(|line| !range.contains(line)));
+        assert!(!HunkRange::new(0, 0).contains(0));
+    }
+
+    #[rstest]
+    #[case::p0(0, "a/deep/nested/file.txt", "b/deep/nested/file.txt")]
+    #[case::p1(1, "deep/nested/file.txt", "deep/nested/file.txt")]
+    #[case::over_strip(10, "file.txt", "file.txt")]
+    fn strip_path_prefix(#[case] n: usize, #[case] original: &str, #[case] modified: &str) {
+        let patch = "\
+--- a/deep/nested/file.txt
++++ b/deep/nested/file.txt
+@@ -1 +1 @@
+-a
++b
+";
+        let config = || ParserConfig {
+            strip_ab_prefix: false,
+            ..Default::default()
+        };
+
+        let mut diff = patch_from_str_with_config(patch, config())
+            .unwrap()
+            .remove(0);
+        diff.strip_path_prefix(n);
+        assert_eq!(diff.original(), Some(original));
+        assert_eq!(diff.modified(), Some(modified));
+
+        let mut diff = patch_from_bytes_with_config(patch.as_bytes(), config())
+            .unwrap()
+            .remove(0);
+        diff.strip_path_prefix(n);
+        assert_eq!(diff.original(), Some(original.as_bytes()));
+
+        // Names which had to be unescaped are owned by the diff
+        let escaped = patch.replace("b/deep/nested/file.txt", "\"b/deep/nested/file\\t.txt\"");
+        let mut diff = patch_from_str_with_config(&escaped, config())
+            .unwrap()
+            .remove(0);
+        diff.strip_path_prefix(n);
+        let unescaped = modified.replace("file.txt", "file\t.txt");
+        assert_eq!(diff.modified(), Some(unescaped.as_str()));
+    }
+
+    #[test]
+    fn shift_ranges() {
+        let shifted = |start, len, delta| {
+            let mut range = HunkRange::new(start, len);
+            range.shift(delta);
+            (range.start(), range.len())
+        };
+        assert_eq!(shifted(5, 3, 4), (9, 3));
+        assert_eq!(shifted(5, 3, -4), (1, 3));
+        assert_eq!(shifted(5, 3, -10), (1, 3));
+        // Empty ranges can move to the start of the file
+        assert_eq!(shifted(5, 0, -5), (0, 0));
+        assert_eq!(shifted(5, 0, -10), (0, 0));
+        assert_eq!(shifted(0, 0, 2), (2, 0));
+
+        // A diff of lines 11 to 20 of a file moved onto the whole file
+        let old: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        let slice: String = (11..=20).map(|i| format!("{i}\n")).collect();
+        let mut diff =
+            Diff::from_str("@@ -2,2 +2,2 @@\n 12\n-13\n+thirteen\n@@ -9,0 +10 @@\n+20.5\n")
+                .unwrap();
+        let (slice_result, _) = crate::apply(&slice, &diff).unwrap();
+        diff.shift_all(10);
+        assert_eq!(
+            diff.to_string(),
+            "@@ -12,2 +12,2 @@\n 12\n-13\n+thirteen\n@@ -19,0 +20 @@\n+20.5\n"
+        );
+        let (result, _) = crate::apply(&old, &diff).unwrap();
+        assert!(result.ends_with(&slice_result));
+
+        diff.shift_all(-15);
+        assert_eq!(
+            diff.to_string(),
+            "@@ -1,2 +1,2 @@\n 12\n-13\n+thirteen\n@@ -4,0 +5 @@\n+20.5\n"
+        );
+    }
+}
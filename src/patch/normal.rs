@@ -0,0 +1,929 @@
+//! Parse and format patches in the normal diff format
+//!
+//! The normal format is the default output of `diff` without `-u` or `-c`. It has no file headers
+//! and no context lines, and every hunk is a single command:
+//!
+//! * `NaR` adds the lines `R` of the new file after line `N` of the old file
+//! * `RcR` changes the lines `R` of the old file into the lines `R` of the new file
+//! * `RdN` deletes the lines `R` of the old file, which would have followed line `N` of the new
+//!   file
+//!
+//! where a range `R` is either a single line number or two line numbers separated by a comma.
+//! Deleted lines follow the command prefixed by `< `, inserted lines prefixed by `> `, and change
+//! commands separate the two with a `---` line.
+
+use std::{
+    io,
+    iter::{Enumerate, Peekable},
+};
+
+use super::{Diff, Hunk, HunkRange, Line, NO_NEWLINE_AT_EOF, ParsePatchError};
+use crate::{
+    DiffOptions, LineEnd,
+    utils::{LineIter, Text, strip_bom},
+};
+
+type Result<T, E = ParsePatchError> = std::result::Result<T, E>;
+
+/// Parse a patch in the normal diff format, see [`Diff::to_normal_string`]
+///
+/// The returned `Diff` has no filenames, and its hunks have no context lines. Any line of the
+/// patch may end with either `\n` or `\r\n`, and the content lines keep their line endings.
+/// Errors are wrapped in [`ParsePatchError::Location`] with the line of the patch they were found
+/// at. A UTF-8 byte order mark at the start of the patch is ignored.
+///
+/// ```
+/// use flickzeug::{apply, parse_normal};
+///
+/// let diff = parse_normal("2c2\n< b\n---\n> B\n3a4\n> d\n").unwrap();
+/// assert_eq!(apply("a\nb\nc\n", &diff).unwrap().0, "a\nB\nc\nd\n");
+/// ```
+pub fn parse_normal(input: &str) -> Result<Diff<'_, str>> {
+    let hunks = parse_normal_hunks(input)?;
+    Ok(Diff::new(None::<&str>, None::<&str>, hunks))
+}
+
+/// Parse a potentially non-utf8 patch in the normal diff format, see [`parse_normal`]
+pub fn parse_normal_bytes(input: &[u8]) -> Result<Diff<'_, [u8]>> {
+    let hunks = parse_normal_hunks(input)?;
+    Ok(Diff::new(None::<&[u8]>, None::<&[u8]>, hunks))
+}
+
+/// Parse a patch in the normal diff format, collecting its errors instead of stopping at the
+/// first one, see [`parse_normal`]
+///
+/// After an error the lines up to the next command are skipped and parsing continues there. The
+/// hunks between two errors are returned as separate diffs, as the ones following an error can't
+/// be applied without the broken one. Each error is wrapped in [`ParsePatchError::Location`] with
+/// the line of the patch it was found at.
+///
+/// Change commands whose `---` separator was lost, e.g. when editing a patch by hand, are
+/// recovered by splitting their lines where the `> ` lines start. The hunk is kept, and a
+/// [`ParsePatchError::MissingSeparator`] at the line the separator should be at is returned as a
+/// warning along with the errors.
+///
+/// ```
+/// use flickzeug::{ParsePatchError, parse_normal_lenient};
+///
+/// let (diffs, errors) = parse_normal_lenient("1c1\n< a\n---\n> A\n2x\n3a4\n> d\n");
+/// assert_eq!(diffs.len(), 2);
+/// assert_eq!(errors[0].line(), Some(5));
+///
+/// let (diffs, warnings) = parse_normal_lenient("1c1\n< a\n> A\n");
+/// assert_eq!(diffs[0].to_normal_string(), "1c1\n< a\n---\n> A\n");
+/// assert!(matches!(warnings[0].kind(), ParsePatchError::MissingSeparator));
+/// ```
+pub fn parse_normal_lenient(input: &str) -> (Vec<Diff<'_, str>>, Vec<ParsePatchError>) {
+    let (runs, errors) = parse_normal_hunks_lenient(input);
+    let diffs = runs
+        .into_iter()
+        .map(|hunks| Diff::new(None::<&str>, None::<&str>, hunks))
+        .collect();
+    (diffs, errors)
+}
+
+/// Parse a potentially non-utf8 patch in the normal diff format, collecting its errors, see
+/// [`parse_normal_lenient`]
+pub fn parse_normal_bytes_lenient(input: &[u8]) -> (Vec<Diff<'_, [u8]>>, Vec<ParsePatchError>) {
+    let (runs, errors) = parse_normal_hunks_lenient(input);
+    let diffs = runs
+        .into_iter()
+        .map(|hunks| Diff::new(None::<&[u8]>, None::<&[u8]>, hunks))
+        .collect();
+    (diffs, errors)
+}
+
+/// Returns `true` if the first non-empty line of `input` is a normal diff command like `3,5c3`
+///
+/// ```
+/// use flickzeug::is_normal_diff;
+///
+/// assert!(is_normal_diff("\n2c2\n< b\n---\n> B\n"));
+/// assert!(!is_normal_diff("@@ -2 +2 @@\n-b\n+B\n"));
+/// ```
+pub fn is_normal_diff<T: Text + ?Sized>(input: &T) -> bool {
+    LineIter::new(strip_bom(input))
+        .map(|(line, _)| line)
+        .find(|line| !line.is_empty())
+        .is_some_and(|line| command_line(line).is_ok())
+}
+
+/// Create a normal diff between two texts, like running `diff` without any options
+///
+/// The returned `Diff` has no filenames and no context lines, so every hunk corresponds to one
+/// `a`, `c` or `d` command. Lines are added after a line of the old file, so lines inserted at
+/// the very top of the file produce a `0a` command.
+///
+/// ```
+/// use flickzeug::{apply, diff_normal};
+///
+/// let diff = diff_normal("b\nc\n", "a\nb\n");
+/// assert_eq!(diff.to_normal_string(), "0a1\n> a\n2d2\n< c\n");
+/// assert_eq!(apply("b\nc\n", &diff).unwrap().0, "a\nb\n");
+/// ```
+pub fn diff_normal<'a>(old: &'a str, new: &'a str) -> Diff<'a, str> {
+    let diff = DiffOptions::new().set_context_len(0).create_patch(old, new);
+    Diff::new(None::<&str>, None::<&str>, diff.hunks)
+}
+
+/// Create a normal diff between two potentially non-utf8 texts, see [`diff_normal`]
+pub fn diff_normal_bytes<'a>(old: &'a [u8], new: &'a [u8]) -> Diff<'a, [u8]> {
+    let diff = DiffOptions::new()
+        .set_context_len(0)
+        .create_patch_bytes(old, new);
+    Diff::new(None::<&[u8]>, None::<&[u8]>, diff.hunks)
+}
+
+/// The kind of a normal diff command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Add,
+    Change,
+    Delete,
+}
+
+/// The lines of a patch along with their 0-based index
+type Lines<'a, T> = Peekable<Enumerate<LineIter<'a, T>>>;
+
+/// Parse the hunks of a normal diff, reporting the line of the patch an error was found at as
+/// [`ParsePatchError::Location`]
+fn parse_normal_hunks<'a, T: Text + ToOwned + ?Sized>(input: &'a T) -> Result<Vec<Hunk<'a, T>>> {
+    let mut lines = LineIter::new(strip_bom(input)).enumerate().peekable();
+    let mut hunks = Vec::new();
+
+    while let Some((index, (line, _))) = lines.next() {
+        let hunk = hunk(&mut lines, index, line, None)?;
+        push_hunk(&mut hunks, hunk, index)?;
+    }
+
+    Ok(hunks)
+}
+
+/// Parse the hunks of a normal diff like [`parse_normal_hunks`], but skip to the next command after
+/// an error, returning the runs of hunks between the errors along with the errors
+fn parse_normal_hunks_lenient<'a, T: Text + ToOwned + ?Sized>(
+    input: &'a T,
+) -> (Vec<Vec<Hunk<'a, T>>>, Vec<ParsePatchError>) {
+    let mut lines = LineIter::new(strip_bom(input)).enumerate().peekable();
+    let mut runs = vec![Vec::new()];
+    let mut errors = Vec::new();
+
+    while let Some((index, (line, _))) = lines.next() {
+        let hunks = runs.last_mut().unwrap();
+        let result = hunk(&mut lines, index, line, Some(&mut errors))
+            .and_then(|hunk| push_hunk(hunks, hunk, index));
+        if let Err(error) = result {
+            errors.push(error);
+            if !hunks.is_empty() {
+                runs.push(Vec::new());
+            }
+            while lines
+                .peek()
+                .is_some_and(|(_, (line, _))| command_line(*line).is_err())
+            {
+                lines.next();
+            }
+        }
+    }
+
+    runs.retain(|hunks| !hunks.is_empty());
+    (runs, errors)
+}
+
+/// Parse the hunk of the command `line`, which is the line at `index` of the patch
+///
+/// With `warnings`, a missing `---` separator of a change command is recovered from and added to
+/// them, see [`parse_normal_lenient`].
+fn hunk<'a, T: Text + ToOwned + ?Sized>(
+    lines: &mut Lines<'a, T>,
+    index: usize,
+    line: &'a T,
+    warnings: Option<&mut Vec<ParsePatchError>>,
+) -> Result<Hunk<'a, T>> {
+    let at_command = |error: ParsePatchError| error.at_line(index + 1);
+    let (command, old_range, new_range) = command_line(line).map_err(at_command)?;
+
+    // Either side of a change may be missing entirely, which the line counts catch, as the
+    // lines of one side never extend past the separator or into the next command
+    let mut hunk_lines = Vec::new();
+    if command != Command::Add {
+        content_lines(lines, "<", Line::Delete, &mut hunk_lines);
+        check_line_count(old_range.len(), hunk_lines.len()).map_err(at_command)?;
+    }
+    if command == Command::Change {
+        match lines.peek() {
+            Some((_, (separator, _))) if separator.as_bytes() == b"---" => {
+                lines.next();
+            }
+            // The inserted lines following the deleted ones right away mark where the separator
+            // should have been
+            Some(&(index, (next, _))) if next.starts_with(">") && warnings.is_some() => {
+                if let Some(warnings) = warnings {
+                    warnings.push(ParsePatchError::MissingSeparator.at_line(index + 1));
+                }
+            }
+            Some(&(index, _)) => {
+                lines.next();
+                return Err(ParsePatchError::HunkHeader.at_line(index + 1));
+            }
+            None => return Err(at_command(ParsePatchError::HunkHeader)),
+        }
+    }
+    if command != Command::Delete {
+        let deleted = hunk_lines.len();
+        content_lines(lines, ">", Line::Insert, &mut hunk_lines);
+        check_line_count(new_range.len(), hunk_lines.len() - deleted).map_err(at_command)?;
+    }
+
+    Ok(Hunk::new(old_range, new_range, None, hunk_lines))
+}
+
+/// Add `hunk`, whose command is the line at `index` of the patch, to `hunks`, checking that it
+/// follows the previous one
+fn push_hunk<'a, T: Text + ToOwned + ?Sized>(
+    hunks: &mut Vec<Hunk<'a, T>>,
+    hunk: Hunk<'a, T>,
+    index: usize,
+) -> Result<()> {
+    hunks.push(hunk);
+    if !super::parse::verify_hunks_in_order(&hunks[hunks.len().saturating_sub(2)..]) {
+        hunks.pop();
+        return Err(ParsePatchError::HunksOrder.at_line(index + 1));
+    }
+    Ok(())
+}
+
+/// Check that a command is followed by as many lines as its range covers
+fn check_line_count(expected: usize, found: usize) -> Result<()> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(ParsePatchError::HunkLineCountMismatch { expected, found })
+    }
+}
+
+/// Parse a command line like `3,5c3`
+fn command_line<T: Text + ?Sized>(line: &T) -> Result<(Command, HunkRange, HunkRange)> {
+    let line = line.as_bytes();
+    let position = line
+        .iter()
+        .position(|b| matches!(b, b'a' | b'c' | b'd'))
+        .ok_or(ParsePatchError::HunkHeader)?;
+    let command = match line[position] {
+        b'a' => Command::Add,
+        b'c' => Command::Change,
+        _ => Command::Delete,
+    };
+    let old = range(&line[..position])?;
+    let new = range(&line[position + 1..])?;
+
+    // The side without lines is given as the line the others are inserted after
+    let after = |(start, end): (usize, usize)| {
+        if start == end {
+            Ok(HunkRange::new(start, 0))
+        } else {
+            Err(ParsePatchError::HunkHeader)
+        }
+    };
+    // Line 0 is only valid as the line others are inserted after, buggy generators emit
+    // degenerate commands like `0a0` or `0d0` which would produce inconsistent hunks
+    let lines = |(start, end): (usize, usize)| {
+        if start == 0 {
+            Err(ParsePatchError::EmptyCommand)
+        } else {
+            Ok(HunkRange::new(start, end - start + 1))
+        }
+    };
+
+    let (old_range, new_range) = match command {
+        Command::Add => (after(old)?, lines(new)?),
+        Command::Change => (lines(old)?, lines(new)?),
+        Command::Delete => (lines(old)?, after(new)?),
+    };
+    Ok((command, old_range, new_range))
+}
+
+/// Parse a range like `3` or `3,5` into its first and last line
+///
+/// Ranges ending at the largest representable line are rejected, so computing their length or
+/// the line following them can't overflow.
+pub(super) fn range(range: &[u8]) -> Result<(usize, usize)> {
+    let number = |s: &[u8]| {
+        std::str::from_utf8(s)
+            .ok()
+            .filter(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|s| s.parse().ok())
+            .ok_or(ParsePatchError::Range)
+    };
+
+    let (start, end) = match range.iter().position(|&b| b == b',') {
+        Some(comma) => {
+            let (start, end) = (number(&range[..comma])?, number(&range[comma + 1..])?);
+            if end < start {
+                return Err(ParsePatchError::ReversedRange { start, end });
+            }
+            (start, end)
+        }
+        None => {
+            let line = number(range)?;
+            (line, line)
+        }
+    };
+    if end == usize::MAX {
+        return Err(ParsePatchError::Range);
+    }
+    Ok((start, end))
+}
+
+/// Read the lines starting with `marker` followed by a space, along with their "No newline at end
+/// of file" markers
+fn content_lines<'a, T: Text + ?Sized>(
+    lines: &mut Lines<'a, T>,
+    marker: &str,
+    kind: fn((&'a T, Option<LineEnd>)) -> Line<'a, T>,
+    hunk_lines: &mut Vec<Line<'a, T>>,
+) {
+    while let Some(&(_, (line, end))) = lines.peek() {
+        let Some(rest) = line.strip_prefix(marker) else {
+            break;
+        };
+        let content = match rest.strip_prefix(" ") {
+            Some(content) => content,
+            // Some tools strip the trailing space of empty lines
+            None if rest.is_empty() => rest,
+            None => break,
+        };
+        lines.next();
+
+        let end = match lines.peek() {
+            Some((_, (next, _))) if next.starts_with(NO_NEWLINE_AT_EOF) => {
+                lines.next();
+                None
+            }
+            _ => end,
+        };
+        hunk_lines.push(kind((content, end)));
+    }
+}
+
+impl<T: AsRef<[u8]> + ToOwned + ?Sized> Diff<'_, T> {
+    /// Convert a `Diff` into a patch in the normal diff format, see [`Diff::to_normal_string`]
+    pub fn to_normal_bytes(&self) -> Vec<u8> {
+        self.to_normal_bytes_with_terminator(LineEnd::Lf)
+    }
+
+    /// Convert a `Diff` into a patch in the normal diff format, terminating the command lines,
+    /// the `---` separators and the "No newline at end of file" markers with `line_terminator`
+    ///
+    /// The content lines keep their own line endings. Like `diff`, [`Diff::to_normal_bytes`] uses
+    /// [`LineEnd::Lf`], while [`LineEnd::CrLf`] reproduces patches whose lines were all converted
+    /// to Windows line endings.
+    ///
+    /// ```
+    /// use flickzeug::{LineEnd, parse_normal_bytes};
+    ///
+    /// let patch = b"1c1\r\n< a\r\n---\r\n> b\r\n";
+    /// let diff = parse_normal_bytes(patch).unwrap();
+    /// assert_eq!(diff.to_normal_bytes(), b"1c1\n< a\r\n---\n> b\r\n");
+    /// assert_eq!(diff.to_normal_bytes_with_terminator(LineEnd::CrLf), patch);
+    /// ```
+    pub fn to_normal_bytes_with_terminator(&self, line_terminator: LineEnd) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_normal_into(&mut bytes, line_terminator).unwrap();
+        bytes
+    }
+
+    fn write_normal_into<W: io::Write>(
+        &self,
+        mut w: W,
+        line_terminator: LineEnd,
+    ) -> io::Result<()> {
+        let terminator: &[u8] = line_terminator.into();
+        for hunk in &self.hunks {
+            let mut old_index = hunk.old_range.index();
+            let mut new_index = hunk.new_range.index();
+
+            // Normal diffs have no context, so every block of changes becomes its own command
+            let is_context = |line: &Line<'_, T>| matches!(line, Line::Context(_));
+            for block in hunk.lines.chunk_by(|a, b| is_context(a) == is_context(b)) {
+                let (old_len, new_len) = super::hunk_lines_count(block);
+                if !is_context(&block[0]) {
+                    write_command(&mut w, old_index, old_len, new_index, new_len)?;
+                    w.write_all(terminator)?;
+
+                    let deleted = block.iter().filter(|line| matches!(line, Line::Delete(_)));
+                    for line in deleted {
+                        write_line(&mut w, "< ", line, terminator)?;
+                    }
+                    if old_len > 0 && new_len > 0 {
+                        w.write_all(b"---")?;
+                        w.write_all(terminator)?;
+                    }
+                    let inserted = block.iter().filter(|line| matches!(line, Line::Insert(_)));
+                    for line in inserted {
+                        write_line(&mut w, "> ", line, terminator)?;
+                    }
+                }
+                old_index += old_len;
+                new_index += new_len;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Diff<'_, str> {
+    /// Convert a `Diff` into a patch in the normal diff format, like the default output of `diff`
+    ///
+    /// The command ranges are computed from the ranges and lines of the hunks. Context lines
+    /// can't be represented in the normal format and are left out, splitting a hunk into one
+    /// command per block of changes. Filenames are left out as well.
+    ///
+    /// ```
+    /// use flickzeug::create_patch;
+    ///
+    /// let diff = create_patch("a\nb\nc\n", "a\nB\nc\nd\n");
+    /// assert_eq!(diff.to_normal_string(), "2c2\n< b\n---\n> B\n3a4\n> d\n");
+    /// ```
+    pub fn to_normal_string(&self) -> String {
+        String::from_utf8(self.to_normal_bytes()).unwrap()
+    }
+}
+
+/// Write the command line of a block of changes starting after `old_index` lines of the old and
+/// `new_index` lines of the new file, without a line ending
+fn write_command<W: io::Write>(
+    w: &mut W,
+    old_index: usize,
+    old_len: usize,
+    new_index: usize,
+    new_len: usize,
+) -> io::Result<()> {
+    let range = |index: usize, len: usize| match len {
+        0 => index.to_string(),
+        1 => (index + 1).to_string(),
+        _ => format!("{},{}", index + 1, index + len),
+    };
+    let command = match (old_len, new_len) {
+        (0, _) => 'a',
+        (_, 0) => 'd',
+        _ => 'c',
+    };
+    write!(
+        w,
+        "{}{command}{}",
+        range(old_index, old_len),
+        range(new_index, new_len)
+    )
+}
+
+fn write_line<T: AsRef<[u8]> + ToOwned + ?Sized, W: io::Write>(
+    w: &mut W,
+    prefix: &str,
+    line: &Line<'_, T>,
+    terminator: &[u8],
+) -> io::Result<()> {
+    let (Line::Context((content, end))
+    | Line::Delete((content, end))
+    | Line::Insert((content, end))) = line;
+    w.write_all(prefix.as_bytes())?;
+    w.write_all(content.as_ref())?;
+    match end {
+        Some(end) => w.write_all((*end).into()),
+        None => {
+            w.write_all(terminator)?;
+            w.write_all(NO_NEWLINE_AT_EOF.as_bytes())?;
+            w.write_all(terminator)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rstest::rstest;
+
+    use super::{
+        diff_normal, diff_normal_bytes, parse_normal, parse_normal_bytes,
+        parse_normal_bytes_lenient, parse_normal_lenient,
+    };
+    use crate::{
+        Diff, HunkRange, Line, LineEnd, ParsePatchError, apply, apply_bytes, create_patch,
+    };
+
+    fn load_fixture(name: &str) -> (String, String, String) {
+        let folder = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join("normal-diff")
+            .join(name);
+        let read = |file| std::fs::read_to_string(folder.join(file)).unwrap();
+        (read("target.txt"), read("patch.diff"), read("result.txt"))
+    }
+
+    #[test]
+    fn fixtures_round_trip() {
+        for name in ["add", "change", "delete", "mixed"] {
+            let (target, patch, result) = load_fixture(name);
+
+            let diff = parse_normal(&patch).unwrap();
+            assert_eq!(diff.to_normal_string(), patch, "{name}");
+            assert_eq!(apply(&target, &diff).unwrap().0, result, "{name}");
+
+            // The normal diff of the texts is the same as the one generated by `diff`
+            assert_eq!(create_patch(&target, &result).to_normal_string(), patch);
+
+            let diff = parse_normal_bytes(patch.as_bytes()).unwrap();
+            assert_eq!(diff.to_normal_bytes(), patch.as_bytes(), "{name}");
+        }
+    }
+
+    #[test]
+    fn parse_commands() {
+        let diff = parse_normal("0a1,2\n> a\n> b\n3,4d4\n< c\n< d\n").unwrap();
+        let hunks = diff.hunks();
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(
+            (
+                hunks[0].old_range().to_string(),
+                hunks[0].new_range().to_string()
+            ),
+            ("0,0".to_owned(), "1,2".to_owned())
+        );
+        assert_eq!(
+            (
+                hunks[1].old_range().to_string(),
+                hunks[1].new_range().to_string()
+            ),
+            ("3,2".to_owned(), "4,0".to_owned())
+        );
+        assert_eq!(
+            hunks[1].lines(),
+            [
+                Line::Delete(("c", Some(LineEnd::Lf))),
+                Line::Delete(("d", Some(LineEnd::Lf))),
+            ]
+        );
+
+        // Missing separator, bad ranges and commands
+        for patch in [
+            "1c1\n< a\n> b\n",
+            "1,2a3\n> a\n",
+            "2,1d0\n< a\n",
+            "1x1\n",
+            "a1\n",
+        ] {
+            assert!(parse_normal(patch).is_err(), "{patch:?}");
+        }
+        let error = parse_normal("3d2\n< c\n1d0\n< a\n").unwrap_err();
+        assert!(matches!(error.kind(), ParsePatchError::HunksOrder));
+        assert_eq!(error.line(), Some(3));
+    }
+
+    #[test]
+    fn error_locations() {
+        let patch = "1c1\n< a\n---\n> b\n3x3\n< c\n";
+        let error = parse_normal(patch).unwrap_err();
+        assert!(matches!(error.kind(), ParsePatchError::HunkHeader));
+        assert_eq!(error.line(), Some(5));
+        assert_eq!(error.to_string(), "line 5: unable to parse hunk header");
+
+        // The line where the separator should be
+        let error = parse_normal("0a1\n> a\n2,3c3\n< b\n< c\n> B\n").unwrap_err();
+        assert!(matches!(error.kind(), ParsePatchError::HunkHeader));
+        assert_eq!(error.line(), Some(6));
+
+        // Bad ranges and line counts are reported at their command
+        let error = parse_normal("1d0\n< a\n3,2d1\n").unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ParsePatchError::ReversedRange { start: 3, end: 2 }
+        ));
+        assert_eq!(error.line(), Some(3));
+        let error = parse_normal("1d0\n< a\n3,4d1\n< c\n").unwrap_err();
+        assert_eq!(error.line(), Some(3));
+
+        // A separator missing at the end of the patch is reported at its command
+        let error = parse_normal("1d0\n< a\n3c2\n< c\n").unwrap_err();
+        assert_eq!(error.line(), Some(3));
+    }
+
+    #[test]
+    fn descending_ranges() {
+        for patch in ["5,3c5\n< a\n---\n> b\n", "1c5,3\n< a\n---\n> b\n"] {
+            let error = parse_normal(patch).unwrap_err();
+            assert_eq!(error.line(), Some(1), "{patch:?}");
+            assert!(
+                matches!(
+                    error.kind(),
+                    ParsePatchError::ReversedRange { start: 5, end: 3 }
+                ),
+                "{patch:?}"
+            );
+            assert!(parse_normal_bytes(patch.as_bytes()).is_err());
+        }
+        assert_eq!(
+            parse_normal("5,3c5\n").unwrap_err().to_string(),
+            "line 1: invalid range 5,3: the last line is before the first"
+        );
+    }
+
+    #[test]
+    fn huge_ranges_dont_overflow() {
+        let max = usize::MAX;
+        for patch in [
+            format!("5a{max}\n> x\n6a{max}\n> y\n"),
+            format!("{max}c{max}\n< a\n---\n> b\n"),
+            format!("1,{max}d0\n< a\n"),
+        ] {
+            let error = parse_normal(&patch).unwrap_err();
+            assert!(matches!(error.kind(), ParsePatchError::Range), "{patch:?}");
+            assert!(parse_normal_bytes(patch.as_bytes()).is_err());
+            assert!(crate::parse_auto(&patch).is_err());
+        }
+    }
+
+    #[test]
+    fn empty_commands() {
+        for patch in [
+            "0a0\n",
+            "0a0\n> a\n",
+            "0d0\n",
+            "0d0\n< a\n",
+            "0c1\n< a\n---\n> b\n",
+        ] {
+            let error = parse_normal(patch).unwrap_err();
+            assert!(
+                matches!(error.kind(), ParsePatchError::EmptyCommand),
+                "{patch:?}"
+            );
+            assert_eq!(error.line(), Some(1), "{patch:?}");
+            assert!(parse_normal_bytes(patch.as_bytes()).is_err(), "{patch:?}");
+        }
+
+        let (diffs, errors) = parse_normal_lenient("0a0\n2d1\n< b\n");
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].hunks()[0].old_range(), HunkRange::new(2, 1));
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0].kind(), ParsePatchError::EmptyCommand));
+    }
+
+    #[test]
+    fn line_spans() {
+        let patch = "2c2\n< old\n---\n> new\n";
+        let diff = parse_normal(patch).unwrap();
+        let spans: Vec<_> = diff
+            .lines()
+            .map(|line| line.span_in(patch).unwrap())
+            .collect();
+        assert_eq!(spans, [6..9, 16..19]);
+        assert_eq!(&patch[spans[0].clone()], "old");
+        assert_eq!(&patch[spans[1].clone()], "new");
+
+        let diff = parse_normal_bytes(patch.as_bytes()).unwrap();
+        let spans: Vec<_> = diff
+            .lines()
+            .map(|line| line.span_in(patch.as_bytes()).unwrap())
+            .collect();
+        assert_eq!(spans, [6..9, 16..19]);
+
+        // Lines which aren't part of the given text have no span in it
+        let other = patch.to_owned();
+        assert_eq!(diff.lines().next().unwrap().span_in(other.as_bytes()), None);
+    }
+
+    #[test]
+    fn line_count_mismatch() {
+        for (patch, expected, found) in [
+            ("0a1,5\n> a\n> b\n", 5, 2),
+            ("0a1\n> a\n> b\n", 1, 2),
+            ("1,3d0\n< a\n", 3, 1),
+            ("1d0\n< a\n< b\n2a3\n> c\n", 1, 2),
+            ("1,2c1\n< a\n---\n> b\n", 2, 1),
+            ("1c1,3\n< a\n---\n> b\n> c\n", 3, 2),
+            ("1c1\n< a\n---\n", 1, 0),
+        ] {
+            let error = parse_normal(patch).unwrap_err();
+            assert!(
+                matches!(
+                    *error.kind(),
+                    ParsePatchError::HunkLineCountMismatch { expected: e, found: f }
+                        if (e, f) == (expected, found)
+                ),
+                "{patch:?}: {error:?}"
+            );
+        }
+        // Changes without deleted or inserted lines
+        for (patch, expected) in [
+            ("2c2,3\n---\n> a\n> b\n", (1, 0)),
+            ("2,3c2\n< a\n< b\n---\n", (1, 0)),
+            ("1c1\n< a\n---\n2d1\n< b\n", (1, 0)),
+        ] {
+            let error = parse_normal(patch).unwrap_err();
+            assert!(
+                matches!(
+                    *error.kind(),
+                    ParsePatchError::HunkLineCountMismatch { expected: e, found: f }
+                        if (e, f) == expected
+                ),
+                "{patch:?}: {error:?}"
+            );
+        }
+        // A change without its separator is malformed
+        let error = parse_normal("2c2\n< a\n> b\n").unwrap_err();
+        assert!(matches!(error.kind(), ParsePatchError::HunkHeader));
+        assert!(parse_normal("2c2,3\n> a\n> b\n").is_err());
+
+        assert_eq!(
+            parse_normal("0a1,5\n> a\n").unwrap_err().to_string(),
+            "line 1: expected 5 lines in hunk but found 1"
+        );
+    }
+
+    #[test]
+    fn missing_newline() {
+        let patch =
+            "2c2\n< b\n\\ No newline at end of file\n---\n> B\n\\ No newline at end of file\n";
+        let diff = parse_normal(patch).unwrap();
+        assert_eq!(
+            diff.hunks()[0].lines(),
+            [Line::Delete(("b", None)), Line::Insert(("B", None))]
+        );
+        assert_eq!(diff.to_normal_string(), patch);
+        assert_eq!(apply("a\nb", &diff).unwrap().0, "a\nB");
+
+        // Removing and adding the final newline
+        let patch = "2c2\n< b\n---\n> b\n\\ No newline at end of file\n";
+        let diff = parse_normal(patch).unwrap();
+        assert_eq!(apply("a\nb\n", &diff).unwrap().0, "a\nb");
+        assert_eq!(apply("a\nb", &diff.reverse()).unwrap().0, "a\nb\n");
+        assert_eq!(diff_normal("a\nb\n", "a\nb").to_normal_string(), patch);
+
+        // The marker only applies to the line right before it
+        let patch = "1,2c1\n< a\n< b\n\\ No newline at end of file\n---\n> a b\n";
+        let diff = parse_normal(patch).unwrap();
+        assert_eq!(
+            diff.hunks()[0].lines(),
+            [
+                Line::Delete(("a", Some(LineEnd::Lf))),
+                Line::Delete(("b", None)),
+                Line::Insert(("a b", Some(LineEnd::Lf))),
+            ]
+        );
+        assert_eq!(apply("a\nb", &diff).unwrap().0, "a b\n");
+        assert!(parse_normal("\\ No newline at end of file\n").is_err());
+    }
+
+    #[test]
+    fn crlf() {
+        let (target, patch, result) = load_fixture("mixed-endings");
+        let diff = parse_normal(&patch).unwrap();
+        assert_eq!(
+            diff.hunks()[0].lines(),
+            [
+                Line::Delete(("second", Some(LineEnd::CrLf))),
+                Line::Insert(("SECOND", Some(LineEnd::CrLf))),
+            ]
+        );
+        assert_eq!(
+            diff.hunks()[2].lines(),
+            [Line::Delete(("sixth", Some(LineEnd::Lf)))]
+        );
+        assert_eq!(diff.to_normal_string(), patch);
+        assert_eq!(create_patch(&target, &result).to_normal_string(), patch);
+
+        // Applying uses the most common line ending of the target for all lines
+        let crlf = result.replace("\r\n", "\n").replace('\n', "\r\n");
+        assert_eq!(apply(&target, &diff).unwrap().0, crlf);
+
+        // A patch whose lines were all converted to CRLF
+        let patch = "\
+2c2\r
+< b\r
+\\ No newline at end of file\r
+---\r
+> B\r
+3a4\r
+> c\r
+";
+        let diff = parse_normal_bytes(patch.as_bytes()).unwrap();
+        assert_eq!(
+            diff.to_normal_bytes_with_terminator(LineEnd::CrLf),
+            patch.as_bytes()
+        );
+        assert_eq!(
+            apply_bytes(b"a\r\nb".as_slice(), &diff).unwrap().0,
+            b"a\r\nB\r\nc\r\n"
+        );
+        assert_eq!(
+            diff.to_normal_bytes(),
+            b"2c2\n< b\n\\ No newline at end of file\n---\n> B\r\n3a4\n> c\r\n"
+        );
+    }
+
+    #[test]
+    fn context_lines_are_left_out() {
+        let diff = Diff::from_str("@@ -1,4 +1,4 @@\n-a\n+A\n b\n c\n-d\n+D\n").unwrap();
+        assert_eq!(
+            diff.to_normal_string(),
+            "1c1\n< a\n---\n> A\n4c4\n< d\n---\n> D\n"
+        );
+    }
+
+    #[rstest]
+    #[case::insert_at_start("c\nd\n", "a\nb\nc\nd\n", "0a1,2\n> a\n> b\n")]
+    #[case::delete_at_end("a\nb\nc\nd\n", "a\nb\n", "3,4d2\n< c\n< d\n")]
+    #[case::interleaved(
+        "a\nb\nc\nd\ne\nf\n",
+        "a\nB\nc\nx\nd\nf\ng",
+        "2c2\n< b\n---\n> B\n3a4\n> x\n5d5\n< e\n6a7\n> g\n\\ No newline at end of file\n"
+    )]
+    #[case::empty_old("", "a\nb\n", "0a1,2\n> a\n> b\n")]
+    #[case::empty_new("a\nb\n", "", "1,2d0\n< a\n< b\n")]
+    #[case::equal("a\nb\n", "a\nb\n", "")]
+    fn diff_round_trip(#[case] old: &str, #[case] new: &str, #[case] expected: &str) {
+        let diff = diff_normal(old, new);
+        assert!(diff.hunks().iter().all(|hunk| {
+            hunk.lines()
+                .iter()
+                .all(|line| !matches!(line, Line::Context(_)))
+        }));
+        assert_eq!(diff.to_normal_string(), expected);
+        assert_eq!(apply(old, &diff).unwrap().0, new);
+
+        let parsed = parse_normal(expected).unwrap();
+        assert_eq!(apply(old, &parsed).unwrap().0, new);
+
+        let diff = diff_normal_bytes(old.as_bytes(), new.as_bytes());
+        assert_eq!(diff.to_normal_bytes(), expected.as_bytes());
+        assert_eq!(
+            apply_bytes(old.as_bytes(), &diff).unwrap().0,
+            new.as_bytes()
+        );
+    }
+
+    #[test]
+    fn lenient_skips_broken_hunk() {
+        let patch = "1c1\n< a\n---\n> A\n3,4c3\n< c\n---\n> C\n5a6\n> f\n";
+        let (diffs, errors) = parse_normal_lenient(patch);
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line(), Some(5));
+        assert!(matches!(
+            errors[0].kind(),
+            ParsePatchError::HunkLineCountMismatch {
+                expected: 2,
+                found: 1
+            }
+        ));
+        assert_eq!(diffs[0].to_normal_string(), "1c1\n< a\n---\n> A\n");
+        assert_eq!(diffs[1].to_normal_string(), "5a6\n> f\n");
+
+        let (diffs, errors) = parse_normal_bytes_lenient(patch.as_bytes());
+        assert_eq!((diffs.len(), errors.len()), (2, 1));
+
+        let (diffs, errors) = parse_normal_lenient(&patch[..patch.find("3,4c3").unwrap()]);
+        assert_eq!((diffs.len(), errors.len()), (1, 0));
+    }
+
+    #[test]
+    fn lenient_recovers_missing_separator() {
+        let patch = "1c1\n< a\n> A\n3,4c3\n< c\n< d\n> C\n5a5\n> f\n";
+        assert!(matches!(
+            parse_normal(patch).unwrap_err().kind(),
+            ParsePatchError::HunkHeader
+        ));
+
+        // The hunks are kept in a single diff, along with a warning for every missing separator
+        let (diffs, warnings) = parse_normal_lenient(patch);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            diffs[0].to_normal_string(),
+            "1c1\n< a\n---\n> A\n3,4c3\n< c\n< d\n---\n> C\n5a5\n> f\n"
+        );
+        let lines: Vec<_> = warnings.iter().map(ParsePatchError::line).collect();
+        assert_eq!(lines, [Some(3), Some(7)]);
+        assert!(
+            warnings
+                .iter()
+                .all(|warning| matches!(warning.kind(), ParsePatchError::MissingSeparator))
+        );
+        assert_eq!(
+            apply("a\nb\nc\nd\ne\n", &diffs[0]).unwrap().0,
+            "A\nb\nC\ne\nf\n"
+        );
+
+        let (diffs, warnings) = parse_normal_bytes_lenient(patch.as_bytes());
+        assert_eq!((diffs.len(), warnings.len()), (1, 2));
+
+        // Line counts are still checked, and other lines in place of the separator are errors
+        let (diffs, errors) = parse_normal_lenient("1,2c1\n< a\n> A\n");
+        assert!(diffs.is_empty());
+        assert!(matches!(
+            errors[0].kind(),
+            ParsePatchError::HunkLineCountMismatch { .. }
+        ));
+        let (_, errors) = parse_normal_lenient("1c1\n< a\nb\n> A\n");
+        assert!(matches!(errors[0].kind(), ParsePatchError::HunkHeader));
+    }
+}
@@ -1,10 +1,10 @@
 //! Parse a Patch
 
-use super::{ESCAPED_CHARS_BYTES, Hunk, HunkRange, Line, NO_NEWLINE_AT_EOF};
+use super::{ESCAPED_CHARS_BYTES, Filename, Hunk, HunkRange, Line, NO_NEWLINE_AT_EOF};
 use crate::{
     LineEnd,
     patch::Diff,
-    utils::{LineIter, Text},
+    utils::{LineIter, Text, strip_bom},
 };
 use std::{borrow::Cow, fmt};
 
@@ -75,6 +75,57 @@ pub enum ParsePatchError {
     UnexpectedLineInHunkBody,
     #[error("missing newline")]
     MissingNewline,
+    #[error("unable to detect the format of the patch")]
+    UnknownFormat,
+    #[error("expected {expected} lines in hunk but found {found}")]
+    HunkLineCountMismatch { expected: usize, found: usize },
+    /// A hunk header at the given 1-based line of the patch whose range ends past the largest
+    /// representable line number
+    #[error("hunk range at line {line} overflows")]
+    InvalidRange { line: usize },
+    /// A normal diff range like `5,3` whose last line is before its first
+    #[error("invalid range {start},{end}: the last line is before the first")]
+    ReversedRange { start: usize, end: usize },
+    /// A normal diff command like `0a0` or `0d0` whose lines start at line 0, which doesn't exist
+    #[error("command changes line 0, which doesn't exist")]
+    EmptyCommand,
+    /// A normal diff change command whose inserted lines directly follow its deleted lines,
+    /// without the `---` separator between them
+    ///
+    /// [`parse_normal_lenient`](crate::parse_normal_lenient) recovers from this, keeping the hunk.
+    #[error("change command is missing its '---' separator")]
+    MissingSeparator,
+    /// An error found at the given 1-based line of the patch
+    #[error("line {line}: {error}")]
+    Location {
+        line: usize,
+        error: Box<ParsePatchError>,
+    },
+}
+
+impl ParsePatchError {
+    /// Returns the 1-based line of the patch the error was found at, if known
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            ParsePatchError::Location { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without the line it was found at
+    pub fn kind(&self) -> &ParsePatchError {
+        match self {
+            ParsePatchError::Location { error, .. } => error.kind(),
+            error => error,
+        }
+    }
+
+    pub(super) fn at_line(self, line: usize) -> Self {
+        ParsePatchError::Location {
+            line,
+            error: Box::new(self),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -103,7 +154,8 @@ pub struct ParserConfig {
     pub hunk_strategy: HunkRangeStrategy,
     /// Skip verification that hunks are in order and don't overlap.
     /// Useful for parsing malformed patches where hunk header line numbers
-    /// are incorrect but the patch content is still valid.
+    /// are incorrect but the patch content is still valid. The hunks of such
+    /// patches aren't checked for overlaps when applying them either.
     pub skip_order_check: bool,
     /// Strip conventional `a/` and `b/` prefixes from filenames.
     ///
@@ -130,6 +182,8 @@ impl Default for ParserConfig {
 struct Parser<'a, T: Text + ?Sized> {
     lines: std::iter::Peekable<LineIter<'a, T>>,
     config: ParserConfig,
+    /// The number of lines read so far
+    line: usize,
 }
 
 impl<'a, T: Text + ?Sized> Parser<'a, T> {
@@ -139,8 +193,9 @@ impl<'a, T: Text + ?Sized> Parser<'a, T> {
 
     fn with_config(input: &'a T, config: ParserConfig) -> Self {
         Self {
-            lines: LineIter::new(input).peekable(),
+            lines: LineIter::new(strip_bom(input)).peekable(),
             config,
+            line: 0,
         }
     }
 
@@ -150,6 +205,7 @@ impl<'a, T: Text + ?Sized> Parser<'a, T> {
 
     fn next(&mut self) -> Result<(&'a T, Option<LineEnd>)> {
         let line = self.lines.next().ok_or(ParsePatchError::UnexpectedEof)?;
+        self.line += 1;
         Ok(line)
     }
 }
@@ -163,20 +219,13 @@ pub fn parse_multiple_with_config(input: &str, config: ParserConfig) -> Result<V
     let mut patches = vec![];
     loop {
         match (patch_header(&mut parser), hunks(&mut parser)) {
-            (Ok(header), Ok(hunks)) => {
-                let original = header.0.map(|(line, _end)| convert_cow_to_str(line));
-                let modified = header.1.map(|(line, _end)| convert_cow_to_str(line));
-                patches.push(Diff::new(original, modified, hunks))
-            }
-            (Ok((None, None)), Err(_)) => break,
-            // Allow NoHunks error when we have valid headers (pure renames/deletes/adds)
-            (Ok(header), Err(ParsePatchError::NoHunks))
-                if header.0.is_some() || header.1.is_some() =>
-            {
-                let original = header.0.map(|(line, _end)| convert_cow_to_str(line));
-                let modified = header.1.map(|(line, _end)| convert_cow_to_str(line));
-                patches.push(Diff::new(original, modified, vec![]))
+            (Ok(header), Ok(hunks)) => patches.push(header.into_diff(hunks, convert_cow_to_str)),
+            // Allow NoHunks error when we have valid headers (pure renames/deletes/adds) or the
+            // file is binary
+            (Ok(header), Err(ParsePatchError::NoHunks)) if header.has_file() => {
+                patches.push(header.into_diff(vec![], convert_cow_to_str))
             }
+            (Ok(header), Err(_)) if !header.has_file() => break,
             (Ok(_), Err(e)) | (Err(e), _) => {
                 return Err(e);
             }
@@ -188,12 +237,8 @@ pub fn parse_multiple_with_config(input: &str, config: ParserConfig) -> Result<V
 pub fn parse(input: &str) -> Result<Diff<'_, str>> {
     let mut parser = Parser::new(input);
     let header = patch_header(&mut parser)?;
-    let hunks = hunks(&mut parser)?;
-
-    let original = header.0.map(|(line, _end)| convert_cow_to_str(line));
-    let modified = header.1.map(|(line, _end)| convert_cow_to_str(line));
-
-    Ok(Diff::new(original, modified, hunks))
+    let hunks = header_hunks(&mut parser, &header)?;
+    Ok(header.into_diff(hunks, convert_cow_to_str))
 }
 
 pub fn parse_bytes_multiple(input: &[u8]) -> Result<Vec<Diff<'_, [u8]>>> {
@@ -208,21 +253,14 @@ pub fn parse_bytes_multiple_with_config(
     let mut patches = vec![];
     loop {
         match (patch_header(&mut parser), hunks(&mut parser)) {
-            (Ok(header), Ok(hunks)) => {
-                let original = header.0.map(|(line, _end)| line);
-                let modified = header.1.map(|(line, _end)| line);
-
-                patches.push(Diff::new(original, modified, hunks))
-            }
-            (Ok((None, None)), Err(_)) | (Err(_), Err(_)) => break,
-            // Allow NoHunks error when we have valid headers (pure renames/deletes/adds)
-            (Ok(header), Err(ParsePatchError::NoHunks))
-                if header.0.is_some() || header.1.is_some() =>
-            {
-                let original = header.0.map(|(line, _end)| line);
-                let modified = header.1.map(|(line, _end)| line);
-                patches.push(Diff::new(original, modified, vec![]))
+            (Ok(header), Ok(hunks)) => patches.push(header.into_diff(hunks, |line| line)),
+            // Allow NoHunks error when we have valid headers (pure renames/deletes/adds) or the
+            // file is binary
+            (Ok(header), Err(ParsePatchError::NoHunks)) if header.has_file() => {
+                patches.push(header.into_diff(vec![], |line| line))
             }
+            (Ok(header), Err(_)) if !header.has_file() => break,
+            (Err(_), Err(_)) => break,
             (Ok(_), Err(e)) | (Err(e), Ok(_)) => {
                 return Err(e);
             }
@@ -234,30 +272,212 @@ pub fn parse_bytes_multiple_with_config(
 pub fn parse_bytes(input: &[u8]) -> Result<Diff<'_, [u8]>> {
     let mut parser = Parser::new(input);
     let header = patch_header(&mut parser)?;
-    let hunks = hunks(&mut parser)?;
+    let hunks = header_hunks(&mut parser, &header)?;
+    Ok(header.into_diff(hunks, |line| line))
+}
 
-    let original = header.0.map(|(line, _end)| line);
-    let modified = header.1.map(|(line, _end)| line);
+/// Parse the patches of all files in `input`, collecting the errors of the broken ones instead of
+/// stopping at the first one
+///
+/// After an error the lines up to the next `diff ` or `--- ` line are skipped, dropping the patch
+/// of the file the error was found in. The errors are wrapped in [`ParsePatchError::Location`]
+/// with the line of the patch they were found at, which is the header of the hunk for errors
+/// within a hunk.
+pub(super) fn parse_multiple_lenient<'a, T: Text + ToOwned + ?Sized>(
+    input: &'a T,
+    convert: fn(Cow<'a, [u8]>) -> Cow<'a, T>,
+) -> (Vec<Diff<'a, T>>, Vec<ParsePatchError>) {
+    let mut parser = Parser::new(input);
+    let mut patches = vec![];
+    let mut errors = vec![];
+    while parser.peek().is_some() {
+        let start = parser.line;
+        let file = patch_header(&mut parser)
+            .map_err(|e| e.at_line(parser.line.max(1)))
+            .and_then(|header| {
+                let mut hunks = vec![];
+                while parser
+                    .peek()
+                    .is_some_and(|(line, _)| line.starts_with("@@ "))
+                {
+                    let line = parser.line + 1;
+                    hunks.push(hunk(&mut parser).map_err(|e| e.at_line(line))?);
+                    if !parser.config.skip_order_check && !verify_hunks_in_order(&hunks) {
+                        return Err(ParsePatchError::HunksOrder.at_line(line));
+                    }
+                }
+                Ok((header, hunks))
+            });
+        match file {
+            Ok((header, hunks)) if !hunks.is_empty() || header.has_file() => {
+                patches.push(header.into_diff(hunks, convert))
+            }
+            // Text following the last patch
+            Ok(_) => {}
+            Err(e) => {
+                errors.push(e);
+                skip_to_next_file(&mut parser, start);
+            }
+        }
+    }
+    (patches, errors)
+}
 
-    Ok(Diff::new(original, modified, hunks))
+/// Skip the rest of the patch of a file after an error, which started at line `start`
+///
+/// The line the error was found at is usually only peeked at and may start the next file, so it's
+/// only skipped if no line of the broken file has been read, to make progress.
+fn skip_to_next_file<T: Text + ?Sized>(parser: &mut Parser<'_, T>, start: usize) {
+    if parser.line == start {
+        let _ = parser.next();
+    }
+    while parser
+        .peek()
+        .is_some_and(|(line, _)| !line.starts_with("diff ") && !line.starts_with("--- "))
+    {
+        let _ = parser.next();
+    }
+}
+
+pub fn parse_nth(input: &str, n: usize) -> Result<Option<Diff<'_, str>>> {
+    match git_file_section(input, n) {
+        Some(section) => Ok(parse_multiple(section)?.into_iter().next()),
+        None => Ok(None),
+    }
+}
+
+pub fn parse_bytes_nth(input: &[u8], n: usize) -> Result<Option<Diff<'_, [u8]>>> {
+    match git_file_section(input, n) {
+        Some(section) => Ok(parse_bytes_multiple(section)?.into_iter().next()),
+        None => Ok(None),
+    }
+}
+
+/// Prefixes of the extended header lines git writes between `diff --git` and the file headers
+pub(super) const GIT_EXTENDED_HEADERS: &[&str] = &[
+    "index ",
+    "old mode ",
+    "new mode ",
+    "new file mode ",
+    "deleted file mode ",
+    "similarity index ",
+    "dissimilarity index ",
+    "rename from ",
+    "rename to ",
+    "copy from ",
+    "copy to ",
+];
+
+/// Returns `true` if the first non-empty line of `input` is a `--- ` file header or a hunk header
+/// like `@@ -1,2 +1,3 @@`
+///
+/// A leading `diff --git` line along with git's extended headers like `index` is skipped. Like
+/// [`is_normal_diff`](crate::is_normal_diff), the patch is not parsed.
+///
+/// ```
+/// use flickzeug::is_unified_diff;
+///
+/// assert!(is_unified_diff("--- a/f\n+++ b/f\n@@ -2 +2 @@\n-b\n+B\n"));
+/// assert!(is_unified_diff("diff --git a/f b/f\nindex 1234567..89abcde\n--- a/f\n"));
+/// assert!(!is_unified_diff("2c2\n< b\n---\n> B\n"));
+/// ```
+pub fn is_unified_diff<T: Text + ?Sized>(input: &T) -> bool {
+    let mut lines = LineIter::new(strip_bom(input))
+        .map(|(line, _)| line)
+        .filter(|line| !line.is_empty());
+    let mut first = lines.next();
+    if first.is_some_and(|line| line.starts_with("diff --git ")) {
+        first = lines.find(|line| {
+            !GIT_EXTENDED_HEADERS
+                .iter()
+                .any(|prefix| line.starts_with(prefix))
+        });
+    }
+    first.is_some_and(|line| line.starts_with("--- ") || hunk_header((line, None)).is_ok())
+}
+
+/// Returns the part of a git patch describing its `n`th file, from the file's `diff --git` line up
+/// to the next one.
+///
+/// Lines of a hunk always start with a ` `, `+`, `-` or `\\`, so a line starting with `diff --git`
+/// always starts a new file and the hunks don't need to be parsed to find it.
+fn git_file_section<T: Text + ?Sized>(input: &T, n: usize) -> Option<&T> {
+    let mut offset = 0;
+    let mut starts = input
+        .as_bytes()
+        .split_inclusive(|&b| b == b'\n')
+        .filter_map(|line| {
+            let start = offset;
+            offset += line.len();
+            line.starts_with(b"diff --git ").then_some(start)
+        });
+
+    let start = starts.nth(n)?;
+    let end = starts.next().unwrap_or(input.len());
+    Some(input.split_at(end).0.split_at(start).1)
 }
 
 // This is only used when the type originated as a utf8 string
-fn convert_cow_to_str(cow: Cow<'_, [u8]>) -> Cow<'_, str> {
+pub(super) fn convert_cow_to_str(cow: Cow<'_, [u8]>) -> Cow<'_, str> {
     match cow {
         Cow::Borrowed(b) => std::str::from_utf8(b).unwrap().into(),
-        Cow::Owned(o) => String::from_utf8(o).unwrap().into(),
+        // Octal escapes in quoted filenames may decode to bytes which aren't valid utf8
+        Cow::Owned(o) => String::from_utf8(o)
+            .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+            .into(),
+    }
+}
+
+/// The header of the patch of a single file
+///
+/// The filenames are paired with whether they still start with an `a/` or `b/` prefix.
+struct PatchHeader<'a> {
+    original: Option<(Cow<'a, [u8]>, bool)>,
+    modified: Option<(Cow<'a, [u8]>, bool)>,
+    preamble: Option<String>,
+    binary: bool,
+    git_index: Option<String>,
+    old_mode: Option<u32>,
+    new_mode: Option<u32>,
+    skip_order_check: bool,
+}
+
+impl<'a> PatchHeader<'a> {
+    /// Returns `true` if the header describes a file, even if the patch has no hunks
+    fn has_file(&self) -> bool {
+        self.original.is_some() || self.modified.is_some() || self.binary
+    }
+
+    fn into_diff<T: Text + ToOwned + ?Sized>(
+        self,
+        hunks: Vec<Hunk<'a, T>>,
+        convert: fn(Cow<'a, [u8]>) -> Cow<'a, T>,
+    ) -> Diff<'a, T> {
+        let mut diff = Diff::new(None::<Cow<T>>, None::<Cow<T>>, hunks);
+        diff.original = self
+            .original
+            .map(|(name, prefixed)| Filename(convert(name), prefixed));
+        diff.modified = self
+            .modified
+            .map(|(name, prefixed)| Filename(convert(name), prefixed));
+        diff.preamble = self.preamble;
+        diff.binary = self.binary;
+        diff.git_index = self.git_index;
+        diff.old_mode = self.old_mode;
+        diff.new_mode = self.new_mode;
+        diff.skip_order_check = self.skip_order_check;
+        diff
     }
 }
 
-#[allow(clippy::type_complexity)]
 fn patch_header<'a, T: Text + ToOwned + ?Sized>(
     parser: &mut Parser<'a, T>,
-) -> Result<(
-    Option<(Cow<'a, [u8]>, Option<LineEnd>)>,
-    Option<(Cow<'a, [u8]>, Option<LineEnd>)>,
-)> {
-    let (git_original, git_modified) = header_preamble(parser)?;
+) -> Result<PatchHeader<'a>> {
+    let mut header = header_preamble(parser)?;
+    // The patch of a binary file ends with its header, which has no filename headers
+    if header.binary {
+        return Ok(header);
+    }
     let strip_ab_prefix = parser.config.strip_ab_prefix;
 
     let mut filename1 = None;
@@ -267,19 +487,21 @@ fn patch_header<'a, T: Text + ToOwned + ?Sized>(
 
     while let Some((line, _end)) = parser.peek() {
         if line.starts_with("--- ") {
+            let line = parser.next()?;
             if saw_traditional_header1 {
                 return Err(ParsePatchError::HeaderMultipleLines(
                     HeaderLineKind::Removing,
                 ));
             }
             saw_traditional_header1 = true;
-            filename1 = parse_filename("--- ", parser.next()?, strip_ab_prefix)?;
+            filename1 = parse_filename("--- ", line, strip_ab_prefix)?;
         } else if line.starts_with("+++ ") {
+            let line = parser.next()?;
             if saw_traditional_header2 {
                 return Err(ParsePatchError::HeaderMultipleLines(HeaderLineKind::Adding));
             }
             saw_traditional_header2 = true;
-            filename2 = parse_filename("+++ ", parser.next()?, strip_ab_prefix)?;
+            filename2 = parse_filename("+++ ", line, strip_ab_prefix)?;
         } else {
             break;
         }
@@ -288,39 +510,50 @@ fn patch_header<'a, T: Text + ToOwned + ?Sized>(
     // Traditional --- +++ headers take precedence over git metadata
     // If we saw a traditional header (even if it parsed to None for /dev/null), use it
     // Otherwise fall back to git metadata
-    let original = if saw_traditional_header1 {
-        filename1
-    } else {
-        git_original
-    };
-    let modified = if saw_traditional_header2 {
-        filename2
-    } else {
-        git_modified
-    };
+    if saw_traditional_header1 {
+        header.original = filename1;
+    }
+    if saw_traditional_header2 {
+        header.modified = filename2;
+    }
 
-    Ok((original, modified))
+    Ok(header)
+}
+
+/// Parse the hunks following `header`, which may be left out for binary files
+fn header_hunks<'a, T: Text + ToOwned + ?Sized>(
+    parser: &mut Parser<'a, T>,
+    header: &PatchHeader<'_>,
+) -> Result<Vec<Hunk<'a, T>>> {
+    match hunks(parser) {
+        Err(ParsePatchError::NoHunks) if header.binary => Ok(Vec::new()),
+        hunks => hunks,
+    }
 }
 
 // Parse the patch header preamble, extracting filenames from git metadata.
 // Skips preamble lines like "diff --git", git metadata, etc., until reaching
 // the first filename header ("--- " or "+++ ") or hunk line.
-// Returns extracted filenames from git metadata (for pure renames/deletes/adds).
-#[allow(clippy::type_complexity)]
+// Returns extracted filenames from git metadata (for pure renames/deletes/adds),
+// the text before the "diff --git" line or the headers, like a commit message,
+// and whether the file is binary, which git and GNU diff only describe in a
+// "Binary files ... differ" line or a "GIT binary patch" section.
 fn header_preamble<'a, T: Text + ToOwned + ?Sized>(
     parser: &mut Parser<'a, T>,
-) -> Result<(
-    Option<(Cow<'a, [u8]>, Option<LineEnd>)>,
-    Option<(Cow<'a, [u8]>, Option<LineEnd>)>,
-)> {
+) -> Result<PatchHeader<'a>> {
     let strip_ab_prefix = parser.config.strip_ab_prefix;
     let mut git_original = None;
     let mut git_modified = None;
     let mut rename_from = None;
     let mut rename_to = None;
     let mut seen_diff_git = false;
+    let mut preamble_lines = Vec::new();
+    let mut binary = false;
+    let mut git_index = None;
+    let mut old_mode = None;
+    let mut new_mode = None;
 
-    while let Some((line, end)) = parser.peek() {
+    while let Some((line, _end)) = parser.peek() {
         if line.starts_with("--- ") | line.starts_with("+++ ") | line.starts_with("@@ ") {
             break;
         }
@@ -337,56 +570,118 @@ fn header_preamble<'a, T: Text + ToOwned + ?Sized>(
             if let Some(rest) = line.strip_prefix("diff --git ") {
                 // Parse "a/file1 b/file2" (with prefixes) or "file1 file2" (without prefixes)
                 // Try to split on " b/" first to detect standard format
-                if let Some((file1, file2)) = rest.split_at_exclusive(" b/") {
-                    // Standard format with b/ prefix
-                    let has_prefix = strip_ab_prefix;
-                    git_original = parse_git_filename(file1, has_prefix).map(|f| (f, *end));
-                    git_modified = parse_git_filename(file2, has_prefix).map(|f| (f, *end));
+                if let Some(space) = rest.find(" b/") {
+                    // Standard format with b/ prefix, which is kept for parse_git_filename
+                    let (file1, file2) = rest.split_at(space);
+                    let file2 = file2.split_at(1).1;
+                    git_original = parse_git_filename(file1, true, strip_ab_prefix);
+                    git_modified = parse_git_filename(file2, true, strip_ab_prefix);
                 } else if let Some((file1, file2)) = rest.split_at_exclusive(" ") {
                     // Either --no-prefix format, or one side is /dev/null which
                     // prevents the " b/" split from matching. When /dev/null is
                     // involved, the other file may still have a/ or b/ prefix.
                     let has_dev_null =
                         file1.as_bytes() == b"/dev/null" || file2.as_bytes() == b"/dev/null";
-                    let has_prefix = has_dev_null && strip_ab_prefix;
-                    git_original = parse_git_filename(file1, has_prefix).map(|f| (f, *end));
-                    git_modified = parse_git_filename(file2, has_prefix).map(|f| (f, *end));
+                    git_original = parse_git_filename(file1, has_dev_null, strip_ab_prefix);
+                    git_modified = parse_git_filename(file2, has_dev_null, strip_ab_prefix);
                 }
                 // If neither split works, skip this line (malformed diff --git line)
             }
         }
-        // Parse rename from/to
-        else if line.starts_with("rename from ")
-            && let Some(filename) = line.strip_prefix("rename from ")
+        // Parse rename and copy from/to
+        else if let Some(filename) = line
+            .strip_prefix("rename from ")
+            .or_else(|| line.strip_prefix("copy from "))
+        {
+            rename_from = Some((Cow::Borrowed(filename.as_bytes()), false));
+        } else if let Some(filename) = line
+            .strip_prefix("rename to ")
+            .or_else(|| line.strip_prefix("copy to "))
+        {
+            rename_to = Some((Cow::Borrowed(filename.as_bytes()), false));
+        } else if seen_diff_git && let Some(index) = line.strip_prefix("index ") {
+            git_index = Some(String::from_utf8_lossy(index.as_bytes()).into_owned());
+        } else if seen_diff_git
+            && let Some(mode) = line
+                .strip_prefix("old mode ")
+                .or_else(|| line.strip_prefix("deleted file mode "))
+        {
+            old_mode = mode.as_str().and_then(super::parse_mode);
+        } else if seen_diff_git
+            && let Some(mode) = line
+                .strip_prefix("new mode ")
+                .or_else(|| line.strip_prefix("new file mode "))
         {
-            rename_from = Some((Cow::Borrowed(filename.as_bytes()), *end));
-        } else if line.starts_with("rename to ")
-            && let Some(filename) = line.strip_prefix("rename to ")
+            new_mode = mode.as_str().and_then(super::parse_mode);
+        } else if line.as_bytes() == b"GIT binary patch" {
+            // The data of the patch follows until the next file
+            binary = true;
+        } else if let Some(files) = line
+            .strip_prefix("Binary files ")
+            .and_then(|files| files.strip_suffix(" differ"))
         {
-            rename_to = Some((Cow::Borrowed(filename.as_bytes()), *end));
+            // Without a "diff --git" line, e.g. in the output of GNU diff, this line is all there
+            // is about the file
+            if !seen_diff_git && let Some((file1, file2)) = files.split_at_exclusive(" and ") {
+                git_original = parse_git_filename(file1, true, strip_ab_prefix);
+                git_modified = parse_git_filename(file2, true, strip_ab_prefix);
+            }
+            binary = true;
+            parser.next()?;
+            break;
         }
 
-        parser.next()?;
+        let line = parser.next()?;
+        if !seen_diff_git {
+            preamble_lines.push(line);
+        }
     }
 
-    // Prefer rename from/to over git diff header
-    let original = rename_from.or(git_original);
-    let modified = rename_to.or(git_modified);
+    // Prefer rename and copy from/to over git diff header
+    Ok(PatchHeader {
+        original: rename_from.or(git_original),
+        modified: rename_to.or(git_modified),
+        preamble: preamble(&preamble_lines),
+        binary,
+        git_index,
+        old_mode,
+        new_mode,
+        skip_order_check: parser.config.skip_order_check,
+    })
+}
+
+/// Join the lines preceding the headers of a patch into its preamble, see [`Diff::preamble`]
+pub(super) fn preamble<T: Text + ?Sized>(lines: &[(&T, Option<LineEnd>)]) -> Option<String> {
+    if lines.is_empty() {
+        return None;
+    }
 
-    Ok((original, modified))
+    let mut preamble = Vec::new();
+    for &(line, end) in lines {
+        preamble.extend_from_slice(line.as_bytes());
+        if let Some(end) = end {
+            preamble.extend_from_slice(end.into());
+        }
+    }
+    Some(String::from_utf8_lossy(&preamble).into_owned())
 }
 
-#[allow(clippy::type_complexity)]
-fn parse_filename<'a, T: Text + ToOwned + ?Sized>(
+/// Parse the filename of a `--- ` or `+++ ` header, along with whether it still starts with an
+/// `a/` or `b/` prefix
+pub(super) fn parse_filename<'a, T: Text + ToOwned + ?Sized>(
     prefix: &str,
     l: (&'a T, Option<LineEnd>),
     strip_ab_prefix: bool,
-) -> Result<Option<(Cow<'a, [u8]>, Option<LineEnd>)>> {
+) -> Result<Option<(Cow<'a, [u8]>, bool)>> {
     let line =
         l.0.strip_prefix(prefix)
             .ok_or(ParsePatchError::UnableToParseFilename)?;
 
-    let filename = if let Some((filename, _)) = line.split_at_exclusive("\t") {
+    // Quoted filenames may contain spaces and are followed by the timestamp, if any
+    let filename = if line.starts_with("\"") {
+        let end = closing_quote(line.as_bytes()).ok_or(ParsePatchError::UnterminatedFilename)?;
+        line.split_at(end + 1).0
+    } else if let Some((filename, _)) = line.split_at_exclusive("\t") {
         filename
     } else if let Some((filename, _)) = line.split_at_exclusive(" ") {
         filename
@@ -408,27 +703,44 @@ fn parse_filename<'a, T: Text + ToOwned + ?Sized>(
     };
 
     // Strip conventional a/ or b/ prefix used by diff tools to distinguish sides
-    if strip_ab_prefix && let Cow::Borrowed(bytes) = parsed_filename {
-        if let Some(rest) = std::str::from_utf8(bytes)
-            .ok()
-            .and_then(|s| s.strip_prefix("a/"))
-        {
-            parsed_filename = Cow::Borrowed(rest.as_bytes());
-        } else if let Some(rest) = std::str::from_utf8(bytes)
-            .ok()
-            .and_then(|s| s.strip_prefix("b/"))
-        {
-            parsed_filename = Cow::Borrowed(rest.as_bytes());
-        }
+    let has_prefix = has_ab_prefix(&parsed_filename);
+    if strip_ab_prefix {
+        parsed_filename = match parsed_filename {
+            Cow::Borrowed(bytes) if has_prefix => Cow::Borrowed(&bytes[2..]),
+            Cow::Owned(mut bytes) if has_prefix => {
+                bytes.drain(..2);
+                Cow::Owned(bytes)
+            }
+            filename => filename,
+        };
     }
 
-    Ok(Some((parsed_filename, l.1)))
+    Ok(Some((parsed_filename, has_prefix && !strip_ab_prefix)))
+}
+
+fn has_ab_prefix(filename: &[u8]) -> bool {
+    filename.starts_with(b"a/") || filename.starts_with(b"b/")
 }
 
 fn is_quoted<T: Text + ?Sized>(s: &T) -> Option<&T> {
     s.strip_prefix("\"").and_then(|s| s.strip_suffix("\""))
 }
 
+/// Returns the index of the quote closing the quoted filename at the start of `line`
+fn closing_quote(line: &[u8]) -> Option<usize> {
+    let mut bytes = line.iter().enumerate().skip(1);
+    while let Some((i, b)) = bytes.next() {
+        match b {
+            b'\\' => {
+                bytes.next();
+            }
+            b'"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
 fn unescaped_filename<T: Text + ToOwned + ?Sized>(filename: &T) -> Result<Cow<'_, [u8]>> {
     // NOTE: may be a problem for other types of line feed except "\n" and "\r\n".
     let bytes = filename.as_bytes().trim_ascii_end();
@@ -443,7 +755,7 @@ fn unescaped_filename<T: Text + ToOwned + ?Sized>(filename: &T) -> Result<Cow<'_
 fn escaped_filename<T: Text + ToOwned + ?Sized>(escaped: &T) -> Result<Cow<'_, [u8]>> {
     let mut filename = Vec::new();
 
-    let mut chars = escaped.as_bytes().iter().copied();
+    let mut chars = escaped.as_bytes().iter().copied().peekable();
     while let Some(c) = chars.next() {
         if c == b'\\' {
             let ch = match chars
@@ -452,10 +764,24 @@ fn escaped_filename<T: Text + ToOwned + ?Sized>(escaped: &T) -> Result<Cow<'_, [
             {
                 b'n' => b'\n',
                 b't' => b'\t',
-                b'0' => b'\0',
                 b'r' => b'\r',
+                b'a' => b'\x07',
+                b'b' => b'\x08',
+                b'f' => b'\x0c',
+                b'v' => b'\x0b',
                 b'\"' => b'\"',
                 b'\\' => b'\\',
+                // Octal escapes of up to three digits, which git uses for bytes outside of ASCII
+                digit @ b'0'..=b'7' => {
+                    let mut value = u32::from(digit - b'0');
+                    for _ in 0..2 {
+                        let Some(digit) = chars.next_if(|b| matches!(b, b'0'..=b'7')) else {
+                            break;
+                        };
+                        value = value * 8 + u32::from(digit - b'0');
+                    }
+                    u8::try_from(value).map_err(|_| ParsePatchError::InvalidEscapedCharacter)?
+                }
                 _ => return Err(ParsePatchError::InvalidEscapedCharacter),
             };
             filename.push(ch);
@@ -474,27 +800,30 @@ fn escaped_filename<T: Text + ToOwned + ?Sized>(escaped: &T) -> Result<Cow<'_, [
 /// - --no-prefix format without prefixes
 /// - /dev/null for created/deleted files
 ///
-/// Returns None for /dev/null (represents non-existent file)
-fn parse_git_filename<T: Text + ?Sized>(filename: &T, has_prefix: bool) -> Option<Cow<'_, [u8]>> {
+/// Returns None for /dev/null (represents non-existent file), otherwise the filename along with
+/// whether it still starts with an `a/` or `b/` prefix
+fn parse_git_filename<T: Text + ?Sized>(
+    filename: &T,
+    has_prefix: bool,
+    strip_ab_prefix: bool,
+) -> Option<(Cow<'_, [u8]>, bool)> {
     // Check for /dev/null (file doesn't exist)
     if filename.as_bytes() == b"/dev/null" {
         return None;
     }
 
-    // If we detected prefixes (found " b/" in the line), strip a/ or b/
-    if has_prefix {
-        if let Some(stripped) = filename.strip_prefix("a/") {
-            return Some(Cow::Borrowed(stripped.as_bytes()));
-        } else if let Some(stripped) = filename.strip_prefix("b/") {
-            return Some(Cow::Borrowed(stripped.as_bytes()));
-        }
+    // If we detected prefixes (found " b/" in the line), strip a/ or b/ unless they're kept
+    let filename = filename.as_bytes();
+    let has_prefix = has_prefix && has_ab_prefix(filename);
+    if has_prefix && strip_ab_prefix {
+        return Some((Cow::Borrowed(&filename[2..]), false));
     }
 
     // No prefix or couldn't strip, use as-is
-    Some(Cow::Borrowed(filename.as_bytes()))
+    Some((Cow::Borrowed(filename), has_prefix))
 }
 
-fn verify_hunks_in_order<T: ?Sized + ToOwned>(hunks: &[Hunk<'_, T>]) -> bool {
+pub(super) fn verify_hunks_in_order<T: ?Sized + ToOwned>(hunks: &[Hunk<'_, T>]) -> bool {
     for hunk in hunks.windows(2) {
         if hunk[0].old_range.end() > hunk[1].old_range.start()
             || hunk[0].new_range.end() > hunk[1].new_range.start()
@@ -513,10 +842,11 @@ fn hunks<'a, T: Text + ?Sized + ToOwned>(parser: &mut Parser<'a, T>) -> Result<V
         // TODO: Handle properly. For example there is case where hunk
         // is partially parsed. I think we want to make it hard error
         // instead or treating it as PS.
-        if let Ok(h) = r {
-            hunks.push(h);
-        } else {
-            break;
+        match r {
+            Ok(h) => hunks.push(h),
+            // A header with a range that can't be represented is never the start of trailing text
+            Err(e @ ParsePatchError::InvalidRange { .. }) => return Err(e),
+            Err(_) => break,
         }
     }
 
@@ -552,6 +882,15 @@ fn tolerance_level<T: Text + ?Sized + ToOwned>(lines: &[Line<'_, T>]) -> (usize,
 fn hunk<'a, T: Text + ?Sized + ToOwned>(parser: &mut Parser<'a, T>) -> Result<Hunk<'a, T>> {
     let n = *parser.peek().ok_or(ParsePatchError::UnexpectedEof)?;
     let (mut range1, mut range2, function_context) = hunk_header(n)?;
+    // Reject ranges whose end can't be represented, so computing it later can't overflow
+    if [range1, range2]
+        .iter()
+        .any(|range| range.start().checked_add(range.len()).is_none())
+    {
+        return Err(ParsePatchError::InvalidRange {
+            line: parser.line + 1,
+        });
+    }
     let _ = parser.next();
     let mut lines = hunk_lines(parser, &range1, &range2)?;
 
@@ -624,7 +963,7 @@ fn hunk_header<T: Text + ?Sized>(oinput: (&T, Option<LineEnd>)) -> Result<HunkHe
 }
 
 fn range<T: Text + ?Sized>(s: &T) -> Result<HunkRange> {
-    let (start, len) = if let Some((start, len)) = s.split_at_exclusive(",") {
+    let (start, len): (usize, usize) = if let Some((start, len)) = s.split_at_exclusive(",") {
         (
             start.parse().ok_or(ParsePatchError::Range)?,
             len.parse().ok_or(ParsePatchError::Range)?,
@@ -632,7 +971,6 @@ fn range<T: Text + ?Sized>(s: &T) -> Result<HunkRange> {
     } else {
         (s.parse().ok_or(ParsePatchError::Range)?, 1)
     };
-
     Ok(HunkRange::new(start, len))
 }
 
@@ -718,12 +1056,17 @@ fn hunk_lines<'a, T: Text + ?Sized + ToOwned>(
 
 #[cfg(test)]
 mod tests {
+    use crate::LineEnd;
     use crate::patch::Line;
     use crate::patch::parse::{
-        HunkRangeStrategy, ParsePatchError, ParserConfig, parse_multiple_with_config,
+        HunkRangeStrategy, ParsePatchError, ParserConfig, convert_cow_to_str, parse_bytes_multiple,
+        parse_bytes_multiple_with_config, parse_multiple_lenient, parse_multiple_with_config,
     };
 
-    use super::{parse, parse_bytes, parse_multiple};
+    use super::{
+        hunk_header, is_unified_diff, parse, parse_bytes, parse_bytes_nth, parse_multiple,
+        parse_nth,
+    };
 
     #[test]
     fn test_escaped_filenames() {
@@ -772,6 +1115,45 @@ mod tests {
         assert_eq!(b.modified(), Some(&b"mo\0\t\r\n\\dified"[..]));
     }
 
+    #[test]
+    fn test_filenames_with_timestamps_and_quotes() {
+        let s = "\
+--- a/file name.txt\t2023-01-01 12:00:00.000000000 +0000
++++ b/file name.txt\t2023-01-02 12:00:00.000000000 +0000
+@@ -1 +1 @@
+-a
++b
+";
+        let p = parse(s).unwrap();
+        assert_eq!(p.original(), Some("file name.txt"));
+        assert_eq!(p.modified(), Some("file name.txt"));
+
+        // git quotes names with special characters and writes other bytes as octal escapes
+        let s = r#"\
+--- "a/file name\twith \"escapes\"\\.txt"	2023-01-01 12:00:00.000000000 +0000
++++ "b/caf\303\251 \0.txt"
+@@ -1 +1 @@
+-a
++b
+"#;
+        let p = parse(s).unwrap();
+        assert_eq!(p.original(), Some("file name\twith \"escapes\"\\.txt"));
+        assert_eq!(p.modified(), Some("café \0.txt"));
+        let b = parse_bytes(s.as_ref()).unwrap();
+        assert_eq!(b.modified(), Some("café \0.txt".as_bytes()));
+
+        // Names which aren't valid utf8 can only be represented by byte patches
+        let s = "--- \"a/\\377.txt\"\n+++ b/f\n@@ -1 +1 @@\n-a\n+b\n";
+        assert_eq!(parse(s).unwrap().original(), Some("\u{fffd}.txt"));
+        let b = parse_bytes(s.as_ref()).unwrap();
+        assert_eq!(b.original(), Some(&b"\xff.txt"[..]));
+
+        for header in [r#"--- "a/unterminated"#, r#"--- "a/\400""#] {
+            let s = format!("{header}\n+++ b/f\n@@ -1 +1 @@\n-a\n+b\n");
+            parse(&s).unwrap_err();
+        }
+    }
+
     #[test]
     fn test_missing_filename_header() {
         // Missing Both '---' and '+++' lines
@@ -831,6 +1213,27 @@ mod tests {
         parse(s).unwrap_err();
     }
 
+    #[test]
+    fn reversed_header_order_assigns_names_by_marker() {
+        let s = "\
++++ b/new-name.txt
+--- a/old-name.txt
+@@ -1 +1 @@
+-old
++new
+";
+        let patch = parse(s).unwrap();
+        assert_eq!(patch.original(), Some("old-name.txt"));
+        assert_eq!(patch.modified(), Some("new-name.txt"));
+
+        // Serializing emits the canonical order
+        assert!(
+            patch
+                .to_string()
+                .starts_with("--- old-name.txt\n+++ new-name.txt\n")
+        );
+    }
+
     #[test]
     fn adjacent_hunks_correctly_parse() {
         let s = "\
@@ -874,6 +1277,102 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_lenient_skips_broken_file() {
+        let s = "\
+diff --git a/first.txt b/first.txt
+--- a/first.txt
++++ b/first.txt
+@@ -1 +1 @@
+-a
++b
+diff --git a/second.txt b/second.txt
+--- a/second.txt
++++ b/second.txt
+@@ -1,2 +1,2 @@
+-c
+?d
++e
+diff --git a/third.txt b/third.txt
+--- a/third.txt
++++ b/third.txt
+@@ -1 +1 @@
+-f
++g
+";
+        let (diffs, errors) = parse_multiple_lenient(s, convert_cow_to_str);
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].modified(), Some("first.txt"));
+        assert_eq!(diffs[1].modified(), Some("third.txt"));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line(), Some(10));
+        assert!(matches!(
+            errors[0].kind(),
+            ParsePatchError::UnexpectedLineInHunkBody
+        ));
+
+        let (diffs, errors) = parse_multiple_lenient(s.as_bytes(), |line| line);
+        assert_eq!((diffs.len(), errors.len()), (2, 1));
+
+        // Without errors the same diffs are parsed as by `parse_multiple`
+        let valid = s.replace("?d", " d");
+        let (diffs, errors) = parse_multiple_lenient(valid.as_str(), convert_cow_to_str);
+        assert!(errors.is_empty());
+        assert_eq!(diffs, parse_multiple(&valid).unwrap());
+    }
+
+    #[test]
+    fn lenient_keeps_git_metadata_after_broken_file() {
+        // The broken hunk ends at the `diff --git` line of the next file
+        let s = "\
+diff --git a/broken.txt b/broken.txt
+--- a/broken.txt
++++ b/broken.txt
+@@ -1,2 +1,2 @@
+-a
+diff --git a/x.txt b/y.txt
+similarity index 100%
+rename from x.txt
+rename to y.txt
+diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+diff --git a/c.txt b/c.txt
+--- a/c.txt
++++ b/c.txt
+@@ -1 +1 @@
+-c
++C
+";
+        let (diffs, errors) = parse_multiple_lenient(s, convert_cow_to_str);
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(
+            errors[0].kind(),
+            ParsePatchError::UnexpectedLineInHunkBody
+        ));
+        let names: Vec<_> = diffs
+            .iter()
+            .map(|diff| (diff.original(), diff.modified()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (Some("x.txt"), Some("y.txt")),
+                (Some("run.sh"), Some("run.sh")),
+                (Some("c.txt"), Some("c.txt")),
+            ]
+        );
+        assert_eq!(diffs[1].new_mode(), Some(0o100755));
+        assert!(diffs[1].hunks().is_empty());
+
+        // The same files follow a valid patch
+        let valid = s.replace("-a\n", "-a\n+b\n").replace("-1,2 +1,2", "-1 +1");
+        assert_eq!(&parse_multiple(&valid).unwrap()[1..], diffs);
+        let (diffs, errors) = parse_multiple_lenient(valid.as_str(), convert_cow_to_str);
+        assert!(errors.is_empty());
+        assert_eq!(diffs, parse_multiple(&valid).unwrap());
+    }
+
     #[test]
     fn test_malformed_patch_strict_mode_fails() {
         // This patch has overlapping new_range values between hunks 7 and 8
@@ -1001,6 +1500,194 @@ rename to another_new.txt
         );
     }
 
+    #[test]
+    fn test_multi_file_patch() {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join("multi-file")
+            .join("patch.patch");
+        let patch = std::fs::read_to_string(path).unwrap();
+
+        let result = parse_multiple(&patch).unwrap();
+        let files: Vec<_> = result
+            .iter()
+            .map(|diff| (diff.original(), diff.modified(), diff.hunks().len()))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (Some("src/lib.rs"), Some("src/lib.rs"), 2),
+                // Pure rename
+                (Some("docs/old-guide.md"), Some("docs/guide.md"), 0),
+                // Mode change
+                (Some("scripts/build.sh"), Some("scripts/build.sh"), 0),
+            ]
+        );
+
+        let bytes = parse_bytes_multiple(patch.as_bytes()).unwrap();
+        assert_eq!(bytes.len(), 3);
+        assert_eq!(bytes[1].modified(), Some(b"docs/guide.md".as_slice()));
+    }
+
+    #[test]
+    fn test_git_metadata() {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join("git-metadata")
+            .join("patch.patch");
+        let patch = std::fs::read_to_string(path).unwrap();
+
+        let result = parse_multiple(&patch).unwrap();
+        let metadata: Vec<_> = result
+            .iter()
+            .map(|diff| (diff.git_index(), diff.old_mode(), diff.new_mode()))
+            .collect();
+        assert_eq!(
+            metadata,
+            [
+                (Some("4163036..21ba682"), Some(0o100644), Some(0o100755)),
+                (
+                    Some("94954ab..65a56c3 100644"),
+                    Some(0o100644),
+                    Some(0o100644)
+                ),
+                (None, Some(0o100644), Some(0o100755)),
+            ]
+        );
+        assert_eq!(result[2].modified(), Some("run.sh"));
+        assert!(result[2].hunks().is_empty());
+
+        // With the a/ and b/ prefixes kept, the patch is written back unchanged
+        let config = ParserConfig {
+            strip_ab_prefix: false,
+            ..Default::default()
+        };
+        let result = parse_multiple_with_config(&patch, config.clone()).unwrap();
+        let formatted: String = result.iter().map(|diff| diff.to_string()).collect();
+        assert_eq!(formatted, patch);
+        let bytes = parse_bytes_multiple_with_config(patch.as_bytes(), config).unwrap();
+        let formatted: Vec<u8> = bytes.iter().flat_map(|diff| diff.to_bytes()).collect();
+        assert_eq!(formatted, patch.as_bytes());
+
+        // Reversing swaps the modes and drops the index line, which names the blobs
+        let reversed = result[0].reverse();
+        assert_eq!(reversed.git_index(), None);
+        assert_eq!(
+            (reversed.old_mode(), reversed.new_mode()),
+            (Some(0o100755), Some(0o100644))
+        );
+        assert!(reversed.to_string().starts_with(
+            "diff --git a/build.sh b/build.sh\nold mode 100755\nnew mode 100644\n--- a/build.sh\n"
+        ));
+    }
+
+    #[test]
+    fn test_git_created_and_deleted_files() {
+        let patch = "\
+diff --git a/new.txt b/new.txt
+new file mode 100644
+index 0000000..ce01362
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+diff --git a/old.txt b/old.txt
+deleted file mode 100644
+index ce01362..0000000
+--- a/old.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-hello
+diff --git a/empty.txt b/empty.txt
+new file mode 100644
+index 0000000..e69de29
+";
+        for config in [
+            ParserConfig::default(),
+            ParserConfig {
+                strip_ab_prefix: false,
+                ..Default::default()
+            },
+        ] {
+            let result = parse_multiple_with_config(patch, config.clone()).unwrap();
+            assert_eq!(result[0].original(), None);
+            assert_eq!(result[0].new_path(), Some("new.txt"));
+            assert_eq!(result[1].old_path(), Some("old.txt"));
+            assert_eq!(result[1].modified(), None);
+
+            let formatted: String = result.iter().map(|diff| diff.to_string()).collect();
+            assert_eq!(formatted, patch);
+            let reparsed = parse_multiple_with_config(&formatted, config.clone()).unwrap();
+            assert_eq!(reparsed, result);
+
+            let bytes = parse_bytes_multiple_with_config(patch.as_bytes(), config).unwrap();
+            let formatted: Vec<u8> = bytes.iter().flat_map(|diff| diff.to_bytes()).collect();
+            assert_eq!(formatted, patch.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_binary_files() {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join("binary")
+            .join("patch.patch");
+        let patch = std::fs::read_to_string(path).unwrap();
+
+        let result = parse_multiple(&patch).unwrap();
+        let files: Vec<_> = result
+            .iter()
+            .map(|diff| (diff.modified(), diff.is_binary(), diff.hunks().len()))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (Some("assets/logo.png"), true, 0),
+                (Some("greeting.txt"), false, 1),
+            ]
+        );
+        let bytes = parse_bytes_multiple(patch.as_bytes()).unwrap();
+        assert!(bytes[0].is_binary() && !bytes[1].is_binary());
+
+        assert_eq!(
+            crate::apply("", &result[0]).unwrap_err(),
+            crate::ApplyError::BinaryNotSupported
+        );
+        assert_eq!(
+            crate::apply("hello\nworld\n", &result[1]).unwrap().0,
+            "hello\nthere\n"
+        );
+
+        // Without the data of the binary patch
+        let patch = "\
+diff --git a/logo.png b/logo.png
+index d186a24..a488cbe 100644
+Binary files a/logo.png and b/logo.png differ
+";
+        let diff = parse(patch).unwrap();
+        assert!(diff.is_binary());
+        assert_eq!(diff.original(), Some("logo.png"));
+
+        // GNU diff only writes the line about the binary files
+        let patch = "\
+Binary files old/logo.png and new/logo.png differ
+--- old/greeting.txt
++++ new/greeting.txt
+@@ -1 +1 @@
+-hello
++Hello
+";
+        let result = parse_multiple(patch).unwrap();
+        assert_eq!(result.len(), 2);
+        assert!(result[0].is_binary());
+        assert_eq!(
+            (result[0].original(), result[0].modified()),
+            (Some("old/logo.png"), Some("new/logo.png"))
+        );
+        assert!(!result[1].is_binary());
+        assert_eq!(result[1].hunks().len(), 1);
+    }
+
     #[test]
     fn test_deleted_file() {
         // Test parsing patches with deleted files
@@ -1273,6 +1960,200 @@ new file mode 100644
         assert_eq!(result[0].hunks().len(), 1);
     }
 
+    #[test]
+    fn test_is_unified_diff() {
+        for patch in [
+            "--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n+b\n",
+            "\n\n@@ -1 +1 @@\n-a\n+b\n",
+            "@@ -0,0 +1,2 @@ fn main() {\r\n+a\r\n+b\r\n",
+            "diff --git a/f b/f\n--- a/f\n+++ b/f\n",
+            "diff --git a/f b/g\nsimilarity index 90%\nrename from f\nrename to g\nindex 1..2 100644\n--- a/f\n",
+        ] {
+            assert!(is_unified_diff(patch), "{patch:?}");
+            assert!(is_unified_diff(patch.as_bytes()), "{patch:?}");
+        }
+
+        for patch in [
+            "",
+            "\n",
+            // Normal and context diffs
+            "2c2\n< b\n---\n> B\n",
+            "0a1\n> a\n",
+            "*** a\n--- b\n***************\n*** 1 ****\n! a\n--- 1 ----\n! b\n",
+            "***************\n*** 1 ****\n- a\n--- 0 ----\n",
+            // Pure renames and invalid hunk headers
+            "diff --git a/f b/g\nsimilarity index 100%\nrename from f\nrename to g\n",
+            "@@ -a +1 @@\n",
+            "@@ -1 +1\n",
+            "just some text\n--- a/f\n",
+        ] {
+            assert!(!is_unified_diff(patch), "{patch:?}");
+        }
+    }
+
+    #[test]
+    fn test_git_paths() {
+        let config = || ParserConfig {
+            strip_ab_prefix: false,
+            ..Default::default()
+        };
+
+        // Plain modification
+        let patch = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1234567..89abcde 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1 @@
+-old
++new
+";
+        let diff = parse_multiple_with_config(patch, config())
+            .unwrap()
+            .remove(0);
+        assert_eq!(diff.original(), Some("a/src/lib.rs"));
+        assert_eq!(diff.old_path(), Some("src/lib.rs"));
+        assert_eq!(diff.new_path(), Some("src/lib.rs"));
+
+        // Rename with edits
+        let patch = "\
+diff --git a/old.txt b/new.txt
+similarity index 80%
+rename from old.txt
+rename to new.txt
+index 1234567..89abcde 100644
+--- a/old.txt
++++ b/new.txt
+@@ -1 +1 @@
+-old
++new
+";
+        let diff = parse_multiple_with_config(patch, config())
+            .unwrap()
+            .remove(0);
+        assert_eq!(diff.old_path(), Some("old.txt"));
+        assert_eq!(diff.new_path(), Some("new.txt"));
+        assert_eq!(diff.hunks().len(), 1);
+
+        // Pure rename and copy, without any hunks
+        let patch = "\
+diff --git a/docs/old.md b/docs/new.md
+similarity index 100%
+rename from docs/old.md
+rename to docs/new.md
+diff --git a/template.txt b/copy.txt
+similarity index 100%
+copy from template.txt
+copy to copy.txt
+";
+        for diffs in [
+            parse_multiple(patch).unwrap(),
+            parse_multiple_with_config(patch, config()).unwrap(),
+        ] {
+            let paths: Vec<_> = diffs
+                .iter()
+                .map(|diff| (diff.old_path(), diff.new_path(), diff.hunks().len()))
+                .collect();
+            assert_eq!(
+                paths,
+                [
+                    (Some("docs/old.md"), Some("docs/new.md"), 0),
+                    (Some("template.txt"), Some("copy.txt"), 0),
+                ]
+            );
+        }
+
+        // Prefixes are only removed once, and names of renames never have one
+        let patch = "\
+diff --git a/a/foo.c b/a/foo.c
+--- a/a/foo.c
++++ b/a/foo.c
+@@ -1 +1 @@
+-old
++new
+diff --git a/a/x.txt b/b/x.txt
+similarity index 100%
+rename from a/x.txt
+rename to b/x.txt
+";
+        for diffs in [
+            parse_multiple(patch).unwrap(),
+            parse_multiple_with_config(patch, config()).unwrap(),
+        ] {
+            let paths: Vec<_> = diffs
+                .iter()
+                .map(|diff| (diff.old_path(), diff.new_path()))
+                .collect();
+            assert_eq!(
+                paths,
+                [
+                    (Some("a/foo.c"), Some("a/foo.c")),
+                    (Some("a/x.txt"), Some("b/x.txt")),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_preamble() {
+        let patch = "\
+# Fix the greeting
+#
+# Upstream: https://example.com/issue/1
+--- hello.txt
++++ hello.txt
+@@ -1 +1 @@
+-hello
++Hello
+";
+        let comment = "# Fix the greeting\n#\n# Upstream: https://example.com/issue/1\n";
+        let diff = parse(patch).unwrap();
+        assert_eq!(diff.preamble(), Some(comment));
+        assert_eq!(diff.to_string(), patch);
+        let diff = parse_bytes(patch.as_bytes()).unwrap();
+        assert_eq!(diff.preamble(), Some(comment));
+        assert_eq!(diff.to_bytes(), patch.as_bytes());
+
+        // Only the text before the `diff --git` line of the first file is kept
+        let patch = "\
+From 1234 Mon Sep 17 00:00:00 2001
+Subject: [PATCH] Update
+
+---
+ f | 2 +-
+
+diff --git a/f b/f
+index 1111111..2222222 100644
+--- a/f
++++ b/f
+@@ -1 +1 @@
+-a
++b
+diff --git a/g b/g
+--- a/g
++++ b/g
+@@ -1 +1 @@
+-c
++d
+";
+        let diffs = parse_multiple(patch).unwrap();
+        assert_eq!(
+            diffs[0].preamble(),
+            Some(
+                "From 1234 Mon Sep 17 00:00:00 2001\nSubject: [PATCH] Update\n\n---\n f | 2 +-\n\n"
+            )
+        );
+        assert_eq!(diffs[1].preamble(), None);
+        assert!(diffs[0].to_string().starts_with("From 1234"));
+
+        assert_eq!(parse("@@ -1 +1 @@\n-a\n+b\n").unwrap().preamble(), None);
+        let diff = parse("@@ -1 +1 @@\n-a\n+b\n")
+            .unwrap()
+            .with_preamble("Fix a\n\n");
+        assert_eq!(diff.to_string(), "Fix a\n\n@@ -1 +1 @@\n-a\n+b\n");
+        assert_eq!(diff.reverse().preamble(), None);
+    }
+
     #[test]
     fn test_git_diff_dev_null_deleted_in_git_header() {
         // Test /dev/null as the second file in git header (deleted file)
@@ -1342,4 +2223,131 @@ deleted file mode 100644
         assert_eq!(result[0].modified(), None);
         assert_eq!(result[0].hunks().len(), 1);
     }
+
+    #[test]
+    fn huge_ranges_dont_overflow() {
+        let max = usize::MAX;
+        for header in [
+            format!("@@ -{max},1 +1 @@"),
+            format!("@@ -1 +2,{max} @@"),
+            format!("@@ -{max} +1 @@"),
+            format!("@@ -1,{max} +1 @@"),
+        ] {
+            let patch = format!("--- a\n+++ b\n@@ -1 +1 @@\n-a\n+b\n{header}\n-a\n+b\n");
+            let err = parse(&patch).unwrap_err();
+            assert!(
+                matches!(err, ParsePatchError::InvalidRange { line: 6 }),
+                "{header}: {err}"
+            );
+            let err = parse_multiple(&patch).unwrap_err();
+            assert!(matches!(err, ParsePatchError::InvalidRange { line: 6 }));
+            let (_, errors) = parse_multiple_lenient(patch.as_str(), convert_cow_to_str);
+            assert!(matches!(
+                errors[0].kind(),
+                ParsePatchError::InvalidRange { line: 6 }
+            ));
+        }
+
+        // Ranges whose end fits but which don't match the hunk don't read past the input
+        let patch = "--- a\n+++ b\n@@ -1,4294967295 +1,1 @@\n-a\n+b\n";
+        parse(patch).unwrap_err();
+        let config = ParserConfig {
+            hunk_strategy: HunkRangeStrategy::Recount,
+            ..Default::default()
+        };
+        let recounted = parse_multiple_with_config(patch, config).unwrap();
+        assert_eq!(recounted[0].hunks()[0].old_range().len(), 1);
+
+        parse(&format!("--- a\n+++ b\n@@ -1,{max} +1,1 @@\n-a\n+b\n")).unwrap_err();
+    }
+
+    #[test]
+    fn parse_nth_file() {
+        let patch = "\
+From 1234 Mon Sep 17 00:00:00 2001
+Subject: [PATCH] Update files
+
+diff --git a/one.txt b/one.txt
+index 1111111..2222222 100644
+--- a/one.txt
++++ b/one.txt
+@@ -1,3 +1,3 @@
+ diff --git a/not-a-file b/not-a-file
+-old
++new
+ end
+diff --git a/old-name.txt b/new-name.txt
+similarity index 100%
+rename from old-name.txt
+rename to new-name.txt
+diff --git a/three.txt b/three.txt
+--- a/three.txt
++++ b/three.txt
+@@ -1 +1 @@
+-diff --git a/x b/x
++diff --git a/y b/y
+";
+
+        let mut all = parse_multiple(patch).unwrap();
+        // The commit message precedes the first file but isn't part of its section
+        assert_eq!(
+            all[0].preamble.take().as_deref(),
+            Some("From 1234 Mon Sep 17 00:00:00 2001\nSubject: [PATCH] Update files\n\n")
+        );
+        for (n, expected) in all.iter().enumerate() {
+            assert_eq!(parse_nth(patch, n).unwrap().as_ref(), Some(expected));
+            let bytes = parse_bytes_nth(patch.as_bytes(), n).unwrap().unwrap();
+            assert_eq!(bytes.modified(), expected.modified().map(str::as_bytes));
+        }
+        assert_eq!(
+            parse_nth(patch, 0).unwrap().unwrap().hunks()[0]
+                .lines()
+                .len(),
+            4
+        );
+        assert_eq!(
+            parse_nth(patch, 1).unwrap().unwrap().modified(),
+            Some("new-name.txt")
+        );
+        assert!(parse_nth(patch, 3).unwrap().is_none());
+
+        // Only the requested file is parsed, the last one has overlapping hunks
+        let broken = format!("{patch}@@ -1 +1 @@\n-z\n+w\n");
+        assert!(parse_multiple(&broken).is_err());
+        assert!(parse_nth(&broken, 0).is_ok());
+        assert!(parse_nth(&broken, 2).is_err());
+    }
+
+    #[test]
+    fn section_heading_is_kept_verbatim() {
+        for (header, heading) in [
+            ("@@ -1 +1 @@", None),
+            ("@@ -1 +1 @@ fn main()", Some("fn main()")),
+            ("@@ -1 +1 @@ ", Some("")),
+            ("@@ -1 +1 @@  fn main() ", Some(" fn main() ")),
+            ("@@ -1 +1 @@ \tfn main()\t", Some("\tfn main()\t")),
+            ("@@ -1 +1 @@ a @@ b", Some("a @@ b")),
+            // Only the text after `@@ ` is a heading
+            ("@@ -1 +1 @@\tfn main()", None),
+        ] {
+            let (_, _, function_context) = hunk_header((header, Some(LineEnd::Lf))).unwrap();
+            assert_eq!(
+                function_context.map(|(heading, _)| heading),
+                heading,
+                "{header:?}"
+            );
+
+            let patch = format!("{header}\n-a\n+b\n");
+            let diff = parse(&patch).unwrap();
+            assert_eq!(diff.hunks()[0].function_context().map(|(h, _)| h), heading);
+            if heading.is_some() {
+                assert_eq!(diff.to_string(), patch);
+            }
+
+            let crlf = patch.replace('\n', "\r\n");
+            let diff = parse_bytes(crlf.as_bytes()).unwrap();
+            let context = diff.hunks()[0].function_context();
+            assert_eq!(context.map(|(h, _)| h), heading.map(str::as_bytes));
+        }
+    }
 }
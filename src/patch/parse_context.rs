@@ -0,0 +1,500 @@
+//! Parse patches in the context diff format
+//!
+//! The context format is the output of `diff -c`. After `*** old` and `--- new` file headers, every
+//! hunk starts with a `***************` line and lists the lines of the old file after a
+//! `*** start,end ****` line, followed by the lines of the new file after a `--- start,end ----`
+//! line. Lines are prefixed by two characters:
+//!
+//! * `  ` for context lines, which appear on both sides
+//! * `- ` for deleted lines, which only appear on the old side
+//! * `+ ` for inserted lines, which only appear on the new side
+//! * `! ` for changed lines, where a block of lines on the old side is replaced by the block at the
+//!   same position on the new side
+//!
+//! A side without any deleted, inserted or changed lines is left out, as its lines are the context
+//! lines of the other side.
+
+use std::{borrow::Cow, iter::Peekable};
+
+use super::{
+    Diff, Hunk, HunkRange, Line, NO_NEWLINE_AT_EOF, ParsePatchError,
+    normal::range,
+    parse::{convert_cow_to_str, parse_filename, preamble, verify_hunks_in_order},
+};
+use crate::{
+    LineEnd,
+    utils::{LineIter, Text, strip_bom},
+};
+
+type Result<T, E = ParsePatchError> = std::result::Result<T, E>;
+
+/// The line separating the hunks of a context diff
+const HUNK_SEPARATOR: &str = "***************";
+
+/// Parse a patch in the context diff format
+///
+/// Like with unified diffs, the conventional `a/` and `b/` prefixes are stripped from the
+/// filenames in the headers.
+///
+/// ```
+/// use flickzeug::{apply, parse_context};
+///
+/// let patch = "\
+/// *** a/file.txt
+/// --- b/file.txt
+/// ***************
+/// *** 1,3 ****
+///   a
+/// ! b
+///   c
+/// --- 1,3 ----
+///   a
+/// ! B
+///   c
+/// ";
+/// let diff = parse_context(patch).unwrap();
+/// assert_eq!(diff.original(), Some("file.txt"));
+/// assert_eq!(apply("a\nb\nc\n", &diff).unwrap().0, "a\nB\nc\n");
+/// ```
+pub fn parse_context(input: &str) -> Result<Diff<'_, str>> {
+    let (original, modified, hunks, preamble) = parse_context_diff(input)?;
+    let mut diff = Diff::new(
+        original.map(convert_cow_to_str),
+        modified.map(convert_cow_to_str),
+        hunks,
+    );
+    diff.preamble = preamble;
+    Ok(diff)
+}
+
+/// Parse a potentially non-utf8 patch in the context diff format, see [`parse_context`]
+pub fn parse_context_bytes(input: &[u8]) -> Result<Diff<'_, [u8]>> {
+    let (original, modified, hunks, preamble) = parse_context_diff(input)?;
+    let mut diff = Diff::new(original, modified, hunks);
+    diff.preamble = preamble;
+    Ok(diff)
+}
+
+/// Returns `true` if `input` looks like a patch in the context diff format
+///
+/// This only checks for a hunk separator followed by the header of the old side of a hunk, the
+/// patch is not parsed.
+///
+/// ```
+/// use flickzeug::is_context_diff;
+///
+/// assert!(is_context_diff("***************\n*** 1 ****\n- a\n--- 0 ----\n"));
+/// assert!(!is_context_diff("@@ -1 +0,0 @@\n-a\n"));
+/// ```
+pub fn is_context_diff<T: Text + ?Sized>(input: &T) -> bool {
+    let mut lines = LineIter::new(strip_bom(input)).map(|(line, _)| line);
+    while lines.any(is_hunk_separator) {
+        if lines
+            .next()
+            .is_some_and(|line| side_header(line, "*** ", " ****").is_ok())
+        {
+            return true;
+        }
+    }
+    false
+}
+
+type Filename<'a> = Option<Cow<'a, [u8]>>;
+
+/// A line of one side of a hunk along with its marker
+type SideLine<'a, T> = (u8, (&'a T, Option<LineEnd>));
+
+#[allow(clippy::type_complexity)]
+fn parse_context_diff<'a, T: Text + ToOwned + ?Sized>(
+    input: &'a T,
+) -> Result<(Filename<'a>, Filename<'a>, Vec<Hunk<'a, T>>, Option<String>)> {
+    let mut lines = LineIter::new(strip_bom(input)).peekable();
+
+    // Keep anything before the file headers or the first hunk, like a `diff -c` command line, as
+    // the preamble
+    let mut preamble_lines = Vec::new();
+    while let Some(line) =
+        lines.next_if(|(line, _)| !line.starts_with("*** ") && !is_hunk_separator(*line))
+    {
+        preamble_lines.push(line);
+    }
+
+    let mut original = None;
+    let mut modified = None;
+    if let Some(&line) = lines.peek()
+        && line.0.starts_with("*** ")
+    {
+        lines.next();
+        original = parse_filename("*** ", line, true)?.map(|(filename, _)| filename);
+        let line = lines
+            .next_if(|(line, _)| line.starts_with("--- "))
+            .ok_or(ParsePatchError::UnableToParseFilename)?;
+        modified = parse_filename("--- ", line, true)?.map(|(filename, _)| filename);
+    }
+
+    let mut hunks = Vec::new();
+    while let Some((separator, end)) = lines.next_if(|(line, _)| is_hunk_separator(*line)) {
+        // `diff -p` adds the function containing the hunk after the separator
+        let function_context = separator
+            .strip_prefix(HUNK_SEPARATOR)
+            .and_then(|rest| rest.strip_prefix(" "))
+            .filter(|rest| !rest.is_empty())
+            .map(|context| (context, end));
+        hunks.push(hunk(&mut lines, function_context)?);
+    }
+
+    if hunks.is_empty() {
+        return Err(ParsePatchError::NoHunks);
+    }
+    if !verify_hunks_in_order(&hunks) {
+        return Err(ParsePatchError::HunksOrder);
+    }
+
+    Ok((original, modified, hunks, preamble(&preamble_lines)))
+}
+
+pub(super) fn is_hunk_separator<T: Text + ?Sized>(line: &T) -> bool {
+    line.strip_prefix(HUNK_SEPARATOR)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(" "))
+}
+
+/// Parse a hunk after its `***************` line
+fn hunk<'a, T: Text + ToOwned + ?Sized>(
+    lines: &mut Peekable<LineIter<'a, T>>,
+    function_context: Option<(&'a T, Option<LineEnd>)>,
+) -> Result<Hunk<'a, T>> {
+    let (line, _) = lines.next().ok_or(ParsePatchError::UnexpectedEof)?;
+    let old_header = side_header(line, "*** ", " ****")?;
+    let old = side_lines(lines, b'-')?;
+
+    let (line, _) = lines.next().ok_or(ParsePatchError::UnexpectedEof)?;
+    let new_header = side_header(line, "--- ", " ----")?;
+    let new = side_lines(lines, b'+')?;
+
+    // A side which was left out consists of the context lines of the other side
+    let context = |lines: &[SideLine<'a, T>]| {
+        lines
+            .iter()
+            .filter(|(marker, _)| *marker == b' ')
+            .copied()
+            .collect::<Vec<_>>()
+    };
+    let (old, new) = match (old.is_empty(), new.is_empty()) {
+        (true, false) => (context(&new), new),
+        (false, true) => {
+            let new = context(&old);
+            (old, new)
+        }
+        _ => (old, new),
+    };
+
+    let old_range = side_range(old_header, old.len())?;
+    let new_range = side_range(new_header, new.len())?;
+    let hunk_lines = merge_sides(&old, &new)?;
+
+    Ok(Hunk::new(
+        old_range,
+        new_range,
+        function_context,
+        hunk_lines,
+    ))
+}
+
+/// Parse the header of a side of a hunk, like `*** 3,5 ****`, into its first and last line
+fn side_header<T: Text + ?Sized>(line: &T, prefix: &str, suffix: &str) -> Result<(usize, usize)> {
+    let range_text = line
+        .strip_prefix(prefix)
+        .and_then(|line| line.strip_suffix(suffix))
+        .ok_or(ParsePatchError::HunkHeader)?;
+    range(range_text.as_bytes())
+}
+
+/// Convert the first and last line of a side into a `HunkRange` with `len` lines
+///
+/// A single line number refers to the line before the hunk if the side has no lines.
+fn side_range((start, end): (usize, usize), len: usize) -> Result<HunkRange> {
+    if len == 0 && start == end {
+        return Ok(HunkRange::new(start, 0));
+    }
+    let lines = end
+        .checked_sub(start)
+        .and_then(|lines| lines.checked_add(1))
+        .filter(|_| start.checked_add(len).is_some())
+        .ok_or(ParsePatchError::Range)?;
+    if lines != len {
+        return Err(ParsePatchError::HunkHeaderHunkMismatch);
+    }
+    Ok(HunkRange::new(start, len))
+}
+
+/// Read the lines of a side of a hunk along with their markers, which are `' '`, `'!'` or
+/// `change`
+fn side_lines<'a, T: Text + ?Sized>(
+    lines: &mut Peekable<LineIter<'a, T>>,
+    change: u8,
+) -> Result<Vec<SideLine<'a, T>>> {
+    let mut side = Vec::new();
+    while let Some((line, end)) = lines.next_if(|(line, _)| {
+        line.starts_with(NO_NEWLINE_AT_EOF) || content_marker(*line, change).is_some()
+    }) {
+        if line.starts_with(NO_NEWLINE_AT_EOF) {
+            let (_, (_, last_end)) = side
+                .last_mut()
+                .ok_or(ParsePatchError::UnexpectedNoNewlineAtEOF)?;
+            *last_end = None;
+            continue;
+        }
+
+        let marker = line.as_bytes()[0];
+        // Trailing whitespace may have been stripped from empty lines
+        let content = line.split_at(line.len().min(2)).1;
+        side.push((marker, (content, end)));
+    }
+    Ok(side)
+}
+
+/// Returns the marker of a content line of a side whose changes are marked by `change`
+fn content_marker<T: Text + ?Sized>(line: &T, change: u8) -> Option<u8> {
+    match *line.as_bytes() {
+        [marker] | [marker, b' ', ..] if [b' ', b'!', change].contains(&marker) => Some(marker),
+        _ => None,
+    }
+}
+
+/// Combine the two sides of a hunk into the lines of a `Hunk`
+///
+/// Context lines appear on both sides, deleted and inserted lines on one side only, and a block of
+/// changed lines on the old side is replaced by the next block of changed lines on the new side.
+fn merge_sides<'a, T: Text + ?Sized>(
+    old: &[SideLine<'a, T>],
+    new: &[SideLine<'a, T>],
+) -> Result<Vec<Line<'a, T>>> {
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    let (mut old, mut new) = (old.iter().peekable(), new.iter().peekable());
+
+    loop {
+        match (old.peek(), new.peek()) {
+            (Some((b'-', line)), _) => {
+                lines.push(Line::Delete(*line));
+                old.next();
+            }
+            (_, Some((b'+', line))) => {
+                lines.push(Line::Insert(*line));
+                new.next();
+            }
+            (Some((b'!', _)), _) | (_, Some((b'!', _))) => {
+                while let Some((_, line)) = old.next_if(|(marker, _)| *marker == b'!') {
+                    lines.push(Line::Delete(*line));
+                }
+                while let Some((_, line)) = new.next_if(|(marker, _)| *marker == b'!') {
+                    lines.push(Line::Insert(*line));
+                }
+            }
+            (Some((_, old_line)), Some((_, new_line))) if old_line.0 == new_line.0 => {
+                lines.push(Line::Context(*old_line));
+                old.next();
+                new.next();
+            }
+            (None, None) => return Ok(lines),
+            _ => return Err(ParsePatchError::HunkHeaderHunkMismatch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{is_context_diff, parse_context, parse_context_bytes, side_range};
+    use crate::{Line, LineEnd, ParsePatchError, apply, apply_bytes};
+
+    fn read_fixture(format: &str, name: &str, file: &str) -> String {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data")
+            .join(format)
+            .join(name)
+            .join(file);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn fixtures_apply() {
+        for name in ["add", "change", "delete", "mixed"] {
+            let patch = read_fixture("context-diff", name, "patch.diff");
+            let target = read_fixture("context-diff", name, "target.txt");
+            let result = read_fixture("context-diff", name, "result.txt");
+            assert!(is_context_diff(patch.as_str()), "{name}");
+
+            let diff = parse_context(&patch).unwrap();
+            assert_eq!(diff.original(), Some("target.txt"), "{name}");
+            assert_eq!(diff.modified(), Some("result.txt"), "{name}");
+            assert_eq!(apply(&target, &diff).unwrap().0, result, "{name}");
+
+            // Without the context lines, the changes are the same as in the normal diff
+            let normal = read_fixture("normal-diff", name, "patch.diff");
+            assert_eq!(diff.to_normal_string(), normal, "{name}");
+
+            let diff = parse_context_bytes(patch.as_bytes()).unwrap();
+            let (patched, _) = apply_bytes(target.as_bytes(), &diff).unwrap();
+            assert_eq!(patched, result.as_bytes(), "{name}");
+        }
+    }
+
+    #[test]
+    fn omitted_sides() {
+        // The old side of a pure insertion and the new side of a pure deletion are left out
+        let patch = "\
+***************
+*** 1,2 ****
+--- 1,3 ----
+  a
++ b
+  c
+***************
+*** 5,6 ****
+  e
+- f
+--- 6 ----
+";
+        let diff = parse_context(patch).unwrap();
+        assert_eq!(diff.original(), None);
+        let hunks = diff.hunks();
+        assert_eq!(
+            (
+                hunks[0].old_range().to_string(),
+                hunks[0].new_range().to_string()
+            ),
+            ("1,2".to_owned(), "1,3".to_owned())
+        );
+        assert_eq!(
+            hunks[0].lines(),
+            [
+                Line::Context(("a", Some(LineEnd::Lf))),
+                Line::Insert(("b", Some(LineEnd::Lf))),
+                Line::Context(("c", Some(LineEnd::Lf))),
+            ]
+        );
+        assert_eq!(
+            (
+                hunks[1].old_range().to_string(),
+                hunks[1].new_range().to_string()
+            ),
+            ("5,2".to_owned(), "6".to_owned())
+        );
+        assert_eq!(
+            apply("a\nc\nd\nd\ne\nf\n", &diff).unwrap().0,
+            "a\nb\nc\nd\nd\ne\n"
+        );
+
+        // Without context, a single line number is the line before an empty side
+        let diff = parse_context("***************\n*** 2 ****\n--- 3 ----\n+ x\n").unwrap();
+        assert_eq!(diff.hunks()[0].old_range().to_string(), "2,0");
+        assert_eq!(apply("a\nb\nc\n", &diff).unwrap().0, "a\nb\nx\nc\n");
+    }
+
+    #[test]
+    fn preamble() {
+        let patch =
+            "diff -c a b\n*** a\n--- b\n***************\n*** 1 ****\n! a\n--- 1 ----\n! b\n";
+        assert_eq!(
+            parse_context(patch).unwrap().preamble(),
+            Some("diff -c a b\n")
+        );
+        let diff = parse_context_bytes(patch.as_bytes()).unwrap();
+        assert_eq!(diff.preamble(), Some("diff -c a b\n"));
+
+        let diff = parse_context("***************\n*** 1 ****\n- a\n--- 0 ----\n").unwrap();
+        assert_eq!(diff.preamble(), None);
+    }
+
+    #[test]
+    fn missing_newline_and_function_context() {
+        let patch = "\
+*** old.c
+--- new.c
+*************** int main()
+*** 1,2 ****
+  a
+! b
+\\ No newline at end of file
+--- 1,2 ----
+  a
+! B
+";
+        let diff = parse_context(patch).unwrap();
+        let hunk = &diff.hunks()[0];
+        assert_eq!(
+            hunk.function_context(),
+            Some(("int main()", Some(LineEnd::Lf)))
+        );
+        assert_eq!(
+            hunk.lines(),
+            [
+                Line::Context(("a", Some(LineEnd::Lf))),
+                Line::Delete(("b", None)),
+                Line::Insert(("B", Some(LineEnd::Lf))),
+            ]
+        );
+        assert_eq!(apply("a\nb", &diff).unwrap().0, "a\nB\n");
+    }
+
+    #[test]
+    fn invalid_patches() {
+        for (patch, expected) in [
+            ("*** a\n--- b\n", ParsePatchError::NoHunks),
+            (
+                "***************\n*** 1,3 ****\n- a\n--- 0 ----\n",
+                ParsePatchError::HunkHeaderHunkMismatch,
+            ),
+            (
+                "***************\n*** 1 ****\n  a\n--- 1 ----\n  b\n",
+                ParsePatchError::HunkHeaderHunkMismatch,
+            ),
+            (
+                "***************\n@@ -1 +1 @@\n",
+                ParsePatchError::HunkHeader,
+            ),
+            (
+                "***************\n*** 3 ****\n- c\n--- 2 ----\n***************\n*** 1 ****\n- a\n--- 0 ----\n",
+                ParsePatchError::HunksOrder,
+            ),
+            (
+                "***************\n*** 1 ****\n\\ No newline at end of file\n--- 1 ----\n",
+                ParsePatchError::UnexpectedNoNewlineAtEOF,
+            ),
+        ] {
+            let err = parse_context(patch).unwrap_err();
+            assert_eq!(
+                std::mem::discriminant(&err),
+                std::mem::discriminant(&expected),
+                "{patch:?}: {err}"
+            );
+        }
+
+        assert!(!is_context_diff("*** a\n--- b\n"));
+        assert!(!is_context_diff("1d0\n< a\n"));
+    }
+
+    #[test]
+    fn huge_ranges_dont_overflow() {
+        let max = usize::MAX;
+        for patch in [
+            format!("***************\n*** 0,{max} ****\n- a\n--- 0 ----\n"),
+            format!("***************\n*** {max} ****\n- a\n--- 0 ----\n"),
+            format!("***************\n*** 1 ****\n- a\n--- {max} ----\n"),
+        ] {
+            assert!(parse_context(&patch).is_err(), "{patch:?}");
+            assert!(parse_context_bytes(patch.as_bytes()).is_err(), "{patch:?}");
+            assert!(crate::parse_auto(&patch).is_err(), "{patch:?}");
+        }
+
+        assert!(matches!(
+            side_range((0, max), 1),
+            Err(ParsePatchError::Range)
+        ));
+        assert!(matches!(
+            side_range((max, max), 1),
+            Err(ParsePatchError::Range)
+        ));
+    }
+}
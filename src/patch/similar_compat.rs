@@ -0,0 +1,143 @@
+//! Conversions from the diff types of the [`similar`] crate
+
+use std::borrow::Cow;
+
+use similar::{DiffTag, DiffableStr, TextDiff};
+
+use super::{Diff, Hunk, HunkRange, Line};
+use crate::{LineEnd, utils::Text};
+
+/// The number of context lines `similar` uses for unified diffs by default
+const DEFAULT_CONTEXT_LEN: usize = 3;
+
+impl<'a, T> Diff<'a, T>
+where
+    T: DiffableStr + Text + ToOwned + ?Sized,
+{
+    /// Convert a line based [`similar::TextDiff`] into a `Diff`, keeping `context_len` lines of
+    /// context around each change
+    ///
+    /// The `TextDiff` must have been created with line granularity (e.g. using
+    /// [`TextDiff::from_lines`]), as every slice of it becomes one line of the `Diff`. The
+    /// resulting `Diff` has no filenames.
+    ///
+    /// ```
+    /// use flickzeug::{Diff, apply};
+    /// use similar::TextDiff;
+    ///
+    /// let original = "alpha\nbeta\ngamma\n";
+    /// let modified = "alpha\nBETA\ngamma\n";
+    ///
+    /// let text_diff = TextDiff::from_lines(original, modified);
+    /// let diff = Diff::from_similar(&text_diff, 3);
+    ///
+    /// assert_eq!(apply(original, &diff).unwrap().0, modified);
+    /// ```
+    pub fn from_similar(diff: &TextDiff<'a, 'a, '_, T>, context_len: usize) -> Self {
+        let old = diff.old_slices();
+        let new = diff.new_slices();
+
+        let hunks = diff
+            .grouped_ops(context_len)
+            .iter()
+            .filter_map(|ops| {
+                let (first, last) = (ops.first()?, ops.last()?);
+                let old_range = first.old_range().start..last.old_range().end;
+                let new_range = first.new_range().start..last.new_range().end;
+
+                let mut lines = Vec::new();
+                for op in ops {
+                    let (tag, old_lines, new_lines) = op.as_tag_tuple();
+                    match tag {
+                        DiffTag::Equal => lines.extend(
+                            old[old_lines]
+                                .iter()
+                                .map(|l| Line::Context(LineEnd::strip(*l))),
+                        ),
+                        DiffTag::Delete => lines.extend(
+                            old[old_lines]
+                                .iter()
+                                .map(|l| Line::Delete(LineEnd::strip(*l))),
+                        ),
+                        DiffTag::Insert => lines.extend(
+                            new[new_lines]
+                                .iter()
+                                .map(|l| Line::Insert(LineEnd::strip(*l))),
+                        ),
+                        DiffTag::Replace => {
+                            lines.extend(
+                                old[old_lines]
+                                    .iter()
+                                    .map(|l| Line::Delete(LineEnd::strip(*l))),
+                            );
+                            lines.extend(
+                                new[new_lines]
+                                    .iter()
+                                    .map(|l| Line::Insert(LineEnd::strip(*l))),
+                            );
+                        }
+                    }
+                }
+
+                Some(Hunk::new(
+                    HunkRange::from_index(old_range.start, old_range.len()),
+                    HunkRange::from_index(new_range.start, new_range.len()),
+                    None,
+                    lines,
+                ))
+            })
+            .collect();
+
+        Diff::new(None::<Cow<'a, T>>, None::<Cow<'a, T>>, hunks)
+    }
+}
+
+/// Converts the diff using the same amount of context as `similar`'s unified diffs
+impl<'a, T> From<&TextDiff<'a, 'a, '_, T>> for Diff<'a, T>
+where
+    T: DiffableStr + Text + ToOwned + ?Sized,
+{
+    fn from(diff: &TextDiff<'a, 'a, '_, T>) -> Self {
+        Self::from_similar(diff, DEFAULT_CONTEXT_LEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use similar::TextDiff;
+
+    use crate::{Diff, Line, LineEnd, apply, create_patch};
+
+    #[test]
+    fn matches_own_diff() {
+        let original = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n";
+        let modified = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n13\n14\n15\nsixteen";
+
+        let text_diff = TextDiff::from_lines(original, modified);
+        let diff = Diff::from(&text_diff);
+        assert_eq!(diff.hunks(), create_patch(original, modified).hunks());
+        assert_eq!(apply(original, &diff).unwrap().0, modified);
+
+        let diff = Diff::from_similar(&text_diff, 0);
+        assert_eq!(diff.hunks().len(), 3);
+        assert_eq!(apply(original, &diff).unwrap().0, modified);
+    }
+
+    #[test]
+    fn bytes_and_empty_sides() {
+        let modified = b"a\r\nb\r\n".as_slice();
+        let text_diff = TextDiff::from_lines(b"".as_slice(), modified);
+        let diff = Diff::from(&text_diff);
+        assert_eq!(diff.hunks()[0].old_range().to_string(), "0,0");
+        assert_eq!(
+            diff.hunks()[0].lines(),
+            [
+                Line::Insert((b"a".as_slice(), Some(LineEnd::CrLf))),
+                Line::Insert((b"b".as_slice(), Some(LineEnd::CrLf))),
+            ]
+        );
+
+        let text_diff = TextDiff::from_lines("same\n", "same\n");
+        assert!(Diff::from(&text_diff).hunks().is_empty());
+    }
+}
@@ -1,16 +1,18 @@
 //! Common utilities
 
 use std::{
+    borrow::Cow,
     collections::{HashMap, hash_map::Entry},
     hash::Hash,
 };
 
-use crate::LineEnd;
+use crate::{LineEnd, MatchOptions};
 
 /// Classifies lines, converting lines into unique `u64`s for quicker comparison
 pub struct Classifier<'a, T: ?Sized> {
     next_id: u64,
     unique_ids: HashMap<(&'a T, Option<LineEnd>), u64>,
+    ignore_cr_at_eol: bool,
 }
 
 impl<'a, T: ?Sized + Eq + Hash> Classifier<'a, T> {
@@ -29,20 +31,77 @@ impl<'a, T: ?Sized + Eq + Hash> Classifier<'a, T> {
 impl<'a, T: std::fmt::Debug + ?Sized + Text> Classifier<'a, T> {
     pub fn classify_lines(&mut self, text: &'a T) -> (Vec<(&'a T, Option<LineEnd>)>, Vec<u64>) {
         LineIter::new(text)
-            .map(|line| (line, self.classify(line)))
+            .map(|line| {
+                let key = match line {
+                    // Lines only differing by a CRLF or LF line ending are classified as the same
+                    // line, as are final lines without a newline only differing by a trailing
+                    // `\r`. A missing final newline is still a change.
+                    (content, Some(_)) if self.ignore_cr_at_eol => (content, Some(LineEnd::Lf)),
+                    (content, None) if self.ignore_cr_at_eol => {
+                        (content.strip_suffix("\r").unwrap_or(content), None)
+                    }
+                    line => line,
+                };
+                (line, self.classify(key))
+            })
             .unzip()
     }
 }
 
+impl<T: Eq + Hash + ?Sized> Classifier<'_, T> {
+    /// Construct a classifier which treats lines only differing by a trailing carriage return as
+    /// equal
+    pub fn ignoring_cr_at_eol() -> Self {
+        Self {
+            ignore_cr_at_eol: true,
+            ..Self::default()
+        }
+    }
+}
+
 impl<T: Eq + Hash + ?Sized> Default for Classifier<'_, T> {
     fn default() -> Self {
         Self {
             next_id: 0,
             unique_ids: HashMap::default(),
+            ignore_cr_at_eol: false,
         }
     }
 }
 
+/// Remove the UTF-8 byte order mark some editors write at the start of a file
+pub fn strip_bom<T: Text + ?Sized>(text: &T) -> &T {
+    text.strip_prefix("\u{feff}").unwrap_or(text)
+}
+
+/// Replace every tab with the spaces up to the next tab stop, using tab stops every `width`
+/// columns
+///
+/// Every character counts as one column, so the continuation bytes of utf8 sequences aren't
+/// counted. A width of `0` is treated like `1`.
+pub fn expand_tabs(line: &[u8], width: usize) -> Cow<'_, [u8]> {
+    if !line.contains(&b'\t') {
+        return Cow::Borrowed(line);
+    }
+
+    let width = width.max(1);
+    let mut expanded = Vec::with_capacity(line.len());
+    let mut column = 0;
+    for &byte in line {
+        if byte == b'\t' {
+            let spaces = width - column % width;
+            expanded.extend(std::iter::repeat_n(b' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(byte);
+            if byte & 0b1100_0000 != 0b1000_0000 {
+                column += 1;
+            }
+        }
+    }
+    Cow::Owned(expanded)
+}
+
 /// Iterator over the lines of a string, including the `\n` character.
 pub struct LineIter<'a, T: ?Sized>(&'a T);
 
@@ -50,6 +109,15 @@ impl<'a, T: ?Sized> LineIter<'a, T> {
     pub fn new(text: &'a T) -> Self {
         Self(text)
     }
+
+    /// Iterate over the lines along with the byte offsets of their start and end, see
+    /// [`LineOffsets`]
+    pub fn with_offsets(self) -> LineOffsets<'a, T> {
+        LineOffsets {
+            lines: self,
+            offset: 0,
+        }
+    }
 }
 
 impl<'a, T: Text + ?Sized> Iterator for LineIter<'a, T> {
@@ -83,6 +151,27 @@ impl<'a, T: Text + ?Sized> Iterator for LineIter<'a, T> {
     }
 }
 
+/// Iterator over the lines of a string along with their byte offsets in it
+///
+/// Yields `(line, start, end)`, where `line` doesn't include the line ending but `start..end`
+/// does, so the start of each line is the end of the previous one.
+pub struct LineOffsets<'a, T: ?Sized> {
+    lines: LineIter<'a, T>,
+    offset: usize,
+}
+
+impl<'a, T: Text + ?Sized> Iterator for LineOffsets<'a, T> {
+    type Item = (&'a T, usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let remaining = self.lines.0.len();
+        let (line, _) = self.lines.next()?;
+        let start = self.offset;
+        self.offset += remaining - self.lines.0.len();
+        Some((line, start, self.offset))
+    }
+}
+
 /// A helper trait for processing text like `str` and `[u8]`
 /// Useful for abstracting over those types for parsing as well as breaking input into lines
 pub trait Text: Eq + Hash {
@@ -101,6 +190,14 @@ pub trait Text: Eq + Hash {
     #[allow(unused)]
     fn lines(&self) -> LineIter<'_, Self>;
 
+    /// Returns `true` if both lines are equal under the given options
+    fn matches(&self, other: &Self, options: &MatchOptions) -> bool {
+        self == other
+            || (*options != MatchOptions::default()
+                && options.normalize_bytes(self.as_bytes())
+                    == options.normalize_bytes(other.as_bytes()))
+    }
+
     fn parse<T: std::str::FromStr>(&self) -> Option<T> {
         self.as_str().and_then(|s| s.parse().ok())
     }
@@ -240,3 +337,36 @@ fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
 fn find_byte(haystack: &[u8], byte: u8) -> Option<usize> {
     haystack.iter().position(|&b| b == byte)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::LineIter;
+
+    #[test]
+    fn line_offsets() {
+        let offsets =
+            |text: &'static str| -> Vec<_> { LineIter::new(text).with_offsets().collect() };
+
+        assert_eq!(offsets(""), []);
+        assert_eq!(
+            offsets("a\nbc\n\nd"),
+            [("a", 0, 2), ("bc", 2, 5), ("", 5, 6), ("d", 6, 7)]
+        );
+        assert_eq!(
+            offsets("a\r\nbc\r\n\r\nd\r\n"),
+            [("a", 0, 3), ("bc", 3, 7), ("", 7, 9), ("d", 9, 12)]
+        );
+
+        for text in ["one\ntwo\r\nthree", "x\r\n\n\r\ny\n"] {
+            let lines: Vec<_> = LineIter::new(text.as_bytes()).with_offsets().collect();
+            let mut previous_end = 0;
+            for (line, start, end) in lines {
+                assert_eq!(start, previous_end);
+                assert!(end > start);
+                assert_eq!(&text.as_bytes()[start..start + line.len()], line);
+                previous_end = end;
+            }
+            assert_eq!(previous_end, text.len());
+        }
+    }
+}